@@ -0,0 +1,47 @@
+use std::env;
+use std::io::{stderr, Write};
+
+use buildkit_client::{Client, ProgressEvent, SolveOptions, DEFAULT_ADDRESS};
+use buildkit_llb::prelude::*;
+use failure::Error;
+
+// Build a small graph on the daemon from `BUILDKIT_HOST` and print its progress:
+//
+//   BUILDKIT_HOST=tcp://127.0.0.1:1234 cargo run --example=solve
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let address = env::var("BUILDKIT_HOST").unwrap_or_else(|_| DEFAULT_ADDRESS.into());
+    let client = Client::connect(address).await?;
+
+    let alpine = Source::image("library/alpine:latest");
+    let command = Command::run("/bin/sh")
+        .args(["-c", "echo 'hello from buildkit' | tee /out/greeting"])
+        .mount(Mount::ReadOnlyLayer(alpine.output(), "/"))
+        .mount(Mount::Scratch(OutputIdx(0), "/out"))
+        .custom_name("greet");
+
+    let definition = Terminal::with(command.output(0)).into_definition();
+    let options = SolveOptions::definition(definition);
+
+    client
+        .solve(options, |event| match event {
+            ProgressEvent::Vertex(ref vertex) if vertex.is_completed() => {
+                let status = match vertex.error {
+                    Some(ref error) => error.as_str(),
+                    None if vertex.cached => "cached",
+                    None => "done",
+                };
+
+                eprintln!("{}: {}", vertex.name, status);
+            }
+
+            ProgressEvent::Log(log) => {
+                stderr().write_all(&log.data).ok();
+            }
+
+            _ => {}
+        })
+        .await?;
+
+    Ok(())
+}
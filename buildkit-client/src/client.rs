@@ -0,0 +1,840 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::channel::{mpsc, oneshot};
+use futures::future::{select, try_join, Either};
+use futures::pin_mut;
+use log::*;
+use tokio::net::UnixStream;
+use tonic::transport::{Channel, Endpoint, Uri};
+use tonic::{Request, Streaming};
+use tower::service_fn;
+
+use buildkit_proto::moby::buildkit::v1::control_client::ControlClient;
+use buildkit_proto::moby::buildkit::v1::{
+    CacheOptionsEntry, DiskUsageRequest, SolveRequest, StatusRequest, StatusResponse,
+};
+use buildkit_proto::pb;
+
+use crate::error::ClientError;
+use crate::progress::{events, ProgressEvent};
+use crate::session::{Session, StreamIo};
+use crate::usage::{CacheRecord, PruneOptions};
+
+/// Where `buildkitd` listens by default.
+pub const DEFAULT_ADDRESS: &str = "unix:///run/buildkit/buildkitd.sock";
+
+/// Client of the daemon `Control` API, the one `buildctl` uses.
+#[derive(Clone)]
+pub struct Client {
+    inner: ControlClient<Channel>,
+}
+
+/// What to build and how to export the result.
+#[derive(Debug, Clone)]
+pub struct SolveOptions {
+    request: SolveRequest,
+}
+
+/// Progress updates of a single solve.
+pub struct Progress {
+    inner: Streaming<StatusResponse>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client").finish()
+    }
+}
+
+impl Client {
+    /// Connect to the daemon at `unix://<path>` or `tcp://<host>:<port>`, e.g. the `BUILDKIT_HOST` value.
+    pub async fn connect<S>(address: S) -> Result<Self, ClientError>
+    where
+        S: AsRef<str>,
+    {
+        let address = address.as_ref();
+        debug!("connecting to {}", address);
+
+        let channel = if let Some(path) = address.strip_prefix("unix://") {
+            let path = PathBuf::from(path);
+            let connector = service_fn(move |_: Uri| UnixStream::connect(path.clone()));
+
+            Endpoint::from_static("http://[::]:50051")
+                .connect_with_connector(connector)
+                .await
+        } else if let Some(host) = address.strip_prefix("tcp://") {
+            Endpoint::from_shared(format!("http://{}", host))
+                .map_err(|_| ClientError::InvalidAddress(address.into()))?
+                .connect()
+                .await
+        } else {
+            return Err(ClientError::InvalidAddress(address.into()));
+        };
+
+        Ok(Self::with_channel(channel.map_err(ClientError::Connect)?))
+    }
+
+    /// Use an already configured channel, e.g. with TLS.
+    pub fn with_channel(channel: Channel) -> Self {
+        Self {
+            inner: ControlClient::new(channel),
+        }
+    }
+
+    /// Run the solve and pass its progress to `on_progress` until it's finished.
+    /// Returns the exporter response, e.g. `containerimage.digest`.
+    pub async fn solve<F>(
+        &self,
+        options: SolveOptions,
+        mut on_progress: F,
+    ) -> Result<HashMap<String, String>, ClientError>
+    where
+        F: FnMut(ProgressEvent),
+    {
+        let mut inner = self.inner.clone();
+        let request = options.request;
+        let id = request.r#ref.clone();
+
+        let solve = async move {
+            inner
+                .solve(request)
+                .await
+                .map_err(ClientError::rpc("solve"))
+        };
+
+        // The daemon closes the status stream once the solve is done. When the solve fails early,
+        // the job might never be registered and the status call stays pending, so it's dropped.
+        let progress = async {
+            let mut progress = self.status(id).await?;
+
+            while let Some(events) = progress.next().await? {
+                events.into_iter().for_each(&mut on_progress);
+            }
+
+            Ok::<_, ClientError>(())
+        };
+
+        let (response, _) = try_join(solve, progress).await?;
+
+        Ok(response.into_inner().exporter_response)
+    }
+
+    /// Run the solve while exposing `session` to the daemon, e.g. for `Source::local` contexts.
+    pub async fn solve_with_session<F>(
+        &self,
+        options: SolveOptions,
+        session: Session,
+        on_progress: F,
+    ) -> Result<HashMap<String, String>, ClientError>
+    where
+        F: FnMut(ProgressEvent),
+    {
+        let options = options.session(session.id());
+        let solve = self.solve(options, on_progress);
+        let session = self.session(session);
+
+        pin_mut!(solve, session);
+
+        // The session is dropped together with the tunnel once the solve is done.
+        match select(solve, session).await {
+            Either::Left((response, _)) => response,
+            Either::Right((Ok(()), solve)) => solve.await,
+            Either::Right((Err(error), _)) => Err(error),
+        }
+    }
+
+    /// Expose the session to the daemon until either side closes the connection.
+    pub async fn session(&self, session: Session) -> Result<(), ClientError> {
+        let (sender, receiver) = mpsc::channel(16);
+        let mut request = Request::new(receiver);
+
+        session.write_headers(request.metadata_mut())?;
+
+        let response = {
+            self.inner
+                .clone()
+                .session(request)
+                .await
+                .map_err(ClientError::rpc("session"))?
+        };
+
+        // Dropping the future closes the tunnel.
+        let (_close, closed) = oneshot::channel();
+
+        session
+            .serve(StreamIo::new(response.into_inner(), sender, closed))
+            .await
+    }
+
+    /// Follow the progress of a solve started elsewhere, by its `SolveOptions::id`.
+    pub async fn status<S>(&self, id: S) -> Result<Progress, ClientError>
+    where
+        S: Into<String>,
+    {
+        let request = StatusRequest { r#ref: id.into() };
+        let response = {
+            self.inner
+                .clone()
+                .status(request)
+                .await
+                .map_err(ClientError::rpc("status"))?
+        };
+
+        Ok(Progress {
+            inner: response.into_inner(),
+        })
+    }
+
+    /// Build cache records matching all the filters, e.g. `type==regular`.
+    pub async fn disk_usage(&self, filters: &[&str]) -> Result<Vec<CacheRecord>, ClientError> {
+        let request = DiskUsageRequest {
+            filter: filters.iter().map(|filter| filter.to_string()).collect(),
+        };
+
+        let response = {
+            self.inner
+                .clone()
+                .disk_usage(request)
+                .await
+                .map_err(ClientError::rpc("disk_usage"))?
+        };
+
+        Ok(response
+            .into_inner()
+            .record
+            .into_iter()
+            .map(CacheRecord::from)
+            .collect())
+    }
+
+    /// Remove build cache and return the removed records.
+    pub async fn prune(&self, options: PruneOptions) -> Result<Vec<CacheRecord>, ClientError> {
+        let mut records = {
+            self.inner
+                .clone()
+                .prune(options.into_request())
+                .await
+                .map_err(ClientError::rpc("prune"))?
+                .into_inner()
+        };
+
+        let mut removed = vec![];
+
+        while let Some(record) = records.message().await.map_err(ClientError::rpc("prune"))? {
+            removed.push(record.into());
+        }
+
+        Ok(removed)
+    }
+}
+
+impl SolveOptions {
+    /// Solve the LLB graph, e.g. from `Terminal::into_definition`.
+    pub fn definition(definition: pb::Definition) -> Self {
+        Self::with_request(SolveRequest {
+            definition: Some(definition),
+            ..Default::default()
+        })
+    }
+
+    /// Let the frontend create the graph, e.g. `dockerfile.v0` or `gateway.v0`.
+    pub fn frontend<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::with_request(SolveRequest {
+            frontend: name.into(),
+            ..Default::default()
+        })
+    }
+
+    fn with_request(request: SolveRequest) -> Self {
+        Self {
+            request: SolveRequest {
+                r#ref: unique_id(),
+                ..request
+            },
+        }
+    }
+
+    /// Frontend option, e.g. `filename` or `build-arg:VERSION`.
+    pub fn frontend_attr<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.request.frontend_attrs.insert(key.into(), value.into());
+        self
+    }
+
+    /// Export the result, e.g. with `image`, `local` or `oci` exporter. Nothing is exported by default.
+    pub fn exporter<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.request.exporter = name.into();
+        self
+    }
+
+    /// Exporter option, e.g. `name` and `push` for the `image` exporter.
+    pub fn exporter_attr<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.request.exporter_attrs.insert(key.into(), value.into());
+        self
+    }
+
+    /// Import cache of the type (`registry` or `local`) with the attributes, e.g. `ref`.
+    pub fn cache_import<S, I, K, V>(mut self, kind: S, attrs: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let entry = cache_entry(kind, attrs);
+
+        self.request
+            .cache
+            .get_or_insert_with(Default::default)
+            .imports
+            .push(entry);
+        self
+    }
+
+    /// Export cache of the type (`registry`, `local` or `inline`) with the attributes, e.g. `mode=max`.
+    pub fn cache_export<S, I, K, V>(mut self, kind: S, attrs: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let entry = cache_entry(kind, attrs);
+
+        self.request
+            .cache
+            .get_or_insert_with(Default::default)
+            .exports
+            .push(entry);
+        self
+    }
+
+    /// Allow the privileged operation, e.g. `network.host` or `security.insecure`.
+    /// The daemon has to allow it as well.
+    pub fn entitlement<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.request.entitlements.push(name.into());
+        self
+    }
+
+    /// Session that provides local sources, secrets and SSH agents to the solve.
+    pub fn session<S>(mut self, id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.request.session = id.into();
+        self
+    }
+
+    /// Replace the generated solve id.
+    pub fn with_id<S>(mut self, id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.request.r#ref = id.into();
+        self
+    }
+
+    /// Id to follow the solve with `Client::status`.
+    pub fn id(&self) -> &str {
+        &self.request.r#ref
+    }
+}
+
+impl Progress {
+    /// Next batch of events, or `None` when the solve is finished.
+    pub async fn next(&mut self) -> Result<Option<Vec<ProgressEvent>>, ClientError> {
+        let response = {
+            self.inner
+                .message()
+                .await
+                .map_err(ClientError::rpc("status"))?
+        };
+
+        Ok(response.map(events))
+    }
+}
+
+fn cache_entry<S, I, K, V>(kind: S, attrs: I) -> CacheOptionsEntry
+where
+    S: Into<String>,
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<String>,
+{
+    CacheOptionsEntry {
+        r#type: kind.into(),
+        attrs: {
+            attrs
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect()
+        },
+    }
+}
+
+/// Solve and session ids only have to be unique within the daemon.
+pub(crate) fn unique_id() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default()
+    };
+
+    format!(
+        "{:x}{:x}{:x}",
+        nanos,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use std::vec;
+
+    use chrono::prelude::*;
+    use futures::{future, stream};
+    use prost_types::Timestamp;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::time;
+    use tonic::transport::server::Connected;
+    use tonic::transport::Server;
+    use tonic::{Code, Request, Response, Status};
+
+    use buildkit_llb::prelude::*;
+    use buildkit_proto::moby::buildkit::v1::control_server::{Control, ControlServer};
+    use buildkit_proto::moby::buildkit::v1::{
+        BytesMessage, DiskUsageResponse, ListWorkersRequest, ListWorkersResponse, PruneRequest,
+        SolveResponse, UsageRecord, Vertex, VertexLog, VertexStatus,
+    };
+
+    use super::*;
+    use crate::LogStream;
+
+    /// Daemon stub that answers with canned responses and records the requests.
+    #[derive(Default)]
+    struct StubControl {
+        solves: Arc<Mutex<Vec<SolveRequest>>>,
+        prunes: Arc<Mutex<Vec<PruneRequest>>>,
+        sessions: Arc<Mutex<Vec<String>>>,
+        pending_status: bool,
+    }
+
+    type StubStream<T> = stream::Iter<vec::IntoIter<Result<T, Status>>>;
+
+    /// Accepted connection of the stub. tonic only serves connections that implement `Connected`.
+    struct StubSocket(UnixStream);
+
+    #[tonic::async_trait]
+    impl Control for StubControl {
+        type PruneStream = StubStream<UsageRecord>;
+        type StatusStream = StubStream<StatusResponse>;
+        type SessionStream = StubStream<BytesMessage>;
+
+        async fn disk_usage(
+            &self,
+            request: Request<DiskUsageRequest>,
+        ) -> Result<Response<DiskUsageResponse>, Status> {
+            assert_eq!(request.into_inner().filter, vec!["type==regular"]);
+
+            Ok(Response::new(DiskUsageResponse {
+                record: usage_records(),
+            }))
+        }
+
+        async fn prune(
+            &self,
+            request: Request<PruneRequest>,
+        ) -> Result<Response<Self::PruneStream>, Status> {
+            self.prunes.lock().unwrap().push(request.into_inner());
+
+            let removed = {
+                usage_records()
+                    .into_iter()
+                    .filter(|record| !record.in_use)
+                    .map(Ok)
+                    .collect::<Vec<_>>()
+            };
+
+            Ok(Response::new(stream::iter(removed)))
+        }
+
+        async fn solve(
+            &self,
+            request: Request<SolveRequest>,
+        ) -> Result<Response<SolveResponse>, Status> {
+            let request = request.into_inner();
+            self.solves.lock().unwrap().push(request.clone());
+
+            if request.definition.is_none() {
+                return Err(Status::invalid_argument(format!(
+                    "unsupported frontend {}",
+                    request.frontend
+                )));
+            }
+
+            let mut response = SolveResponse::default();
+            response
+                .exporter_response
+                .insert("containerimage.digest".into(), "sha256:image".into());
+
+            Ok(Response::new(response))
+        }
+
+        async fn status(
+            &self,
+            request: Request<StatusRequest>,
+        ) -> Result<Response<Self::StatusStream>, Status> {
+            assert_eq!(request.into_inner().r#ref, "test-solve");
+
+            if self.pending_status {
+                future::pending::<()>().await;
+            }
+
+            let now = Timestamp {
+                seconds: 1_583_280_000,
+                nanos: 0,
+            };
+
+            let vertex = Vertex {
+                digest: "sha256:source".into(),
+                name: "docker-image://docker.io/library/alpine:latest".into(),
+                started: Some(now.clone()),
+                ..Default::default()
+            };
+
+            let responses = vec![
+                Ok(StatusResponse {
+                    vertexes: vec![vertex.clone()],
+                    ..Default::default()
+                }),
+                Ok(StatusResponse {
+                    statuses: vec![VertexStatus {
+                        id: "resolve".into(),
+                        vertex: vertex.digest.clone(),
+                        current: 5,
+                        total: 10,
+                        timestamp: Some(now.clone()),
+                        ..Default::default()
+                    }],
+
+                    logs: vec![VertexLog {
+                        vertex: vertex.digest.clone(),
+                        timestamp: Some(now.clone()),
+                        stream: 1,
+                        msg: b"hello\n".to_vec(),
+                    }],
+
+                    ..Default::default()
+                }),
+                Ok(StatusResponse {
+                    vertexes: vec![Vertex {
+                        completed: Some(now),
+                        ..vertex
+                    }],
+
+                    ..Default::default()
+                }),
+            ];
+
+            Ok(Response::new(stream::iter(responses)))
+        }
+
+        async fn session(
+            &self,
+            request: Request<Streaming<BytesMessage>>,
+        ) -> Result<Response<Self::SessionStream>, Status> {
+            let id = {
+                request
+                    .metadata()
+                    .get("x-docker-expose-session-uuid")
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+            };
+
+            self.sessions.lock().unwrap().push(id.into());
+            Err(Status::unimplemented("sessions are not supported"))
+        }
+
+        async fn list_workers(
+            &self,
+            _: Request<ListWorkersRequest>,
+        ) -> Result<Response<ListWorkersResponse>, Status> {
+            Ok(Response::new(ListWorkersResponse::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn solving() {
+        let stub = StubControl::default();
+        let solves = stub.solves.clone();
+        let client = serve("solving", stub).await;
+
+        let definition = Terminal::with(Source::image("alpine").output()).into_definition();
+        let options = {
+            SolveOptions::definition(definition.clone())
+                .exporter("image")
+                .exporter_attr("name", "docker.io/library/test:latest")
+                .cache_export(
+                    "registry",
+                    vec![("ref", "localhost:5000/cache"), ("mode", "max")],
+                )
+                .with_id("test-solve")
+        };
+
+        let mut events = vec![];
+        let response = client.solve(options, |event| events.push(event)).await;
+
+        assert_eq!(
+            response.unwrap().get("containerimage.digest").unwrap(),
+            "sha256:image"
+        );
+
+        let request = solves.lock().unwrap().pop().unwrap();
+        assert_eq!(request.r#ref, "test-solve");
+        assert_eq!(request.definition, Some(definition));
+        assert_eq!(request.exporter, "image");
+        assert_eq!(
+            request.exporter_attrs.get("name").unwrap(),
+            "docker.io/library/test:latest"
+        );
+        assert_eq!(request.cache.unwrap().exports[0].r#type, "registry");
+
+        assert_eq!(events.len(), 4);
+
+        match (&events[0], &events[3]) {
+            (ProgressEvent::Vertex(started), ProgressEvent::Vertex(completed)) => {
+                assert!(!started.is_completed());
+                assert!(completed.is_completed());
+                assert_eq!(started.digest, completed.digest);
+            }
+
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        match (&events[1], &events[2]) {
+            (ProgressEvent::Status(status), ProgressEvent::Log(log)) => {
+                assert_eq!((status.current, status.total), (5, 10));
+                assert_eq!(log.stream, LogStream::Stdout);
+                assert_eq!(log.data, b"hello\n");
+            }
+
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn solve_errors() {
+        let client = serve("solve-errors", StubControl::default()).await;
+        let options = {
+            SolveOptions::frontend("unknown.v0")
+                .frontend_attr("filename", "Dockerfile")
+                .with_id("test-solve")
+        };
+
+        let error = client.solve(options, |_| {}).await.unwrap_err();
+
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(
+            error.to_string(),
+            "solve failed with InvalidArgument: unsupported frontend unknown.v0"
+        );
+    }
+
+    #[tokio::test]
+    async fn solve_errors_with_pending_status() {
+        let stub = StubControl {
+            pending_status: true,
+            ..Default::default()
+        };
+
+        let client = serve("solve-errors-pending-status", stub).await;
+        let options = SolveOptions::frontend("unknown.v0").with_id("test-solve");
+
+        let error = {
+            time::timeout(Duration::from_secs(5), client.solve(options, |_| {}))
+                .await
+                .expect("solve error should cancel the status call")
+                .unwrap_err()
+        };
+
+        assert_eq!(error.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn session_errors() {
+        let stub = StubControl::default();
+        let sessions = stub.sessions.clone();
+        let client = serve("session-errors", stub).await;
+
+        let session = Session::new().secret_env("token", "HOME");
+        let id = session.id().to_owned();
+
+        let error = client.session(session).await.unwrap_err();
+
+        assert_eq!(error.code(), Code::Unimplemented);
+        assert_eq!(*sessions.lock().unwrap(), vec![id]);
+    }
+
+    #[tokio::test]
+    async fn cache_management() {
+        let stub = StubControl::default();
+        let prunes = stub.prunes.clone();
+        let client = serve("cache-management", stub).await;
+
+        let records = client.disk_usage(&["type==regular"]).await.unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].parent, None);
+        assert_eq!(records[1].parent, Some("layer-0".into()));
+        assert_eq!(
+            records[1].created_at,
+            Utc.timestamp_opt(1_583_280_000, 0).single()
+        );
+
+        let options = {
+            PruneOptions::new()
+                .filter("type==regular")
+                .keep_duration(Duration::from_secs(60))
+        };
+
+        let removed = client.prune(options).await.unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, "layer-1");
+
+        let request = prunes.lock().unwrap().pop().unwrap();
+        assert_eq!(request.keep_duration, 60_000_000_000);
+        assert_eq!(request.keep_bytes, 0);
+        assert!(!request.all);
+    }
+
+    #[tokio::test]
+    async fn invalid_address() {
+        let error = Client::connect("http://localhost:1234").await.unwrap_err();
+
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_ne!(
+            SolveOptions::frontend("dockerfile.v0").id(),
+            SolveOptions::frontend("dockerfile.v0").id()
+        );
+    }
+
+    /// Serve the stub on a Unix socket and connect to it.
+    async fn serve(name: &str, stub: StubControl) -> Client {
+        let path = env::temp_dir().join(format!("buildkit-client-{}-{}.sock", process::id(), name));
+        fs::remove_file(&path).ok();
+
+        let listener = UnixListener::bind(&path).unwrap();
+        let incoming = stream::unfold(listener, |mut listener| async move {
+            let socket = listener
+                .accept()
+                .await
+                .map(|(socket, _)| StubSocket(socket));
+            Some((socket, listener))
+        });
+
+        let server = {
+            Server::builder()
+                .add_service(ControlServer::new(stub))
+                .serve_with_incoming(incoming)
+        };
+
+        tokio::spawn(async move {
+            if let Err(error) = server.await {
+                error!("Stub daemon failed: {}", error);
+            }
+        });
+
+        Client::connect(format!("unix://{}", path.display()))
+            .await
+            .unwrap()
+    }
+
+    impl Connected for StubSocket {}
+
+    impl AsyncRead for StubSocket {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for StubSocket {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    fn usage_records() -> Vec<UsageRecord> {
+        let created_at = Timestamp {
+            seconds: 1_583_280_000,
+            nanos: 0,
+        };
+
+        vec![
+            UsageRecord {
+                id: "layer-0".into(),
+                in_use: true,
+                size: 1024,
+                created_at: Some(created_at.clone()),
+                record_type: "regular".into(),
+                ..Default::default()
+            },
+            UsageRecord {
+                id: "layer-1".into(),
+                parent: "layer-0".into(),
+                size: 2048,
+                created_at: Some(created_at),
+                record_type: "regular".into(),
+                ..Default::default()
+            },
+        ]
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+
+use tonic::{Code, Status};
+
+/// Error of a `Client` call.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The address is neither `unix://<path>` nor `tcp://<host>:<port>`.
+    InvalidAddress(String),
+
+    /// The daemon is not reachable.
+    Connect(tonic::transport::Error),
+
+    /// The daemon responded with an error status.
+    Rpc {
+        method: &'static str,
+        status: Status,
+    },
+
+    /// The session can't be served to the daemon.
+    Session(String),
+}
+
+impl ClientError {
+    pub(crate) fn rpc(method: &'static str) -> impl FnOnce(Status) -> Self {
+        move |status| ClientError::Rpc { method, status }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            ClientError::InvalidAddress(_) => Code::InvalidArgument,
+            ClientError::Connect(_) => Code::Unavailable,
+            ClientError::Rpc { status, .. } => status.code(),
+            ClientError::Session(_) => Code::Internal,
+        }
+    }
+
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            ClientError::Rpc { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::InvalidAddress(address) => write!(
+                f,
+                "invalid daemon address '{}', expected unix://<path> or tcp://<host>:<port>",
+                address
+            ),
+
+            ClientError::Connect(error) => write!(f, "unable to connect to the daemon: {}", error),
+
+            ClientError::Rpc { method, status } => write!(
+                f,
+                "{} failed with {:?}: {}",
+                method,
+                status.code(),
+                status.message()
+            ),
+
+            ClientError::Session(reason) => write!(f, "session failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Connect(error) => Some(error),
+            _ => None,
+        }
+    }
+}
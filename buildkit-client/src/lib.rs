@@ -0,0 +1,14 @@
+#![deny(warnings)]
+#![deny(clippy::all)]
+
+mod client;
+mod error;
+mod progress;
+mod session;
+mod usage;
+
+pub use self::client::{Client, Progress, SolveOptions, DEFAULT_ADDRESS};
+pub use self::error::ClientError;
+pub use self::progress::{LogStream, ProgressEvent, TaskProgress, VertexLog, VertexProgress};
+pub use self::session::Session;
+pub use self::usage::{CacheRecord, PruneOptions};
@@ -0,0 +1,176 @@
+use chrono::prelude::*;
+use prost_types::Timestamp;
+
+use buildkit_proto::moby::buildkit::v1::StatusResponse;
+
+/// Solve progress update from the daemon `Status` stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// Vertex was started, completed or failed.
+    Vertex(VertexProgress),
+
+    /// Progress of a task within a vertex, e.g. a layer download.
+    Status(TaskProgress),
+
+    /// Output of a vertex, e.g. what a command printed.
+    Log(VertexLog),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexProgress {
+    pub digest: String,
+    pub inputs: Vec<String>,
+    pub name: String,
+    pub cached: bool,
+    pub started: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskProgress {
+    pub id: String,
+    pub vertex: String,
+    pub name: String,
+    pub current: i64,
+
+    /// Zero when the amount of work is not known.
+    pub total: i64,
+
+    pub timestamp: Option<DateTime<Utc>>,
+    pub started: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexLog {
+    pub vertex: String,
+    pub stream: LogStream,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    Other(i64),
+}
+
+impl VertexProgress {
+    /// The vertex is done, either successfully, from cache or with an error.
+    pub fn is_completed(&self) -> bool {
+        self.completed.is_some() || self.error.is_some()
+    }
+}
+
+impl From<i64> for LogStream {
+    fn from(stream: i64) -> Self {
+        match stream {
+            1 => LogStream::Stdout,
+            2 => LogStream::Stderr,
+            other => LogStream::Other(other),
+        }
+    }
+}
+
+/// Flatten a status update: vertices first, then task progress and logs.
+pub(crate) fn events(response: StatusResponse) -> Vec<ProgressEvent> {
+    let vertices = response.vertexes.into_iter().map(|vertex| {
+        ProgressEvent::Vertex(VertexProgress {
+            digest: vertex.digest,
+            inputs: vertex.inputs,
+            name: vertex.name,
+            cached: vertex.cached,
+            started: timestamp(vertex.started),
+            completed: timestamp(vertex.completed),
+            error: Some(vertex.error).filter(|error| !error.is_empty()),
+        })
+    });
+
+    let statuses = response.statuses.into_iter().map(|status| {
+        ProgressEvent::Status(TaskProgress {
+            id: status.id,
+            vertex: status.vertex,
+            name: status.name,
+            current: status.current,
+            total: status.total,
+            timestamp: timestamp(status.timestamp),
+            started: timestamp(status.started),
+            completed: timestamp(status.completed),
+        })
+    });
+
+    let logs = response.logs.into_iter().map(|log| {
+        ProgressEvent::Log(VertexLog {
+            vertex: log.vertex,
+            stream: log.stream.into(),
+            timestamp: timestamp(log.timestamp),
+            data: log.msg,
+        })
+    });
+
+    vertices.chain(statuses).chain(logs).collect()
+}
+
+pub(crate) fn timestamp(timestamp: Option<Timestamp>) -> Option<DateTime<Utc>> {
+    timestamp.and_then(|timestamp| {
+        Utc.timestamp_opt(timestamp.seconds, timestamp.nanos as u32)
+            .single()
+    })
+}
+
+#[test]
+fn conversion() {
+    use buildkit_proto::moby::buildkit::v1::{Vertex, VertexLog as Log};
+
+    let started = Timestamp {
+        seconds: 1_583_280_000,
+        nanos: 500,
+    };
+
+    let response = StatusResponse {
+        vertexes: vec![Vertex {
+            digest: "sha256:aaa".into(),
+            name: "[1/2] FROM alpine".into(),
+            started: Some(started.clone()),
+            error: "exit code: 1".into(),
+            ..Default::default()
+        }],
+
+        statuses: vec![],
+
+        logs: vec![Log {
+            vertex: "sha256:aaa".into(),
+            stream: 2,
+            msg: b"oops\n".to_vec(),
+            ..Default::default()
+        }],
+    };
+
+    let events = events(response);
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        events[0],
+        ProgressEvent::Vertex(VertexProgress {
+            digest: "sha256:aaa".into(),
+            inputs: vec![],
+            name: "[1/2] FROM alpine".into(),
+            cached: false,
+            started: Utc.timestamp_opt(1_583_280_000, 500).single(),
+            completed: None,
+            error: Some("exit code: 1".into()),
+        })
+    );
+
+    match events[1] {
+        ProgressEvent::Log(ref log) => {
+            assert_eq!(log.stream, LogStream::Stderr);
+            assert_eq!(log.timestamp, None);
+            assert_eq!(log.data, b"oops\n");
+        }
+
+        ref other => panic!("unexpected event: {:?}", other),
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use futures::channel::mpsc;
+use futures::{stream, SinkExt};
+use log::*;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::task;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+
+use buildkit_proto::fsutil::mode::{
+    MODE_CHAR_DEVICE, MODE_DEVICE, MODE_DIR, MODE_NAMED_PIPE, MODE_SETGID, MODE_SETUID,
+    MODE_SOCKET, MODE_STICKY, MODE_SYMLINK,
+};
+use buildkit_proto::fsutil::pattern::glob_match;
+use buildkit_proto::fsutil::types::packet::PacketType;
+use buildkit_proto::fsutil::types::{Packet, Stat};
+use buildkit_proto::moby::filesync::v1::file_sync_server::FileSync;
+use buildkit_proto::moby::filesync::v1::BytesMessage;
+
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Sends local directories for `Source::local` with the fsutil `DiffCopy` protocol.
+pub(super) struct FileSyncProvider {
+    dirs: HashMap<String, PathBuf>,
+}
+
+/// Include and exclude patterns the daemon sends, e.g. from `.dockerignore`.
+struct Filter {
+    includes: Vec<Pattern>,
+    excludes: Vec<(Pattern, bool)>,
+}
+
+/// Docker-style pattern: `*` and `?` within a path component and `**` for any number of components.
+struct Pattern(Vec<String>);
+
+struct Entry {
+    stat: Stat,
+
+    /// Only regular files can be requested.
+    file: Option<PathBuf>,
+}
+
+impl FileSyncProvider {
+    pub(super) fn new(dirs: HashMap<String, PathBuf>) -> Self {
+        Self { dirs }
+    }
+}
+
+#[tonic::async_trait]
+impl FileSync for FileSyncProvider {
+    type DiffCopyStream = mpsc::Receiver<Result<Packet, Status>>;
+    type TarStreamStream = stream::Empty<Result<BytesMessage, Status>>;
+
+    async fn diff_copy(
+        &self,
+        request: Request<Streaming<Packet>>,
+    ) -> Result<Response<Self::DiffCopyStream>, Status> {
+        let metadata = request.metadata();
+        let name = values(metadata, "dir-name").pop().unwrap_or_default();
+
+        let root = match self.dirs.get(&name) {
+            Some(root) => root,
+            None => {
+                return Err(Status::not_found(format!(
+                    "no access allowed to dir {:?}",
+                    name
+                )))
+            }
+        };
+
+        let filter = Filter {
+            includes: {
+                values(metadata, "include-patterns")
+                    .iter()
+                    .map(|pattern| Pattern::new(pattern))
+                    .collect()
+            },
+
+            excludes: {
+                values(metadata, "exclude-patterns")
+                    .iter()
+                    .map(|pattern| match pattern.strip_prefix('!') {
+                        Some(exception) => (Pattern::new(exception), true),
+                        None => (Pattern::new(pattern), false),
+                    })
+                    .collect()
+            },
+        };
+
+        debug!("sending '{}' from {}", name, root.display());
+
+        // Reading the directory tree blocks, so it shouldn't stall the other session streams.
+        let entries = {
+            let root = root.clone();
+
+            task::spawn_blocking(move || {
+                let mut entries = vec![];
+
+                walk(&root, &mut vec![], &filter, &mut entries)
+                    .map(|_| entries)
+                    .map_err(|error| {
+                        Status::internal(format!("unable to read {}: {}", root.display(), error))
+                    })
+            })
+            .await
+            .map_err(|error| {
+                Status::internal(format!("unable to read the directory: {}", error))
+            })??
+        };
+
+        let (sender, receiver) = mpsc::channel(16);
+        tokio::spawn(send(entries, request.into_inner(), sender));
+
+        Ok(Response::new(receiver))
+    }
+
+    async fn tar_stream(
+        &self,
+        _: Request<Streaming<BytesMessage>>,
+    ) -> Result<Response<Self::TarStreamStream>, Status> {
+        Err(Status::unimplemented("tar streams are not supported"))
+    }
+}
+
+impl Filter {
+    fn is_included(&self, path: &[&str]) -> bool {
+        self.includes.is_empty() || self.includes.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// The last matching pattern wins, so `!` exceptions can re-include excluded paths.
+    fn is_excluded(&self, path: &[&str]) -> bool {
+        let mut excluded = false;
+
+        for (pattern, exception) in &self.excludes {
+            if pattern.matches(path) {
+                excluded = !exception;
+            }
+        }
+
+        excluded
+    }
+
+    fn has_exceptions(&self) -> bool {
+        self.excludes.iter().any(|(_, exception)| *exception)
+    }
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Self {
+        Pattern(
+            pattern
+                .split('/')
+                .filter(|component| !component.is_empty() && *component != ".")
+                .map(String::from)
+                .collect(),
+        )
+    }
+
+    /// Whether the pattern matches the path or one of its parents.
+    fn matches(&self, path: &[&str]) -> bool {
+        (1..=path.len()).any(|len| glob_match(&self.0, &path[..len]))
+    }
+}
+
+/// Walk the directory in the order fsutil expects: sorted by name, parents before children.
+fn walk(
+    dir: &Path,
+    prefix: &mut Vec<String>,
+    filter: &Filter,
+    entries: &mut Vec<Entry>,
+) -> io::Result<()> {
+    let mut names = {
+        fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    names.sort();
+
+    for name in names {
+        let name = match name.into_string() {
+            Ok(name) => name,
+            Err(name) => {
+                warn!("skipping a non-UTF-8 path {:?}", dir.join(name));
+                continue;
+            }
+        };
+
+        let path = dir.join(&name);
+        let metadata = fs::symlink_metadata(&path)?;
+
+        prefix.push(name);
+
+        let components = prefix.iter().map(String::as_str).collect::<Vec<_>>();
+        let excluded = filter.is_excluded(&components);
+        let included = filter.is_included(&components) && !excluded;
+        let relative = components.join("/");
+
+        if metadata.is_dir() {
+            // Children of an excluded directory can only come back with an exception.
+            if !excluded || filter.has_exceptions() {
+                let position = entries.len();
+
+                entries.push(Entry {
+                    stat: stat(relative, &path, &metadata)?,
+                    file: None,
+                });
+
+                walk(&path, prefix, filter, entries)?;
+
+                // Directories are only sent as parents of included files unless they are included themselves.
+                if !included && entries.len() == position + 1 {
+                    entries.pop();
+                }
+            }
+        } else if included {
+            entries.push(Entry {
+                stat: stat(relative, &path, &metadata)?,
+                file: Some(path).filter(|_| metadata.is_file()),
+            });
+        }
+
+        prefix.pop();
+    }
+
+    Ok(())
+}
+
+/// Send the stats, then the files the daemon asks for until it's done.
+async fn send(
+    entries: Vec<Entry>,
+    mut requests: Streaming<Packet>,
+    mut responses: mpsc::Sender<Result<Packet, Status>>,
+) {
+    if let Err(status) = transfer(&entries, &mut requests, &mut responses).await {
+        warn!("file transfer failed: {}", status.message());
+        responses.send(Err(status)).await.ok();
+    }
+}
+
+async fn transfer(
+    entries: &[Entry],
+    requests: &mut Streaming<Packet>,
+    responses: &mut mpsc::Sender<Result<Packet, Status>>,
+) -> Result<(), Status> {
+    for entry in entries {
+        let stat = Some(entry.stat.clone());
+        responses
+            .send(Ok(packet(PacketType::PacketStat, 0, stat, vec![])))
+            .await
+            .map_err(closed)?;
+    }
+
+    // Empty stat marks the end of the listing.
+    responses
+        .send(Ok(packet(PacketType::PacketStat, 0, None, vec![])))
+        .await
+        .map_err(closed)?;
+
+    while let Some(request) = requests.message().await? {
+        match PacketType::from_i32(request.r#type) {
+            Some(PacketType::PacketReq) => {
+                let path = {
+                    entries
+                        .get(request.id as usize)
+                        .and_then(|entry| entry.file.as_ref())
+                        .ok_or_else(|| {
+                            Status::invalid_argument(format!("invalid file request {}", request.id))
+                        })?
+                };
+
+                send_file(path, request.id, responses).await?;
+            }
+
+            Some(PacketType::PacketFin) => {
+                responses
+                    .send(Ok(packet(PacketType::PacketFin, 0, None, vec![])))
+                    .await
+                    .map_err(closed)?;
+                return Ok(());
+            }
+
+            Some(PacketType::PacketErr) => {
+                return Err(Status::aborted(String::from_utf8_lossy(&request.data)));
+            }
+
+            _ => {
+                return Err(Status::invalid_argument(format!(
+                    "unexpected packet type {}",
+                    request.r#type
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_file(
+    path: &Path,
+    id: u32,
+    responses: &mut mpsc::Sender<Result<Packet, Status>>,
+) -> Result<(), Status> {
+    let read_error = |error: io::Error| {
+        Status::internal(format!("unable to read {}: {}", path.display(), error))
+    };
+
+    let mut file = File::open(path).await.map_err(read_error)?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+
+    loop {
+        let len = file.read(&mut buffer).await.map_err(read_error)?;
+        let data = buffer[..len].to_vec();
+
+        // Empty data marks the end of the file.
+        responses
+            .send(Ok(packet(PacketType::PacketData, id, None, data)))
+            .await
+            .map_err(closed)?;
+
+        if len == 0 {
+            return Ok(());
+        }
+    }
+}
+
+fn stat(path: String, full_path: &Path, metadata: &Metadata) -> io::Result<Stat> {
+    let file_type = metadata.file_type();
+
+    let mut stat = Stat {
+        path,
+        mode: file_mode(metadata),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mod_time: metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec(),
+        ..Default::default()
+    };
+
+    if !file_type.is_dir() {
+        stat.size = metadata.size() as i64;
+    }
+
+    if file_type.is_symlink() {
+        stat.linkname = fs::read_link(full_path)?.to_string_lossy().into_owned();
+    }
+
+    Ok(stat)
+}
+
+/// Convert the Unix mode into Go's `os.FileMode`.
+fn file_mode(metadata: &Metadata) -> u32 {
+    let file_type = metadata.file_type();
+    let mode = metadata.mode();
+    let mut file_mode = mode & 0o777;
+
+    for (unix, go) in &[
+        (0o4000, MODE_SETUID),
+        (0o2000, MODE_SETGID),
+        (0o1000, MODE_STICKY),
+    ] {
+        if mode & unix != 0 {
+            file_mode |= go;
+        }
+    }
+
+    if file_type.is_dir() {
+        file_mode |= MODE_DIR;
+    } else if file_type.is_symlink() {
+        file_mode |= MODE_SYMLINK;
+    } else if file_type.is_fifo() {
+        file_mode |= MODE_NAMED_PIPE;
+    } else if file_type.is_socket() {
+        file_mode |= MODE_SOCKET;
+    } else if file_type.is_block_device() {
+        file_mode |= MODE_DEVICE;
+    } else if file_type.is_char_device() {
+        file_mode |= MODE_DEVICE | MODE_CHAR_DEVICE;
+    }
+
+    file_mode
+}
+
+fn packet(kind: PacketType, id: u32, stat: Option<Stat>, data: Vec<u8>) -> Packet {
+    Packet {
+        r#type: kind as i32,
+        stat,
+        id,
+        data,
+    }
+}
+
+fn values(metadata: &MetadataMap, key: &str) -> Vec<String> {
+    metadata
+        .get_all(key)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(String::from)
+        .collect()
+}
+
+fn closed(_: mpsc::SendError) -> Status {
+    Status::cancelled("the daemon closed the transfer")
+}
+
+#[test]
+fn filtering() {
+    let filter = Filter {
+        includes: vec![Pattern::new("src"), Pattern::new("**/*.toml")],
+        excludes: vec![
+            (Pattern::new("src/**/*.orig"), false),
+            (Pattern::new("target"), false),
+            (Pattern::new("target/doc"), true),
+        ],
+    };
+
+    let is_sent = |path: &str| {
+        let path = path.split('/').collect::<Vec<_>>();
+        filter.is_included(&path) && !filter.is_excluded(&path)
+    };
+
+    assert!(is_sent("src/main.rs"));
+    assert!(is_sent("Cargo.toml"));
+    assert!(is_sent("crates/core/Cargo.toml"));
+    assert!(!is_sent("README.md"));
+    assert!(!is_sent("src/lib.rs.orig"));
+    assert!(!is_sent("src/bin/tool.orig"));
+
+    assert!(filter.is_excluded(&["target", "debug"]));
+    assert!(!filter.is_excluded(&["target", "doc", "index.html"]));
+    assert!(filter.has_exceptions());
+}
@@ -0,0 +1,201 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::{mpsc, oneshot};
+use futures::{ready, Future, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tonic::transport::server::Connected;
+use tonic::Status;
+
+use buildkit_proto::moby::buildkit::v1::BytesMessage;
+
+/// Byte stream tunneled through the `Control.Session` call, which carries the session HTTP/2 connection.
+pub(crate) struct StreamIo<S> {
+    incoming: S,
+    outgoing: mpsc::Sender<BytesMessage>,
+    closed: oneshot::Receiver<()>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+/// Transport that reports when the connection is done with it.
+pub(super) struct Released<IO> {
+    inner: IO,
+    released: Option<oneshot::Sender<()>>,
+}
+
+impl<S> StreamIo<S>
+where
+    S: Stream<Item = Result<BytesMessage, Status>> + Unpin,
+{
+    /// The stream reads as closed once the `closed` sender is dropped.
+    pub(crate) fn new(
+        incoming: S,
+        outgoing: mpsc::Sender<BytesMessage>,
+        closed: oneshot::Receiver<()>,
+    ) -> Self {
+        Self {
+            incoming,
+            outgoing,
+            closed,
+            buffer: vec![],
+            position: 0,
+        }
+    }
+}
+
+impl<IO> Released<IO> {
+    pub(super) fn new(inner: IO) -> (Self, oneshot::Receiver<()>) {
+        let (sender, receiver) = oneshot::channel();
+        let transport = Self {
+            inner,
+            released: Some(sender),
+        };
+
+        (transport, receiver)
+    }
+}
+
+impl<S> AsyncRead for StreamIo<S>
+where
+    S: Stream<Item = Result<BytesMessage, Status>> + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+
+        if Pin::new(&mut this.closed).poll(cx).is_ready() {
+            return Poll::Ready(Ok(0));
+        }
+
+        while this.position == this.buffer.len() {
+            match ready!(Pin::new(&mut this.incoming).poll_next(cx)) {
+                Some(Ok(message)) => {
+                    this.buffer = message.data;
+                    this.position = 0;
+                }
+
+                Some(Err(status)) => {
+                    return Poll::Ready(Err(io::Error::other(status.message().to_owned())));
+                }
+
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+
+        let len = buf.len().min(this.buffer.len() - this.position);
+
+        buf[..len].copy_from_slice(&this.buffer[this.position..this.position + len]);
+        this.position += len;
+
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<S> AsyncWrite for StreamIo<S>
+where
+    S: Stream<Item = Result<BytesMessage, Status>> + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let message = BytesMessage { data: buf.to_vec() };
+
+        ready!(self.outgoing.poll_ready(cx)).map_err(broken_pipe)?;
+        self.outgoing.start_send(message).map_err(broken_pipe)?;
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.outgoing.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<IO> AsyncRead for Released<IO>
+where
+    IO: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<IO> AsyncWrite for Released<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Session connections are tunneled, so there is no remote address to report.
+impl<IO> Connected for Released<IO> {}
+
+impl<IO> Drop for Released<IO> {
+    fn drop(&mut self) {
+        if let Some(released) = self.released.take() {
+            released.send(()).ok();
+        }
+    }
+}
+
+fn broken_pipe(error: mpsc::SendError) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, error)
+}
+
+/// Open a channel over the single `io` transport, the way the daemon does with the session connection.
+#[cfg(test)]
+pub(crate) async fn connect_once<IO>(io: IO) -> tonic::transport::Channel
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    use std::sync::{Arc, Mutex};
+
+    use tonic::transport::{Endpoint, Uri};
+    use tower::service_fn;
+
+    let io = Arc::new(Mutex::new(Some(io)));
+    let connector = service_fn(move |_: Uri| {
+        let io = io.lock().unwrap().take();
+
+        async move {
+            io.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotConnected, "transport is already used")
+            })
+        }
+    });
+
+    Endpoint::from_static("http://[::]:50051")
+        .connect_with_connector(connector)
+        .await
+        .unwrap()
+}
@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use futures::stream;
+use log::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use buildkit_proto::grpc::health::v1::health_check_response::ServingStatus;
+use buildkit_proto::grpc::health::v1::health_server::{Health, HealthServer};
+use buildkit_proto::grpc::health::v1::{HealthCheckRequest, HealthCheckResponse};
+use buildkit_proto::moby::buildkit::secrets::v1::secrets_server::SecretsServer;
+use buildkit_proto::moby::filesync::v1::file_sync_server::FileSyncServer;
+use buildkit_proto::moby::sshforward::v1::ssh_server::SshServer;
+
+use crate::client::unique_id;
+use crate::error::ClientError;
+
+mod filesync;
+mod io;
+mod secrets;
+mod ssh;
+
+pub(crate) use self::io::StreamIo;
+
+#[cfg(test)]
+pub(crate) use self::io::connect_once;
+
+const HEALTH_METHODS: &[&str] = &["/grpc.health.v1.Health/Check"];
+const FILESYNC_METHODS: &[&str] = &["/moby.filesync.v1.FileSync/DiffCopy"];
+const SECRETS_METHODS: &[&str] = &["/moby.buildkit.secrets.v1.Secrets/GetSecret"];
+const SSH_METHODS: &[&str] = &[
+    "/moby.sshforward.v1.SSH/CheckAgent",
+    "/moby.sshforward.v1.SSH/ForwardAgent",
+];
+
+/// Client-side resources the daemon can access during a solve: directories for `Source::local`,
+/// secrets for secret mounts and SSH agents for SSH mounts.
+///
+/// The daemon calls the session services back over a connection tunneled through `Client::session`.
+#[derive(Debug, Clone)]
+pub struct Session {
+    id: String,
+    name: String,
+    shared_key: String,
+    dirs: HashMap<String, PathBuf>,
+    secrets: HashMap<String, SecretSource>,
+    agents: HashMap<String, PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+enum SecretSource {
+    File(PathBuf),
+    Env(String),
+}
+
+struct HealthProvider;
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            id: unique_id(),
+            name: env!("CARGO_PKG_NAME").into(),
+            shared_key: String::new(),
+            dirs: HashMap::new(),
+            secrets: HashMap::new(),
+            agents: HashMap::new(),
+        }
+    }
+
+    /// Name of the session in the daemon logs.
+    pub fn with_name<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.name = name.into();
+        self
+    }
+
+    /// Local sources of sessions with the same key can reuse the files transferred before, e.g. a hash of the context path.
+    pub fn with_shared_key<S>(mut self, key: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.shared_key = key.into();
+        self
+    }
+
+    /// Serve the directory to `Source::local(name)`.
+    pub fn local_dir<S, P>(mut self, name: S, path: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.dirs.insert(name.into(), path.into());
+        self
+    }
+
+    /// Provide the file contents to secret mounts with the id.
+    pub fn secret_file<S, P>(mut self, id: S, path: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.secrets
+            .insert(id.into(), SecretSource::File(path.into()));
+        self
+    }
+
+    /// Provide the environment variable value to secret mounts with the id.
+    pub fn secret_env<S, V>(mut self, id: S, var: V) -> Self
+    where
+        S: Into<String>,
+        V: Into<String>,
+    {
+        self.secrets
+            .insert(id.into(), SecretSource::Env(var.into()));
+        self
+    }
+
+    /// Forward the SSH agent listening on the socket, e.g. `$SSH_AUTH_SOCK`, to SSH mounts with the id.
+    /// Mounts without an explicit id use `default`.
+    pub fn ssh_agent<S, P>(mut self, id: S, socket: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.agents.insert(id.into(), socket.into());
+        self
+    }
+
+    /// Id to refer to the session with `SolveOptions::session`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Serve the session services over an established connection, where the daemon is the HTTP/2 client.
+    /// Resolves once the connection is closed.
+    pub async fn serve<IO>(self, io: IO) -> Result<(), ClientError>
+    where
+        IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (io, released) = io::Released::new(io);
+
+        let server = {
+            Server::builder()
+                .add_service(HealthServer::new(HealthProvider))
+                .add_service(FileSyncServer::new(filesync::FileSyncProvider::new(
+                    self.dirs,
+                )))
+                .add_service(SecretsServer::new(secrets::SecretsProvider::new(
+                    self.secrets,
+                )))
+                .add_service(SshServer::new(ssh::SshProvider::new(self.agents)))
+                .serve_with_incoming(stream::iter(vec![Ok::<_, std::io::Error>(io)]))
+        };
+
+        // The server only accepts the connection, it's served in the background.
+        server
+            .await
+            .map_err(|error| ClientError::Session(error.to_string()))?;
+
+        released.await.ok();
+        debug!("session {} is closed", self.id);
+
+        Ok(())
+    }
+
+    /// Headers of the `Control.Session` call: who we are and which services the daemon can call.
+    pub(crate) fn write_headers(&self, metadata: &mut MetadataMap) -> Result<(), ClientError> {
+        let value = |text: &str| {
+            MetadataValue::from_str(text)
+                .map_err(|_| ClientError::Session(format!("invalid header value '{}'", text)))
+        };
+
+        metadata.insert("x-docker-expose-session-uuid", value(&self.id)?);
+        metadata.insert("x-docker-expose-session-name", value(&self.name)?);
+        metadata.insert(
+            "x-docker-expose-session-sharedkey",
+            value(&self.shared_key)?,
+        );
+
+        let mut methods = HEALTH_METHODS.to_vec();
+
+        if !self.dirs.is_empty() {
+            methods.extend_from_slice(FILESYNC_METHODS);
+        }
+
+        if !self.secrets.is_empty() {
+            methods.extend_from_slice(SECRETS_METHODS);
+        }
+
+        if !self.agents.is_empty() {
+            methods.extend_from_slice(SSH_METHODS);
+        }
+
+        for method in methods {
+            metadata.append("x-docker-expose-session-grpc-method", value(method)?);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The daemon closes sessions that stop answering health checks.
+#[tonic::async_trait]
+impl Health for HealthProvider {
+    type WatchStream = stream::Empty<Result<HealthCheckResponse, Status>>;
+
+    async fn check(
+        &self,
+        _: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        Ok(Response::new(HealthCheckResponse {
+            status: ServingStatus::Serving as i32,
+        }))
+    }
+
+    async fn watch(
+        &self,
+        _: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        Err(Status::unimplemented("health watching is not supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+
+    use futures::channel::mpsc;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+    use tonic::Code;
+
+    use buildkit_proto::fsutil::types::packet::PacketType;
+    use buildkit_proto::fsutil::types::Packet;
+    use buildkit_proto::grpc::health::v1::health_client::HealthClient;
+    use buildkit_proto::moby::buildkit::secrets::v1::secrets_client::SecretsClient;
+    use buildkit_proto::moby::buildkit::secrets::v1::GetSecretRequest;
+    use buildkit_proto::moby::filesync::v1::file_sync_client::FileSyncClient;
+    use buildkit_proto::moby::sshforward::v1::ssh_client::SshClient;
+    use buildkit_proto::moby::sshforward::v1::{BytesMessage, CheckAgentRequest};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn file_sync() {
+        let root = fixture_dir("file-sync");
+        let channel = serve(Session::new().local_dir("context", &root)).await;
+        let mut client = FileSyncClient::new(channel);
+
+        let (mut requests, receiver) = mpsc::channel(4);
+        let mut request = Request::new(receiver);
+
+        request
+            .metadata_mut()
+            .insert("dir-name", "context".parse().unwrap());
+        request
+            .metadata_mut()
+            .append("exclude-patterns", "target".parse().unwrap());
+
+        let mut packets = client.diff_copy(request).await.unwrap().into_inner();
+        let mut paths = vec![];
+
+        while let Some(stat) = packets.message().await.unwrap().unwrap().stat {
+            paths.push(stat.path);
+        }
+
+        assert_eq!(paths, vec!["Dockerfile", "README.md", "src", "src/main.rs"]);
+
+        requests
+            .send(packet(PacketType::PacketReq, 3))
+            .await
+            .unwrap();
+
+        let data = packets.message().await.unwrap().unwrap();
+        assert_eq!(data.r#type, PacketType::PacketData as i32);
+        assert_eq!((data.id, data.data.as_slice()), (3, &b"fn main() {}"[..]));
+
+        // Empty data marks the end of the file.
+        let data = packets.message().await.unwrap().unwrap();
+        assert_eq!((data.id, data.data.len()), (3, 0));
+
+        requests
+            .send(packet(PacketType::PacketFin, 0))
+            .await
+            .unwrap();
+
+        let fin = packets.message().await.unwrap().unwrap();
+        assert_eq!(fin.r#type, PacketType::PacketFin as i32);
+        assert!(packets.message().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_dir() {
+        let channel = serve(Session::new()).await;
+        let mut client = FileSyncClient::new(channel);
+
+        let mut request = Request::new(stream::empty());
+        request
+            .metadata_mut()
+            .insert("dir-name", "context".parse().unwrap());
+
+        let error = client.diff_copy(request).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn secrets_and_health() {
+        let root = fixture_dir("secrets");
+        let session = {
+            Session::new()
+                .secret_file("token", root.join("Dockerfile"))
+                .secret_env("missing", "BUILDKIT_CLIENT_TEST_UNSET_VARIABLE")
+        };
+
+        let channel = serve(session).await;
+
+        let response = {
+            HealthClient::new(channel.clone())
+                .check(HealthCheckRequest::default())
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(response.into_inner().status, ServingStatus::Serving as i32);
+
+        let mut client = SecretsClient::new(channel);
+        let secret = client.get_secret(secret_request("token")).await.unwrap();
+        assert_eq!(secret.into_inner().data, b"FROM alpine\n");
+
+        for id in &["missing", "unknown"] {
+            let error = client.get_secret(secret_request(id)).await.unwrap_err();
+            assert_eq!(error.code(), Code::NotFound);
+        }
+    }
+
+    #[tokio::test]
+    async fn ssh_forwarding() {
+        let socket = env::temp_dir().join(format!("buildkit-client-{}-agent.sock", process::id()));
+        fs::remove_file(&socket).ok();
+
+        // Echo server in place of the agent.
+        let mut agent = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (mut connection, _) = agent.accept().await.unwrap();
+            let mut buffer = vec![0; 64];
+
+            loop {
+                match connection.read(&mut buffer).await.unwrap() {
+                    0 => break,
+                    len => connection.write_all(&buffer[..len]).await.unwrap(),
+                }
+            }
+        });
+
+        let channel = serve(Session::new().ssh_agent("default", &socket)).await;
+        let mut client = SshClient::new(channel);
+
+        let request = CheckAgentRequest { id: String::new() };
+        client.check_agent(request).await.unwrap();
+
+        let request = CheckAgentRequest {
+            id: "deploy".into(),
+        };
+        let error = client.check_agent(request).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+
+        let messages = stream::iter(vec![BytesMessage {
+            data: b"ping".to_vec(),
+        }]);
+
+        let response = client.forward_agent(Request::new(messages)).await.unwrap();
+        let echoed = {
+            response
+                .into_inner()
+                .map(|message| message.unwrap().data)
+                .concat()
+                .await
+        };
+
+        assert_eq!(echoed, b"ping");
+    }
+
+    #[test]
+    fn headers() {
+        let session = Session::new().local_dir("context", ".").with_name("test");
+        let mut metadata = MetadataMap::new();
+
+        session.write_headers(&mut metadata).unwrap();
+
+        assert_eq!(
+            metadata.get("x-docker-expose-session-uuid").unwrap(),
+            session.id()
+        );
+        assert_eq!(
+            metadata.get("x-docker-expose-session-name").unwrap(),
+            "test"
+        );
+
+        let methods = {
+            metadata
+                .get_all("x-docker-expose-session-grpc-method")
+                .iter()
+                .map(|value| value.to_str().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            methods,
+            vec![
+                "/grpc.health.v1.Health/Check",
+                "/moby.filesync.v1.FileSync/DiffCopy"
+            ]
+        );
+    }
+
+    /// Serve the session over a socket pair and connect to it like the daemon does.
+    async fn serve(session: Session) -> tonic::transport::Channel {
+        let (daemon, client) = UnixStream::pair().unwrap();
+
+        tokio::spawn(async move {
+            if let Err(error) = session.serve(client).await {
+                error!("Session failed: {}", error);
+            }
+        });
+
+        connect_once(daemon).await
+    }
+
+    fn fixture_dir(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("buildkit-client-{}-{}", process::id(), name));
+        fs::remove_dir_all(&root).ok();
+
+        write(&root.join("Dockerfile"), "FROM alpine\n");
+        write(&root.join("README.md"), "# Test\n");
+        write(&root.join("src/main.rs"), "fn main() {}");
+        write(&root.join("target/debug/app"), "binary");
+
+        root
+    }
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn packet(kind: PacketType, id: u32) -> Packet {
+        Packet {
+            r#type: kind as i32,
+            id,
+            ..Default::default()
+        }
+    }
+
+    fn secret_request(id: &str) -> GetSecretRequest {
+        GetSecretRequest {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+}
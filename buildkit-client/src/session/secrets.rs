@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::ffi::OsStringExt;
+
+use tonic::{Request, Response, Status};
+
+use buildkit_proto::moby::buildkit::secrets::v1::secrets_server::Secrets;
+use buildkit_proto::moby::buildkit::secrets::v1::{GetSecretRequest, GetSecretResponse};
+
+use super::SecretSource;
+
+/// Provides values of secret mounts. Files and variables are read on every request.
+pub(super) struct SecretsProvider {
+    secrets: HashMap<String, SecretSource>,
+}
+
+impl SecretsProvider {
+    pub(super) fn new(secrets: HashMap<String, SecretSource>) -> Self {
+        Self { secrets }
+    }
+}
+
+#[tonic::async_trait]
+impl Secrets for SecretsProvider {
+    async fn get_secret(
+        &self,
+        request: Request<GetSecretRequest>,
+    ) -> Result<Response<GetSecretResponse>, Status> {
+        let id = request.into_inner().id;
+        let not_found = || Status::not_found(format!("secret {} not found", id));
+
+        let data = match self.secrets.get(&id) {
+            Some(SecretSource::File(path)) => fs::read(path).map_err(|error| {
+                Status::internal(format!("unable to read secret {}: {}", id, error))
+            })?,
+
+            Some(SecretSource::Env(name)) => env::var_os(name).ok_or_else(not_found)?.into_vec(),
+
+            None => return Err(not_found()),
+        };
+
+        Ok(Response::new(GetSecretResponse { data }))
+    }
+}
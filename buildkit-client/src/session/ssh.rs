@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use log::*;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::UnixStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use buildkit_proto::moby::sshforward::v1::ssh_server::Ssh;
+use buildkit_proto::moby::sshforward::v1::{BytesMessage, CheckAgentRequest, CheckAgentResponse};
+
+/// Id of SSH mounts that don't specify one.
+const DEFAULT_ID: &str = "default";
+
+/// Metadata key of the agent id in `ForwardAgent` calls.
+const ID_KEY: &str = "buildkit.ssh.id";
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Forwards SSH agent connections of SSH mounts to local agent sockets.
+pub(super) struct SshProvider {
+    agents: HashMap<String, PathBuf>,
+}
+
+impl SshProvider {
+    pub(super) fn new(agents: HashMap<String, PathBuf>) -> Self {
+        Self { agents }
+    }
+
+    fn agent(&self, id: &str) -> Result<&PathBuf, Status> {
+        let id = if id.is_empty() { DEFAULT_ID } else { id };
+
+        self.agents
+            .get(id)
+            .ok_or_else(|| Status::not_found(format!("unset ssh forward key {}", id)))
+    }
+}
+
+#[tonic::async_trait]
+impl Ssh for SshProvider {
+    type ForwardAgentStream = mpsc::Receiver<Result<BytesMessage, Status>>;
+
+    async fn check_agent(
+        &self,
+        request: Request<CheckAgentRequest>,
+    ) -> Result<Response<CheckAgentResponse>, Status> {
+        self.agent(&request.into_inner().id)?;
+
+        Ok(Response::new(CheckAgentResponse {}))
+    }
+
+    async fn forward_agent(
+        &self,
+        request: Request<Streaming<BytesMessage>>,
+    ) -> Result<Response<Self::ForwardAgentStream>, Status> {
+        let id = {
+            request
+                .metadata()
+                .get(ID_KEY)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+
+        let socket = self.agent(&id)?;
+        let agent = UnixStream::connect(socket).await.map_err(|error| {
+            Status::unavailable(format!(
+                "unable to connect to the SSH agent at {}: {}",
+                socket.display(),
+                error
+            ))
+        })?;
+
+        let (reader, writer) = io::split(agent);
+        let (sender, receiver) = mpsc::channel(16);
+
+        tokio::spawn(upload(request.into_inner(), writer));
+        tokio::spawn(download(reader, sender));
+
+        Ok(Response::new(receiver))
+    }
+}
+
+/// Pass the daemon messages to the agent.
+async fn upload(mut messages: Streaming<BytesMessage>, mut agent: WriteHalf<UnixStream>) {
+    loop {
+        match messages.message().await {
+            Ok(Some(message)) => {
+                if let Err(error) = agent.write_all(&message.data).await {
+                    warn!("unable to write to the SSH agent: {}", error);
+                    break;
+                }
+            }
+
+            Ok(None) => break,
+            Err(status) => {
+                debug!("SSH forwarding is interrupted: {}", status.message());
+                break;
+            }
+        }
+    }
+
+    agent.shutdown().await.ok();
+}
+
+/// Pass the agent responses back to the daemon.
+async fn download(
+    mut agent: ReadHalf<UnixStream>,
+    mut messages: mpsc::Sender<Result<BytesMessage, Status>>,
+) {
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        let message = match agent.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(len) => Ok(BytesMessage {
+                data: buffer[..len].to_vec(),
+            }),
+
+            Err(error) => Err(Status::unavailable(format!(
+                "unable to read from the SSH agent: {}",
+                error
+            ))),
+        };
+
+        let failed = message.is_err();
+
+        if messages.send(message).await.is_err() || failed {
+            break;
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use chrono::prelude::*;
+
+use buildkit_proto::moby::buildkit::v1::{PruneRequest, UsageRecord};
+
+use crate::progress::timestamp;
+
+/// Build cache record, as reported by `Client::disk_usage` and `Client::prune`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheRecord {
+    pub id: String,
+    pub mutable: bool,
+    pub in_use: bool,
+
+    /// Size in bytes.
+    pub size: i64,
+
+    pub parent: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub usage_count: i64,
+    pub description: String,
+
+    /// Kind of the record, e.g. `regular`, `source.local` or `exec.cachemount`.
+    pub record_type: String,
+
+    pub shared: bool,
+}
+
+/// Which build cache records `Client::prune` should remove.
+#[derive(Debug, Default, Clone)]
+pub struct PruneOptions {
+    filters: Vec<String>,
+    all: bool,
+    keep_duration: Option<Duration>,
+    keep_bytes: Option<u64>,
+}
+
+impl PruneOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only prune the matching records, e.g. `type==source.local`.
+    pub fn filter<S>(mut self, filter: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.filters.push(filter.into());
+        self
+    }
+
+    /// Prune shared and internal records too.
+    pub fn all(mut self) -> Self {
+        self.all = true;
+        self
+    }
+
+    /// Keep records used within the duration.
+    pub fn keep_duration(mut self, duration: Duration) -> Self {
+        self.keep_duration = Some(duration);
+        self
+    }
+
+    /// Keep up to the amount of bytes of the most recently used records.
+    pub fn keep_bytes(mut self, bytes: u64) -> Self {
+        self.keep_bytes = Some(bytes);
+        self
+    }
+
+    pub(crate) fn into_request(self) -> PruneRequest {
+        PruneRequest {
+            filter: self.filters,
+            all: self.all,
+
+            // The daemon expects Go's `time.Duration`, which is in nanoseconds.
+            keep_duration: self
+                .keep_duration
+                .map(|duration| duration.as_nanos() as i64)
+                .unwrap_or_default(),
+
+            keep_bytes: self
+                .keep_bytes
+                .map(|bytes| bytes as i64)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<UsageRecord> for CacheRecord {
+    fn from(record: UsageRecord) -> Self {
+        Self {
+            id: record.id,
+            mutable: record.mutable,
+            in_use: record.in_use,
+            size: record.size,
+            parent: Some(record.parent).filter(|parent| !parent.is_empty()),
+            created_at: timestamp(record.created_at),
+            last_used_at: timestamp(record.last_used_at),
+            usage_count: record.usage_count,
+            description: record.description,
+            record_type: record.record_type,
+            shared: record.shared,
+        }
+    }
+}
@@ -17,7 +17,7 @@ use buildkit_llb::prelude::*;
 async fn main() {
     env_logger::init();
 
-    if let Err(_) = run_frontend(DownloadFrontend).await {
+    if run_frontend(DownloadFrontend).await.is_err() {
         std::process::exit(1);
     }
 }
@@ -53,29 +53,12 @@ const OUTPUT_DIR: &str = "/opt";
 
 impl DownloadFrontend {
     fn image_spec() -> ImageSpecification {
-        ImageSpecification {
-            created: None,
-            author: None,
-
-            architecture: Architecture::Amd64,
-            os: OperatingSystem::Linux,
-
-            config: Some(ImageConfig {
-                entrypoint: Some(vec!["/bin/sh".into()]),
-                cmd: Some(vec!["-c".into(), "/usr/bin/sha256sum *".into()]),
-                env: None,
-                user: None,
-                working_dir: Some(OUTPUT_DIR.into()),
-
-                labels: None,
-                volumes: None,
-                exposed_ports: None,
-                stop_signal: None,
-            }),
-
-            rootfs: None,
-            history: None,
-        }
+        let mut config = ImageConfig::default();
+        config.entrypoint = Some(vec!["/bin/sh".into()]);
+        config.cmd = Some(vec!["-c".into(), "/usr/bin/sha256sum *".into()]);
+        config.working_dir = Some(OUTPUT_DIR.into());
+
+        ImageSpecification::new(Architecture::Amd64, OperatingSystem::Linux).with_config(config)
     }
 
     async fn solve(bridge: &Bridge, options: DownloadOptions) -> Result<OutputRef, Error> {
@@ -90,19 +73,23 @@ impl DownloadFrontend {
                 .await?,
         )?;
 
-        bridge
-            .solve_with_cache(
-                Terminal::with(Self::construct_llb(dockerfile_contents)?),
-                options.cache_entries(),
-            )
-            .await
+        let output = {
+            bridge
+                .solve_with_cache(
+                    Terminal::with(Self::construct_llb(dockerfile_contents)?),
+                    options.cache_entries(),
+                )
+                .await?
+        };
+
+        Ok(output)
     }
 
     fn construct_llb(dockerfile: String) -> Result<OperationOutput<'static>, Error> {
         let alpine = Source::image("alpine:latest").ref_counted();
 
         let builder_rootfs = Command::run("apk")
-            .args(&["add", "curl"])
+            .args(["add", "curl"])
             .custom_name("Installing curl")
             .mount(Mount::Layer(OutputIdx(0), alpine.output(), "/"))
             .ref_counted();
@@ -113,7 +100,7 @@ impl DownloadFrontend {
                 let full_path = PathBuf::from(OUTPUT_DIR).join(&relative_path);
 
                 let op = Command::run("curl")
-                    .args(&[&url.to_string(), "-o", &full_path.to_string_lossy()])
+                    .args([url.as_ref(), "-o", &full_path.to_string_lossy()])
                     .mount(Mount::ReadOnlyLayer(builder_rootfs.output(0), "/"))
                     .mount(Mount::Scratch(OutputIdx(0), OUTPUT_DIR))
                     .custom_name(format!("Downloading '{}'", relative_path.display()))
@@ -149,7 +136,7 @@ impl DownloadFrontend {
         let cmd_regex = Regex::new(r#"Download\s+"(.+)"\s+as\s+"(.+)""#).unwrap();
 
         dockerfile.lines().filter_map(move |line| {
-            let captures = cmd_regex.captures(&line)?;
+            let captures = cmd_regex.captures(line)?;
             Some(Url::parse(&captures[1]).map(|url| (url, captures[2].into())))
         })
     }
@@ -13,7 +13,7 @@ use buildkit_llb::prelude::*;
 async fn main() {
     env_logger::init();
 
-    if let Err(_) = run_frontend(ReverseFrontend).await {
+    if run_frontend(ReverseFrontend).await.is_err() {
         std::process::exit(1);
     }
 }
@@ -34,29 +34,10 @@ const OUTPUT_FILENAME: &str = "/reverse.dockerfile";
 
 impl ReverseFrontend {
     fn image_spec() -> ImageSpecification {
-        ImageSpecification {
-            created: None,
-            author: None,
-
-            architecture: Architecture::Amd64,
-            os: OperatingSystem::Linux,
-
-            config: Some(ImageConfig {
-                entrypoint: None,
-                cmd: Some(vec!["/bin/cat".into(), OUTPUT_FILENAME.into()]),
-                env: None,
-                user: None,
-                working_dir: None,
-
-                labels: None,
-                volumes: None,
-                exposed_ports: None,
-                stop_signal: None,
-            }),
-
-            rootfs: None,
-            history: None,
-        }
+        let mut config = ImageConfig::default();
+        config.cmd = Some(vec!["/bin/cat".into(), OUTPUT_FILENAME.into()]);
+
+        ImageSpecification::new(Architecture::Amd64, OperatingSystem::Linux).with_config(config)
     }
 
     async fn solve(bridge: &Bridge, dockerfile_path: &str) -> Result<OutputRef, Error> {
@@ -72,7 +53,6 @@ impl ReverseFrontend {
         let transformed_contents: String = {
             String::from_utf8_lossy(&dockerfile_contents)
                 .lines()
-                .into_iter()
                 .map(|line| {
                     line.trim()
                         .chars()
@@ -94,6 +74,6 @@ impl ReverseFrontend {
                 .output(0)
         };
 
-        bridge.solve(Terminal::with(llb)).await
+        Ok(bridge.solve(Terminal::with(llb)).await?)
     }
 }
@@ -11,7 +11,7 @@ use buildkit_llb::prelude::*;
 async fn main() {
     env_logger::init();
 
-    if let Err(_) = run_frontend(ReverseFrontend).await {
+    if run_frontend(ReverseFrontend).await.is_err() {
         std::process::exit(1);
     }
 }
@@ -34,29 +34,11 @@ impl Frontend for ReverseFrontend {
 
 impl ReverseFrontend {
     fn image_spec() -> ImageSpecification {
-        ImageSpecification {
-            created: None,
-            author: None,
-
-            architecture: Architecture::Amd64,
-            os: OperatingSystem::Linux,
-
-            config: Some(ImageConfig {
-                entrypoint: None,
-                cmd: Some(vec!["/bin/cat".into(), OUTPUT_FILENAME.into()]),
-                env: None,
-                user: None,
-                working_dir: Some("/output".into()),
-
-                labels: None,
-                volumes: None,
-                exposed_ports: None,
-                stop_signal: None,
-            }),
-
-            rootfs: None,
-            history: None,
-        }
+        let mut config = ImageConfig::default();
+        config.cmd = Some(vec!["/bin/cat".into(), OUTPUT_FILENAME.into()]);
+        config.working_dir = Some("/output".into());
+
+        ImageSpecification::new(Architecture::Amd64, OperatingSystem::Linux).with_config(config)
     }
 
     async fn solve(bridge: &Bridge, dockerfile_path: &str) -> Result<OutputRef, Error> {
@@ -76,23 +58,23 @@ impl ReverseFrontend {
         let mut test = None;
 
         for line in dockerfile_contents.lines() {
-            if line.starts_with("REPO:") {
-                repo = Some(line[5..].trim());
+            if let Some(value) = line.strip_prefix("REPO:") {
+                repo = Some(value.trim());
             }
 
-            if line.starts_with("TAG:") {
-                tag = Some(line[4..].trim());
+            if let Some(value) = line.strip_prefix("TAG:") {
+                tag = Some(value.trim());
             }
 
-            if line.starts_with("TEST:") {
-                test = Some(line[5..].trim());
+            if let Some(value) = line.strip_prefix("TEST:") {
+                test = Some(value.trim());
             }
         }
 
         let rootfs = Source::image("rust:latest");
         let install_command = match (repo, tag) {
             (Some(repo), Some(tag)) => Command::run("cargo")
-                .args(&["install", "--git", repo, "--tag", tag])
+                .args(["install", "--git", repo, "--tag", tag])
                 .mount(Mount::Layer(OutputIdx(0), rootfs.output(), "/"))
                 .mount(Mount::OptionalSshAgent("/tmp/ssh_agent.0"))
                 .env("PATH", PATH)
@@ -108,13 +90,13 @@ impl ReverseFrontend {
 
         let test_command = if let Some(test) = test {
             Command::run("/bin/sh")
-                .args(&["-c", &format!("{} > {}", test, OUTPUT_FILENAME)])
+                .args(["-c", &format!("{} > {}", test, OUTPUT_FILENAME)])
                 .mount(Mount::Layer(OutputIdx(0), install_command.output(0), "/"))
                 .env("PATH", PATH)
         } else {
             bail!("Missing TEST directive!");
         };
 
-        bridge.solve(Terminal::with(test_command.output(0))).await
+        Ok(bridge.solve(Terminal::with(test_command.output(0))).await?)
     }
 }
@@ -1,47 +1,122 @@
-use std::collections::HashMap;
+use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use failure::{bail, format_err, Error, ResultExt};
+use failure::Error;
 use log::*;
+use prost_types::Any;
 use tokio::sync::Mutex;
+use tokio::time;
 
 use tonic::transport::channel::Channel;
-use tonic::Request;
+use tonic::{Code, Request, Response};
 
 use buildkit_proto::google::rpc::Status;
 use buildkit_proto::moby::buildkit::v1::frontend::llb_bridge_client::LlbBridgeClient;
 use buildkit_proto::moby::buildkit::v1::frontend::{
-    result::Result as RefResult, ReadFileRequest, ResolveImageConfigRequest, Result as Output,
-    ReturnRequest, SolveRequest,
+    result::Result as RefResult, PingRequest, ReadDirRequest, ReadFileRequest,
+    ResolveImageConfigRequest, ReturnRequest, SolveRequest, StatFileRequest,
 };
 
-pub use buildkit_llb::ops::source::{ImageSource, ResolveMode};
+pub use buildkit_llb::ops::source::ImageSource;
 pub use buildkit_llb::ops::Terminal;
 pub use buildkit_proto::moby::buildkit::v1::frontend::FileRange;
 
-use crate::error::ErrorCode;
+use crate::caps::Capabilities;
+use crate::error::{BridgeError, ErrorCode};
 use crate::oci::ImageSpecification;
 use crate::options::common::CacheOptionsEntry;
+use crate::output::FrontendOutput;
+use crate::stat::FileStat;
 use crate::utils::OutputRef;
 
+/// Calls that are safe to send again if the previous attempt failed.
+const IDEMPOTENT_METHODS: &[&str] = &[
+    "ping",
+    "resolve_image_config",
+    "read_file",
+    "read_dir",
+    "stat_file",
+];
+
 #[derive(Clone)]
 pub struct Bridge {
-    client: Arc<Mutex<LlbBridgeClient<Channel>>>,
+    /// The client is cheap to clone and every call uses its own copy,
+    /// so concurrent requests are multiplexed over the HTTP/2 connection instead of waiting for each other.
+    client: LlbBridgeClient<Channel>,
+    caps: Arc<Mutex<Option<Capabilities>>>,
+
+    retry_policy: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+}
+
+/// How to retry bridge calls that failed with a transient error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    codes: Vec<Code>,
 }
 
 impl Bridge {
     pub(crate) fn new(channel: Channel) -> Self {
         Self {
-            client: Arc::new(Mutex::new(LlbBridgeClient::new(channel))),
+            client: LlbBridgeClient::new(channel),
+            caps: Arc::new(Mutex::new(None)),
+
+            retry_policy: None,
+            timeout: None,
+        }
+    }
+
+    /// Retry failed calls according to the policy. Calls are not retried by default.
+    /// Only read-only calls are retried, `solve` and `solve_frontend` are sent once because they aren't idempotent.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Fail calls with `DeadlineExceeded` if there is no response in time.
+    /// Use it on a clone of the bridge to set a deadline for specific calls.
+    ///
+    /// The deadline is local: tonic doesn't send `grpc-timeout` along with the request,
+    /// so the daemon keeps processing a call the frontend has already given up on.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Ask the daemon for its capabilities and workers.
+    pub async fn ping(&self) -> Result<Capabilities, BridgeError> {
+        debug!("pinging the daemon");
+        let response = {
+            self.call("ping", |mut client| async move {
+                client.ping(Request::new(PingRequest {})).await
+            })
+            .await?
+        };
+
+        let caps = Capabilities::from(response);
+        *self.caps.lock().await = Some(caps.clone());
+
+        Ok(caps)
+    }
+
+    /// Capabilities of the daemon. Only the first call sends `Ping`, the result is cached afterwards.
+    pub async fn capabilities(&self) -> Result<Capabilities, BridgeError> {
+        if let Some(ref caps) = *self.caps.lock().await {
+            return Ok(caps.clone());
         }
+
+        self.ping().await
     }
 
     pub async fn resolve_image_config(
         &self,
         image: &ImageSource,
         log: Option<&str>,
-    ) -> Result<(String, ImageSpecification), Error> {
+    ) -> Result<(String, ImageSpecification), BridgeError> {
         let request = ResolveImageConfigRequest {
             r#ref: image.canonical_name(),
             platform: None,
@@ -51,23 +126,29 @@ impl Bridge {
 
         debug!("requesting to resolve an image: {:?}", request);
         let response = {
-            self.client
-                .lock()
-                .await
-                .resolve_image_config(Request::new(request))
-                .await
-                .unwrap()
-                .into_inner()
+            self.call("resolve_image_config", |mut client| {
+                let request = request.clone();
+                async move { client.resolve_image_config(Request::new(request)).await }
+            })
+            .await?
+        };
+
+        let spec = {
+            serde_json::from_slice(&response.config).map_err(|error| {
+                BridgeError::InvalidResponse {
+                    method: "resolve_image_config",
+                    reason: format!("unable to parse image specification: {}", error),
+                }
+            })?
         };
 
-        Ok((
-            response.digest,
-            serde_json::from_slice(&response.config)
-                .context("Unable to parse image specification")?,
-        ))
+        Ok((response.digest, spec))
     }
 
-    pub async fn solve<'a, 'b: 'a>(&'a self, graph: Terminal<'b>) -> Result<OutputRef, Error> {
+    pub async fn solve<'a, 'b: 'a>(
+        &'a self,
+        graph: Terminal<'b>,
+    ) -> Result<OutputRef, BridgeError> {
         self.solve_with_cache(graph, &[]).await
     }
 
@@ -75,7 +156,7 @@ impl Bridge {
         &'a self,
         graph: Terminal<'b>,
         cache: &[CacheOptionsEntry],
-    ) -> Result<OutputRef, Error> {
+    ) -> Result<OutputRef, BridgeError> {
         debug!("serializing a graph to request");
         let request = SolveRequest {
             definition: Some(graph.into_definition()),
@@ -88,30 +169,40 @@ impl Bridge {
 
         debug!("solving with cache from: {:?}", cache);
         debug!("requesting to solve a graph");
-        let response = {
-            self.client
-                .lock()
-                .await
-                .solve(Request::new(request))
-                .await
-                .context("Unable to solve the graph")?
-                .into_inner()
-                .result
-                .ok_or_else(|| format_err!("Unable to extract solve result"))?
-        };
+        self.send_solve(request).await
+    }
 
-        debug!("got response: {:#?}", response);
+    /// Let another frontend (e.g. `dockerfile.v0` or `gateway.v0`) solve the build and return its result.
+    /// Named `inputs` (e.g. `context` and `dockerfile`) replace local sources of the frontend
+    /// and require `Capabilities::supports_frontend_inputs`.
+    pub async fn solve_frontend<'a, 'b: 'a, S, O, I>(
+        &'a self,
+        name: S,
+        options: O,
+        inputs: I,
+    ) -> Result<OutputRef, BridgeError>
+    where
+        S: Into<String>,
+        O: IntoIterator<Item = (String, String)>,
+        I: IntoIterator<Item = (String, Terminal<'b>)>,
+    {
+        let request = SolveRequest {
+            frontend: name.into(),
+            frontend_opt: options.into_iter().collect(),
+            frontend_inputs: {
+                inputs
+                    .into_iter()
+                    .map(|(name, graph)| (name, graph.into_definition()))
+                    .collect()
+            },
 
-        let inner = {
-            response
-                .result
-                .ok_or_else(|| format_err!("Unable to extract solve result"))?
+            allow_result_return: true,
+
+            ..Default::default()
         };
 
-        match inner {
-            RefResult::Ref(inner) => Ok(OutputRef(inner)),
-            other => bail!("Unexpected solve response: {:?}", other),
-        }
+        debug!("requesting to solve with the {} frontend", request.frontend);
+        self.send_solve(request).await
     }
 
     pub async fn read_file<'a, 'b: 'a, P>(
@@ -119,7 +210,7 @@ impl Bridge {
         layer: &'b OutputRef,
         path: P,
         range: Option<FileRange>,
-    ) -> Result<Vec<u8>, Error>
+    ) -> Result<Vec<u8>, BridgeError>
     where
         P: Into<PathBuf>,
     {
@@ -133,50 +224,97 @@ impl Bridge {
         };
 
         let response = {
-            self.client
-                .lock()
-                .await
-                .read_file(Request::new(request))
-                .await
-                .context("Unable to read the file")?
-                .into_inner()
-                .data
+            self.call("read_file", |mut client| {
+                let request = request.clone();
+                async move { client.read_file(Request::new(request)).await }
+            })
+            .await?
         };
 
-        Ok(response)
+        Ok(response.data)
     }
 
-    pub(crate) async fn finish_with_success(
-        self,
-        output: OutputRef,
-        config: Option<ImageSpecification>,
-    ) -> Result<(), Error> {
-        let mut metadata = HashMap::new();
+    /// List the directory entries, optionally filtered with a glob `include_pattern`.
+    pub async fn read_dir<'a, 'b: 'a, P>(
+        &'a self,
+        layer: &'b OutputRef,
+        path: P,
+        include_pattern: Option<&str>,
+    ) -> Result<Vec<FileStat>, BridgeError>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir_path = path.into().display().to_string();
+        debug!("requesting a directory contents: {:#?}", dir_path);
+
+        let request = ReadDirRequest {
+            r#ref: layer.0.clone(),
+            dir_path,
+            include_pattern: include_pattern.unwrap_or_default().into(),
+        };
+
+        let response = {
+            self.call("read_dir", |mut client| {
+                let request = request.clone();
+                async move { client.read_dir(Request::new(request)).await }
+            })
+            .await?
+        };
+
+        Ok(response.entries.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn stat_file<'a, 'b: 'a, P>(
+        &'a self,
+        layer: &'b OutputRef,
+        path: P,
+    ) -> Result<FileStat, BridgeError>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into().display().to_string();
+        debug!("requesting a file stat: {:#?}", path);
+
+        let request = StatFileRequest {
+            r#ref: layer.0.clone(),
+            path,
+        };
+
+        let response = {
+            self.call("stat_file", |mut client| {
+                let request = request.clone();
+                async move { client.stat_file(Request::new(request)).await }
+            })
+            .await?
+        };
+
+        match response.stat {
+            Some(stat) => Ok(stat.into()),
 
-        if let Some(config) = config {
-            metadata.insert("containerimage.config".into(), serde_json::to_vec(&config)?);
+            None => Err(BridgeError::InvalidResponse {
+                method: "stat_file",
+                reason: "unable to extract file stat".into(),
+            }),
         }
+    }
 
+    pub(crate) async fn finish_with_success(self, output: FrontendOutput) -> Result<(), Error> {
         let request = ReturnRequest {
             error: None,
-            result: Some(Output {
-                result: Some(RefResult::Ref(output.0)),
-                metadata,
-            }),
+            result: Some(output.into_result()?),
         };
 
-        self.client
-            .lock()
-            .await
-            .r#return(Request::new(request))
-            .await?;
-
-        // TODO: gracefully shutdown the HTTP/2 connection
+        self.client.clone().r#return(Request::new(request)).await?;
 
         Ok(())
     }
 
-    pub(crate) async fn finish_with_error<S>(self, code: ErrorCode, message: S) -> Result<(), Error>
+    pub(crate) async fn finish_with_error<S>(
+        self,
+        code: ErrorCode,
+        message: S,
+        details: Vec<Any>,
+    ) -> Result<(), Error>
     where
         S: Into<String>,
     {
@@ -185,19 +323,138 @@ impl Bridge {
             error: Some(Status {
                 code: code as i32,
                 message: message.into(),
-                details: vec![],
+                details,
             }),
         };
 
         debug!("sending an error result: {:#?}", request);
-        self.client
-            .lock()
-            .await
-            .r#return(Request::new(request))
-            .await?;
-
-        // TODO: gracefully shutdown the HTTP/2 connection
+        self.client.clone().r#return(Request::new(request)).await?;
 
         Ok(())
     }
+
+    async fn send_solve(&self, request: SolveRequest) -> Result<OutputRef, BridgeError> {
+        let response = {
+            self.call("solve", |mut client| {
+                let request = request.clone();
+                async move { client.solve(Request::new(request)).await }
+            })
+            .await?
+        };
+
+        debug!("got response: {:#?}", response);
+
+        match response.result.and_then(|result| result.result) {
+            Some(RefResult::Ref(inner)) => Ok(OutputRef(inner)),
+
+            Some(other) => Err(BridgeError::InvalidResponse {
+                method: "solve",
+                reason: format!("unexpected solve result: {:?}", other),
+            }),
+
+            None => Err(BridgeError::InvalidResponse {
+                method: "solve",
+                reason: "unable to extract solve result".into(),
+            }),
+        }
+    }
+
+    /// Send the request (several times if the retry policy allows) within the deadline.
+    async fn call<T, F, R>(&self, method: &'static str, mut send: F) -> Result<T, BridgeError>
+    where
+        F: FnMut(LlbBridgeClient<Channel>) -> R,
+        R: Future<Output = Result<Response<T>, tonic::Status>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let response = send(self.client.clone());
+            let result = match self.timeout {
+                Some(timeout) => match time::timeout(timeout, response).await {
+                    Ok(result) => result,
+                    Err(_) => Err(tonic::Status::new(
+                        Code::DeadlineExceeded,
+                        format!("no response in {:?}", timeout),
+                    )),
+                },
+
+                None => response.await,
+            };
+
+            let status = match result {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) => status,
+            };
+
+            match self.retry_policy {
+                Some(ref policy)
+                    if IDEMPOTENT_METHODS.contains(&method)
+                        && attempt < policy.max_attempts
+                        && policy.is_retryable(&status) =>
+                {
+                    warn!(
+                        "{} failed (attempt {} of {}), retrying: {}",
+                        method,
+                        attempt,
+                        policy.max_attempts,
+                        status.message()
+                    );
+
+                    time::delay_for(policy.backoff * attempt).await;
+                    attempt += 1;
+                }
+
+                _ => return Err(BridgeError::Rpc { method, status }),
+            }
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Make up to `max_attempts` attempts when a call fails with `Unavailable`, `ResourceExhausted`,
+    /// `Aborted` or `DeadlineExceeded`. The delay between attempts grows linearly from 500ms.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            backoff: Duration::from_millis(500),
+            codes: vec![
+                Code::Unavailable,
+                Code::ResourceExhausted,
+                Code::Aborted,
+                Code::DeadlineExceeded,
+            ],
+        }
+    }
+
+    /// Delay before the second attempt. Every next attempt waits longer by the same amount.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Replace the list of status codes that are worth retrying.
+    pub fn retry_on<I>(mut self, codes: I) -> Self
+    where
+        I: IntoIterator<Item = Code>,
+    {
+        self.codes = codes.into_iter().collect();
+        self
+    }
+
+    pub fn is_retryable(&self, status: &tonic::Status) -> bool {
+        self.codes.contains(&status.code())
+    }
+}
+
+#[test]
+fn retry_policy() {
+    let policy = RetryPolicy::new(3);
+
+    assert!(policy.is_retryable(&tonic::Status::unavailable("registry hiccup")));
+    assert!(!policy.is_retryable(&tonic::Status::not_found("no such image")));
+
+    let policy = policy.retry_on(vec![Code::NotFound]);
+
+    assert!(!policy.is_retryable(&tonic::Status::unavailable("registry hiccup")));
+    assert!(policy.is_retryable(&tonic::Status::not_found("no such image")));
 }
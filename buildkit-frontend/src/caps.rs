@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use buildkit_llb::caps::{Capability, CapabilitySet, UnsupportedCapabilities};
+use buildkit_llb::prelude::Platform;
+use buildkit_proto::moby::buildkit::v1::apicaps::ApiCap;
+use buildkit_proto::moby::buildkit::v1::frontend::PongResponse;
+use buildkit_proto::moby::buildkit::v1::types::WorkerRecord;
+
+// https://github.com/moby/buildkit/blob/v0.7.0/frontend/gateway/pb/caps.go
+const CAP_READ_FILE: &str = "readfile";
+const CAP_READ_DIR: &str = "readdir";
+const CAP_STAT_FILE: &str = "statfile";
+const CAP_RETURN_MAP: &str = "returnmap";
+const CAP_IMPORT_CACHES: &str = "importcaches";
+const CAP_FRONTEND_INPUTS: &str = "frontend.inputs";
+
+#[cfg(any(test, feature = "mock"))]
+pub(crate) const FRONTEND_API_CAPS: &[&str] = &[
+    CAP_READ_FILE,
+    CAP_READ_DIR,
+    CAP_STAT_FILE,
+    CAP_RETURN_MAP,
+    CAP_IMPORT_CACHES,
+    CAP_FRONTEND_INPUTS,
+];
+
+/// Capabilities and workers the daemon reported with `Ping`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// Gateway API capabilities (`FrontendAPICaps`).
+    pub frontend_api: Vec<ApiCap>,
+
+    /// LLB capabilities (`LLBCaps`).
+    pub llb: Vec<ApiCap>,
+
+    pub workers: Vec<Worker>,
+}
+
+/// Worker of the daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Worker {
+    pub id: String,
+    pub labels: HashMap<String, String>,
+
+    /// Platforms the worker can run. The first one is native.
+    pub platforms: Vec<Platform>,
+}
+
+impl Capabilities {
+    /// Whether the gateway API capability (e.g. `readdir`) is present and enabled.
+    pub fn supports_frontend_api(&self, id: &str) -> bool {
+        is_enabled(&self.frontend_api, id)
+    }
+
+    pub fn supports_llb(&self, cap: Capability) -> bool {
+        is_enabled(&self.llb, cap.id())
+    }
+
+    /// Check whether the daemon can solve a graph with the required LLB capabilities.
+    pub fn check_llb(&self, required: &CapabilitySet) -> Result<(), UnsupportedCapabilities> {
+        required.check(&self.llb)
+    }
+
+    pub fn supports_read_file(&self) -> bool {
+        self.supports_frontend_api(CAP_READ_FILE)
+    }
+
+    pub fn supports_read_dir(&self) -> bool {
+        self.supports_frontend_api(CAP_READ_DIR)
+    }
+
+    pub fn supports_stat_file(&self) -> bool {
+        self.supports_frontend_api(CAP_STAT_FILE)
+    }
+
+    /// Whether the daemon accepts several named refs as a result.
+    pub fn supports_return_map(&self) -> bool {
+        self.supports_frontend_api(CAP_RETURN_MAP)
+    }
+
+    pub fn supports_import_caches(&self) -> bool {
+        self.supports_frontend_api(CAP_IMPORT_CACHES)
+    }
+
+    /// Whether the daemon can pass inputs to nested frontends.
+    pub fn supports_frontend_inputs(&self) -> bool {
+        self.supports_frontend_api(CAP_FRONTEND_INPUTS)
+    }
+
+    /// Platforms that at least one of the workers can run, without duplicates.
+    pub fn platforms(&self) -> Vec<Platform> {
+        let mut platforms: Vec<Platform> = vec![];
+
+        for platform in self.workers.iter().flat_map(|worker| &worker.platforms) {
+            if !platforms.contains(platform) {
+                platforms.push(platform.clone());
+            }
+        }
+
+        platforms
+    }
+
+    /// Native platform of the first worker. This is what the daemon builds for by default.
+    pub fn default_platform(&self) -> Option<&Platform> {
+        self.workers
+            .iter()
+            .filter_map(|worker| worker.platforms.first())
+            .next()
+    }
+}
+
+impl From<PongResponse> for Capabilities {
+    fn from(raw: PongResponse) -> Self {
+        Self {
+            frontend_api: raw.frontend_api_caps,
+            llb: raw.llb_caps,
+            workers: raw.workers.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<WorkerRecord> for Worker {
+    fn from(raw: WorkerRecord) -> Self {
+        Self {
+            id: raw.id,
+            labels: raw.labels,
+            platforms: raw.platforms.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+fn is_enabled(caps: &[ApiCap], id: &str) -> bool {
+    caps.iter().any(|cap| cap.id == id && cap.enabled)
+}
+
+#[test]
+fn conversion() {
+    use buildkit_proto::pb;
+
+    let cap = |id: &str, enabled: bool| ApiCap {
+        id: id.into(),
+        enabled,
+        ..Default::default()
+    };
+
+    let platform = |os: &str, architecture: &str| pb::Platform {
+        os: os.into(),
+        architecture: architecture.into(),
+        ..Default::default()
+    };
+
+    let caps = Capabilities::from(PongResponse {
+        frontend_api_caps: vec![cap("readfile", true), cap("readdir", false)],
+        llb_caps: vec![cap("exec.mount.bind", true)],
+        workers: vec![
+            WorkerRecord {
+                id: "first".into(),
+                platforms: vec![platform("linux", "arm64"), platform("linux", "arm")],
+                ..Default::default()
+            },
+            WorkerRecord {
+                id: "second".into(),
+                platforms: vec![platform("linux", "amd64"), platform("linux", "arm")],
+                ..Default::default()
+            },
+        ],
+    });
+
+    assert!(caps.supports_read_file());
+    assert!(!caps.supports_read_dir());
+    assert!(!caps.supports_stat_file());
+
+    assert!(caps.supports_llb(Capability::ExecMountBind));
+    assert!(!caps.supports_llb(Capability::ExecMountTmpfs));
+
+    assert_eq!(
+        caps.default_platform(),
+        Some(&Platform::new("linux", "arm64"))
+    );
+
+    assert_eq!(
+        caps.platforms(),
+        vec![
+            Platform::new("linux", "arm64"),
+            Platform::new("linux", "arm"),
+            Platform::new("linux", "amd64"),
+        ]
+    );
+}
@@ -1,4 +1,11 @@
+use std::fmt;
+
+use prost::Message;
+use prost_types::Any;
+use tonic::{Code, Status};
+
 /// https://godoc.org/google.golang.org/grpc/codes#Code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorCode {
     /// OK is returned on success.
     OK = 0,
@@ -117,3 +124,184 @@ pub enum ErrorCode {
     /// authentication credentials for the operation.
     Unauthenticated = 16,
 }
+
+/// Frontend error that is reported to BuildKit with a specific `ErrorCode` and structured details.
+///
+/// Can be returned from `Frontend::run` directly or as a cause of another error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontendError {
+    code: ErrorCode,
+    message: String,
+    details: Vec<Any>,
+}
+
+impl FrontendError {
+    pub fn new<S>(code: ErrorCode, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            code,
+            message: message.into(),
+            details: vec![],
+        }
+    }
+
+    /// Attach a protobuf message as a `google.protobuf.Any` detail, e.g. location of the error in a build file.
+    pub fn with_detail<S, M>(self, type_url: S, detail: &M) -> Self
+    where
+        S: Into<String>,
+        M: Message,
+    {
+        let mut value = Vec::with_capacity(detail.encoded_len());
+
+        // Encoding into a `Vec` can't run out of capacity.
+        detail.encode(&mut value).unwrap();
+
+        self.with_raw_detail(Any {
+            type_url: type_url.into(),
+            value,
+        })
+    }
+
+    pub fn with_raw_detail(mut self, detail: Any) -> Self {
+        self.details.push(detail);
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn details(&self) -> &[Any] {
+        &self.details
+    }
+}
+
+impl fmt::Display for FrontendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FrontendError {}
+
+/// Error of a `Bridge` call. Keeps the gRPC status, so callers can tell e.g. a missing file from a network failure.
+#[derive(Debug)]
+pub enum BridgeError {
+    /// The daemon responded with an error status, or there was no response before the deadline.
+    Rpc {
+        method: &'static str,
+        status: Status,
+    },
+
+    /// The daemon responded successfully, but the response can't be used.
+    InvalidResponse {
+        method: &'static str,
+        reason: String,
+    },
+}
+
+impl BridgeError {
+    /// Name of the failed `Bridge` method.
+    pub fn method(&self) -> &'static str {
+        match self {
+            BridgeError::Rpc { method, .. } => method,
+            BridgeError::InvalidResponse { method, .. } => method,
+        }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            BridgeError::Rpc { status, .. } => status.code(),
+            BridgeError::InvalidResponse { .. } => Code::Internal,
+        }
+    }
+
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            BridgeError::Rpc { status, .. } => Some(status),
+            BridgeError::InvalidResponse { .. } => None,
+        }
+    }
+
+    /// Whether the requested file, directory or image doesn't exist (`NotFound` status code).
+    pub fn is_not_found(&self) -> bool {
+        self.code() == Code::NotFound
+    }
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BridgeError::Rpc { method, status } => write!(
+                f,
+                "{} failed with {:?}: {}",
+                method,
+                status.code(),
+                status.message()
+            ),
+
+            BridgeError::InvalidResponse { method, reason } => {
+                write!(f, "{} returned an invalid response: {}", method, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+#[test]
+fn details_encoding() {
+    let detail = Any {
+        type_url: "type.googleapis.com/example.Location".into(),
+        value: vec![8, 42],
+    };
+
+    let error = {
+        FrontendError::new(ErrorCode::InvalidArgument, "unknown instruction")
+            .with_detail("type.googleapis.com/google.protobuf.Any", &detail)
+    };
+
+    assert_eq!(error.code(), ErrorCode::InvalidArgument);
+    assert_eq!(error.to_string(), "unknown instruction");
+    assert_eq!(error.details().len(), 1);
+    assert_eq!(
+        Any::decode(error.details()[0].value.as_slice()).unwrap(),
+        detail
+    );
+}
+
+#[test]
+fn bridge_errors() {
+    let error = BridgeError::Rpc {
+        method: "stat_file",
+        status: Status::unknown("lstat /Cargo.toml: no such file or directory"),
+    };
+
+    assert_eq!(error.code(), Code::Unknown);
+    assert!(!error.is_not_found());
+    assert_eq!(
+        error.to_string(),
+        "stat_file failed with Unknown: lstat /Cargo.toml: no such file or directory"
+    );
+
+    let error = BridgeError::Rpc {
+        method: "stat_file",
+        status: Status::not_found("lstat /Cargo.toml: no such file or directory"),
+    };
+
+    assert!(error.is_not_found());
+
+    let error = BridgeError::InvalidResponse {
+        method: "solve",
+        reason: "unable to extract solve result".into(),
+    };
+
+    assert_eq!(error.code(), Code::Internal);
+    assert!(!error.is_not_found());
+}
@@ -0,0 +1,373 @@
+use std::path::{Component, Path, PathBuf};
+
+use failure::{format_err, Error, ResultExt};
+use futures::stream::{self, Stream};
+
+use buildkit_proto::fsutil::pattern::glob_match;
+
+use crate::bridge::{Bridge, FileRange};
+use crate::error::BridgeError;
+use crate::stat::FileStat;
+use crate::utils::OutputRef;
+
+/// Read-only view of a solved reference's filesystem.
+#[derive(Clone)]
+pub struct Filesystem {
+    bridge: Bridge,
+    layer: OutputRef,
+}
+
+impl Filesystem {
+    pub fn new(bridge: Bridge, layer: OutputRef) -> Self {
+        Self { bridge, layer }
+    }
+
+    pub fn layer(&self) -> &OutputRef {
+        &self.layer
+    }
+
+    pub async fn stat<P>(&self, path: P) -> Result<FileStat, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        Ok(self.bridge.stat_file(&self.layer, path).await?)
+    }
+
+    pub async fn exists<P>(&self, path: P) -> Result<bool, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        match self.bridge.stat_file(&self.layer, path).await {
+            Ok(_) => Ok(true),
+            Err(ref error) if error.is_not_found() => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// List the directory. Paths of the entries are joined with the directory path.
+    pub async fn read_dir<P>(&self, path: P) -> Result<Vec<FileStat>, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let mut entries = self
+            .bridge
+            .read_dir(&self.layer, path.clone(), None)
+            .await?;
+
+        for entry in &mut entries {
+            entry.path = path.join(&entry.path);
+        }
+
+        Ok(entries)
+    }
+
+    pub async fn read<P>(&self, path: P) -> Result<Vec<u8>, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        Ok(self.bridge.read_file(&self.layer, path, None).await?)
+    }
+
+    pub async fn read_to_string<P>(&self, path: P) -> Result<String, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let contents = self.read(path.clone()).await?;
+
+        Ok(String::from_utf8(contents)
+            .with_context(|_| format!("File '{}' is not valid UTF-8", path.display()))?)
+    }
+
+    /// Read the file with `chunk_size` pieces, so large files don't have to be kept in memory.
+    /// Zero `chunk_size` is rejected with an error.
+    pub fn read_chunks<P>(
+        &self,
+        path: P,
+        chunk_size: usize,
+    ) -> impl Stream<Item = Result<Vec<u8>, Error>> + '_
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        stream::unfold(Some(0), move |offset| {
+            let path = path.clone();
+
+            async move {
+                let offset = offset?;
+
+                if chunk_size == 0 {
+                    return Some((Err(format_err!("Chunk size must not be zero")), None));
+                }
+
+                let range = FileRange {
+                    offset: offset as i64,
+                    length: chunk_size as i64,
+                };
+
+                match self.bridge.read_file(&self.layer, path, Some(range)).await {
+                    Ok(ref chunk) if chunk.is_empty() => None,
+
+                    Ok(chunk) => {
+                        let next_offset = if chunk.len() < chunk_size {
+                            None
+                        } else {
+                            Some(offset + chunk.len())
+                        };
+
+                        Some((Ok(chunk), next_offset))
+                    }
+
+                    Err(error) => Some((Err(error.into()), None)),
+                }
+            }
+        })
+    }
+
+    /// Recursively list everything inside the directory. Symbolic links are not followed.
+    pub async fn walk<P>(&self, path: P) -> Result<Vec<FileStat>, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let mut pending = vec![path.into()];
+        let mut entries = vec![];
+
+        while let Some(dir) = pending.pop() {
+            for entry in self.read_dir(dir).await? {
+                if entry.is_dir() {
+                    pending.push(entry.path.clone());
+                }
+
+                entries.push(entry);
+            }
+        }
+
+        entries.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+        Ok(entries)
+    }
+
+    /// Find entries matching the pattern, e.g. `**/Cargo.toml` or `/crates/*/src/main.rs`.
+    /// Patterns are resolved from the root and support `*`, `?` and `**` wildcards.
+    pub async fn glob(&self, pattern: &str) -> Result<Vec<FileStat>, Error> {
+        let pattern = components(Path::new(pattern));
+        let literal_prefix = {
+            pattern
+                .iter()
+                .take_while(|component| !is_wildcard(component))
+                .collect::<Vec<_>>()
+        };
+
+        // The pattern without wildcards has a single candidate.
+        if literal_prefix.len() == pattern.len() {
+            let path = Path::new("/").join(pattern.join("/"));
+
+            return match self.stat(path.clone()).await {
+                Ok(mut entry) => {
+                    entry.path = path;
+                    Ok(vec![entry])
+                }
+
+                Err(ref error) if is_not_found(error) => Ok(vec![]),
+                Err(error) => Err(error),
+            };
+        }
+
+        let base = Path::new("/").join(
+            literal_prefix
+                .iter()
+                .map(|item| item.as_str())
+                .collect::<PathBuf>(),
+        );
+        let entries = match self.walk(base).await {
+            Ok(entries) => entries,
+            Err(ref error) if is_not_found(error) => vec![],
+            Err(error) => return Err(error),
+        };
+
+        Ok(entries
+            .into_iter()
+            .filter(|entry| glob_match(&pattern, &components(&entry.path)))
+            .collect())
+    }
+}
+
+impl Bridge {
+    /// Read-only filesystem view of the solved reference.
+    pub fn filesystem(&self, layer: &OutputRef) -> Filesystem {
+        Filesystem::new(self.clone(), layer.clone())
+    }
+}
+
+fn is_not_found(error: &Error) -> bool {
+    error
+        .iter_chain()
+        .filter_map(|cause| cause.downcast_ref::<BridgeError>())
+        .any(BridgeError::is_not_found)
+}
+
+pub(crate) fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_wildcard(component: &str) -> bool {
+    component.contains('*') || component.contains('?')
+}
+
+#[test]
+fn glob_matching() {
+    let check = |pattern: &str, path: &str| {
+        glob_match(
+            &components(Path::new(pattern)),
+            &components(Path::new(path)),
+        )
+    };
+
+    assert!(check("**/Cargo.toml", "/Cargo.toml"));
+    assert!(check("**/Cargo.toml", "/crates/core/Cargo.toml"));
+    assert!(!check("**/Cargo.toml", "/crates/core/Cargo.lock"));
+
+    assert!(check("/crates/*/src/main.rs", "/crates/cli/src/main.rs"));
+    assert!(!check(
+        "/crates/*/src/main.rs",
+        "/crates/cli/nested/src/main.rs"
+    ));
+
+    assert!(check("src/*.rs", "/src/lib.rs"));
+    assert!(check("src/?ib.rs", "/src/lib.rs"));
+    assert!(!check("src/*.rs", "/src/lib.toml"));
+    assert!(check("crates/**", "/crates/core/src/lib.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use buildkit_llb::prelude::*;
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::mock::MockBridge;
+    use crate::{Frontend, FrontendOutput, Options};
+
+    /// What the frontend observed through the filesystem view of the `context` fixture.
+    #[derive(Debug, Default)]
+    struct Findings {
+        chunks: Vec<Vec<Vec<u8>>>,
+        zero_chunks: Option<String>,
+        exists: Vec<bool>,
+        walk: Vec<PathBuf>,
+        globs: Vec<Vec<PathBuf>>,
+    }
+
+    struct Explorer(Arc<Mutex<Findings>>);
+
+    #[tonic::async_trait]
+    impl Frontend for Explorer {
+        async fn run(self, bridge: Bridge, _: Options) -> Result<FrontendOutput, Error> {
+            let context = {
+                bridge
+                    .solve(Terminal::with(Source::local("context").output()))
+                    .await?
+            };
+
+            let filesystem = bridge.filesystem(&context);
+            let mut findings = Findings::default();
+
+            // "Hello from the mock bridge!\n" is 28 bytes long.
+            for chunk_size in &[10, 14, 28, 64] {
+                findings.chunks.push(
+                    filesystem
+                        .read_chunks("/hello.txt", *chunk_size)
+                        .try_collect()
+                        .await?,
+                );
+            }
+
+            findings.zero_chunks = {
+                filesystem
+                    .read_chunks("/hello.txt", 0)
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .err()
+                    .map(|error| error.to_string())
+            };
+
+            for path in &["/hello.txt", "/src", "/missing.txt", "/missing/main.rs"] {
+                findings.exists.push(filesystem.exists(*path).await?);
+            }
+
+            findings.walk = paths(filesystem.walk("/").await?);
+
+            for pattern in &["**/*.rs", "/hello.txt", "/missing.txt", "/missing/**/*.rs"] {
+                findings.globs.push(paths(filesystem.glob(pattern).await?));
+            }
+
+            *self.0.lock().unwrap() = findings;
+            Ok(FrontendOutput::with_ref(context))
+        }
+    }
+
+    fn paths(entries: Vec<FileStat>) -> Vec<PathBuf> {
+        entries.into_iter().map(|entry| entry.path).collect()
+    }
+
+    #[tokio::test]
+    async fn filesystem_view() {
+        let findings = Arc::new(Mutex::new(Findings::default()));
+        let report = {
+            MockBridge::new()
+                .with_solve_result("context")
+                .with_ref("context", "tests/fixtures/context")
+                .run(Explorer(findings.clone()))
+                .await
+                .unwrap()
+        };
+
+        assert!(report.returned_error().is_none());
+
+        let findings = findings.lock().unwrap();
+        let sizes = |chunks: &[Vec<u8>]| chunks.iter().map(Vec::len).collect::<Vec<_>>();
+
+        assert_eq!(sizes(&findings.chunks[0]), vec![10, 10, 8]);
+        assert_eq!(sizes(&findings.chunks[1]), vec![14, 14]);
+        assert_eq!(sizes(&findings.chunks[2]), vec![28]);
+        assert_eq!(sizes(&findings.chunks[3]), vec![28]);
+        assert_eq!(
+            findings.chunks[0].concat(),
+            b"Hello from the mock bridge!\n"
+        );
+
+        assert_eq!(
+            findings.zero_chunks,
+            Some("Chunk size must not be zero".into())
+        );
+
+        assert_eq!(findings.exists, vec![true, true, false, false]);
+
+        assert_eq!(
+            findings.walk,
+            vec![
+                PathBuf::from("/hello.txt"),
+                PathBuf::from("/src"),
+                PathBuf::from("/src/main.rs"),
+            ]
+        );
+
+        assert_eq!(
+            findings.globs,
+            vec![
+                vec![PathBuf::from("/src/main.rs")],
+                vec![PathBuf::from("/hello.txt")],
+                vec![],
+                vec![],
+            ]
+        );
+    }
+}
@@ -4,22 +4,29 @@
 use failure::{Error, ResultExt};
 use log::*;
 use serde::de::DeserializeOwned;
-use tonic::transport::Endpoint;
-use tower::service_fn;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 mod bridge;
 mod error;
+mod output;
+mod stat;
 mod stdio;
+mod transport;
 mod utils;
 
+pub mod caps;
+pub mod fs;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod oci;
 pub mod options;
+pub mod syntax;
 
-use oci::ImageSpecification;
-
-pub use self::bridge::Bridge;
-pub use self::error::ErrorCode;
+pub use self::bridge::{Bridge, RetryPolicy};
+pub use self::error::{BridgeError, ErrorCode, FrontendError};
 pub use self::options::Options;
+pub use self::output::{FrontendOutput, MultiPlatformOutput};
+pub use self::stat::FileStat;
 pub use self::stdio::{stdio_connector, StdioSocket};
 pub use self::utils::{ErrorWithCauses, OutputRef};
 
@@ -31,44 +38,47 @@ where
     async fn run(self, bridge: Bridge, options: O) -> Result<FrontendOutput, Error>;
 }
 
-pub struct FrontendOutput {
-    output: OutputRef,
-    image_spec: Option<ImageSpecification>,
-}
+pub async fn run_frontend<F, O>(frontend: F) -> Result<(), Error>
+where
+    F: Frontend<O>,
+    O: DeserializeOwned,
+{
+    let socket = StdioSocket::try_new().context("Unable to open stdio")?;
 
-impl FrontendOutput {
-    pub fn with_ref(output: OutputRef) -> Self {
-        Self {
-            output,
-            image_spec: None,
-        }
-    }
+    run_frontend_with_io(frontend, socket).await
+}
 
-    pub fn with_spec_and_ref(spec: ImageSpecification, output: OutputRef) -> Self {
-        Self {
-            output,
-            image_spec: Some(spec),
-        }
-    }
+/// Run the frontend over a custom transport instead of stdio.
+///
+/// After the result is returned the HTTP/2 connection is closed and the transport is shut down.
+pub async fn run_frontend_with_io<F, O, IO>(frontend: F, io: IO) -> Result<(), Error>
+where
+    F: Frontend<O>,
+    O: DeserializeOwned,
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    run_frontend_with_env(frontend, io, std::env::vars().collect()).await
 }
 
-pub async fn run_frontend<F, O>(frontend: F) -> Result<(), Error>
+/// Run the frontend with options from `env` instead of the process environment.
+pub(crate) async fn run_frontend_with_env<F, O, IO>(
+    frontend: F,
+    io: IO,
+    env: Vec<(String, String)>,
+) -> Result<(), Error>
 where
     F: Frontend<O>,
     O: DeserializeOwned,
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
 {
-    let channel = {
-        Endpoint::from_static("http://[::]:50051")
-            .connect_with_connector(service_fn(stdio_connector))
-            .await?
-    };
+    let (channel, closed) = transport::connect(io).await?;
 
     let bridge = Bridge::new(channel);
 
-    match frontend_entrypoint(&bridge, frontend).await {
+    match frontend_entrypoint(&bridge, frontend, env).await {
         Ok(output) => {
             bridge
-                .finish_with_success(output.output, output.image_spec)
+                .finish_with_success(output)
                 .await
                 .context("Unable to send a success result")?;
         }
@@ -78,29 +88,119 @@ where
 
             error!("Frontend entrypoint failed: {}", error);
 
-            // https://godoc.org/google.golang.org/grpc/codes#Code
+            let (code, details) = {
+                error
+                    .0
+                    .iter_chain()
+                    .find_map(|cause| cause.downcast_ref::<FrontendError>())
+                    .map(|cause| (cause.code(), cause.details().to_vec()))
+                    .unwrap_or((ErrorCode::Unknown, vec![]))
+            };
+
             bridge
                 .finish_with_error(
-                    ErrorCode::Unknown,
+                    code,
                     ErrorWithCauses::single_line(error.into_inner()).to_string(),
+                    details,
                 )
                 .await
                 .context("Unable to send an error result")?;
         }
     }
 
-    // TODO: gracefully shutdown the HTTP/2 connection
+    // The bridge is consumed by now, so the connection can send GOAWAY and shut the transport down.
+    closed.wait().await;
 
     Ok(())
 }
 
-async fn frontend_entrypoint<F, O>(bridge: &Bridge, frontend: F) -> Result<FrontendOutput, Error>
+async fn frontend_entrypoint<F, O>(
+    bridge: &Bridge,
+    frontend: F,
+    env: Vec<(String, String)>,
+) -> Result<FrontendOutput, Error>
 where
     F: Frontend<O>,
     O: DeserializeOwned,
 {
-    let options = options::from_env(std::env::vars()).context("Unable to parse options")?;
+    let options = options::from_env(env).context("Unable to parse options")?;
 
     debug!("running a frontend entrypoint");
     frontend.run(bridge.clone(), options).await
 }
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Any;
+
+    use super::*;
+    use crate::mock::MockBridge;
+
+    /// Fails with a `FrontendError`, either directly or as a cause of a more generic error.
+    struct FailingFrontend {
+        wrapped: bool,
+    }
+
+    #[tonic::async_trait]
+    impl Frontend for FailingFrontend {
+        async fn run(self, _: Bridge, _: Options) -> Result<FrontendOutput, Error> {
+            let error = {
+                FrontendError::new(ErrorCode::InvalidArgument, "unknown instruction 'RUNN'")
+                    .with_raw_detail(location_detail())
+            };
+
+            if self.wrapped {
+                Err(Error::from(error)
+                    .context("Unable to parse Dockerfile")
+                    .into())
+            } else {
+                Err(error.into())
+            }
+        }
+    }
+
+    fn location_detail() -> Any {
+        Any {
+            type_url: "type.googleapis.com/example.Location".into(),
+            value: vec![8, 3],
+        }
+    }
+
+    #[tokio::test]
+    async fn frontend_error_reaches_return() {
+        for wrapped in [false, true] {
+            let report = {
+                MockBridge::new()
+                    .run(FailingFrontend { wrapped })
+                    .await
+                    .unwrap()
+            };
+
+            let error = report.returned_error().unwrap();
+
+            assert!(report.returned_ref().is_none());
+            assert_eq!(error.code, ErrorCode::InvalidArgument as i32);
+            assert!(error.message.contains("unknown instruction 'RUNN'"));
+            assert_eq!(error.details, vec![location_detail()]);
+        }
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_unknown() {
+        struct Broken;
+
+        #[tonic::async_trait]
+        impl Frontend for Broken {
+            async fn run(self, _: Bridge, _: Options) -> Result<FrontendOutput, Error> {
+                Err(failure::err_msg("something went wrong"))
+            }
+        }
+
+        let report = MockBridge::new().run(Broken).await.unwrap();
+        let error = report.returned_error().unwrap();
+
+        assert_eq!(error.code, ErrorCode::Unknown as i32);
+        assert_eq!(error.message, "something went wrong");
+        assert!(error.details.is_empty());
+    }
+}
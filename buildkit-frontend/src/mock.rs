@@ -0,0 +1,592 @@
+//! In-process `LLBBridge` server to test frontends without a BuildKit daemon.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use failure::{Error, ResultExt};
+use futures::stream;
+use log::*;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UnixStream;
+use tonic::transport::server::Connected;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use buildkit_proto::fsutil::mode::{MODE_DIR, MODE_SYMLINK};
+use buildkit_proto::fsutil::pattern::glob_match;
+use buildkit_proto::fsutil::types::Stat;
+use buildkit_proto::google::rpc::Status as ErrorStatus;
+use buildkit_proto::moby::buildkit::v1::apicaps::ApiCap;
+use buildkit_proto::moby::buildkit::v1::frontend::llb_bridge_server::{LlbBridge, LlbBridgeServer};
+use buildkit_proto::moby::buildkit::v1::frontend::{
+    result::Result as RefResult, InputsRequest, InputsResponse, PingRequest, PongResponse,
+    ReadDirRequest, ReadDirResponse, ReadFileRequest, ReadFileResponse, ResolveImageConfigRequest,
+    ResolveImageConfigResponse, Result as Output, ReturnRequest, ReturnResponse, SolveRequest,
+    SolveResponse, StatFileRequest, StatFileResponse,
+};
+use buildkit_proto::moby::buildkit::v1::types::WorkerRecord;
+use buildkit_proto::pb;
+
+use crate::caps::FRONTEND_API_CAPS;
+use crate::fs::components;
+use crate::Frontend;
+
+/// Fake daemon that serves fixtures and records what the frontend asked for.
+pub struct MockBridge {
+    options: Vec<String>,
+    fixtures: Fixtures,
+    solve_results: VecDeque<String>,
+}
+
+/// Requests the frontend sent to the mock bridge.
+#[derive(Debug, Default, Clone)]
+pub struct MockReport {
+    /// Every `Solve` request in order.
+    pub solves: Vec<SolveRequest>,
+
+    /// The final `Return` request.
+    pub result: Option<ReturnRequest>,
+}
+
+struct Fixtures {
+    refs: HashMap<String, PathBuf>,
+    images: HashMap<String, (String, PathBuf)>,
+    pong: PongResponse,
+}
+
+/// Server end of the socket pair. tonic only serves connections that implement `Connected`.
+struct MockSocket(UnixStream);
+
+struct MockService {
+    fixtures: Fixtures,
+    solve_results: Mutex<VecDeque<String>>,
+    report: Arc<Mutex<MockReport>>,
+}
+
+impl MockBridge {
+    pub fn new() -> Self {
+        Self {
+            options: vec![],
+            solve_results: VecDeque::new(),
+
+            fixtures: Fixtures {
+                refs: HashMap::new(),
+                images: HashMap::new(),
+                pong: default_pong(),
+            },
+        }
+    }
+
+    /// Pass a frontend option in the `key=value` form, e.g. `filename=Dockerfile`.
+    pub fn with_option<S>(mut self, option: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.options.push(option.into());
+        self
+    }
+
+    /// Serve `ReadFile`, `ReadDir` and `StatFile` calls for the `id` ref from a local directory.
+    pub fn with_ref<S, P>(mut self, id: S, dir: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.fixtures.refs.insert(id.into(), dir.into());
+        self
+    }
+
+    /// Answer `ResolveImageConfig` for the image with the digest and the image configuration JSON
+    /// from a local file. The image is matched by its canonical name, e.g. `docker.io/library/alpine:latest`.
+    pub fn with_image<S, D, P>(mut self, name: S, digest: D, config: P) -> Self
+    where
+        S: Into<String>,
+        D: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.fixtures
+            .images
+            .insert(name.into(), (digest.into(), config.into()));
+
+        self
+    }
+
+    /// Ref to answer the next `Solve` call with. Calls without a queued ref get `solve-N` refs,
+    /// where `N` is the number of the call starting from 1.
+    pub fn with_solve_result<S>(mut self, id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.solve_results.push_back(id.into());
+        self
+    }
+
+    /// Replace the `Ping` response. By default all gateway capabilities are enabled,
+    /// there are no LLB capabilities and a single `linux/amd64` worker.
+    pub fn with_capabilities(mut self, pong: PongResponse) -> Self {
+        self.fixtures.pong = pong;
+        self
+    }
+
+    /// Run the frontend against the mock and collect its requests.
+    pub async fn run<F, O>(self, frontend: F) -> Result<MockReport, Error>
+    where
+        F: Frontend<O>,
+        O: DeserializeOwned,
+    {
+        let (client, server) = UnixStream::pair().context("Unable to create a socket pair")?;
+
+        let env = {
+            self.options
+                .into_iter()
+                .enumerate()
+                .map(|(index, option)| (format!("BUILDKIT_FRONTEND_OPT_{}", index), option))
+                .collect()
+        };
+
+        let report = Arc::new(Mutex::new(MockReport::default()));
+        let service = MockService {
+            fixtures: self.fixtures,
+            solve_results: Mutex::new(self.solve_results),
+            report: report.clone(),
+        };
+
+        let server = {
+            Server::builder()
+                .add_service(LlbBridgeServer::new(service))
+                .serve_with_incoming(stream::iter(vec![Ok::<_, io::Error>(MockSocket(server))]))
+        };
+
+        tokio::spawn(async move {
+            if let Err(error) = server.await {
+                error!("Mock bridge failed: {}", error);
+            }
+        });
+
+        crate::run_frontend_with_env(frontend, client, env).await?;
+
+        let report = report.lock().unwrap().clone();
+        Ok(report)
+    }
+}
+
+impl Default for MockBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockReport {
+    /// Ref of a single-ref result.
+    pub fn returned_ref(&self) -> Option<&str> {
+        match self
+            .returned_result()
+            .and_then(|output| output.result.as_ref())
+        {
+            Some(RefResult::Ref(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Result metadata, e.g. `containerimage.config`.
+    pub fn returned_metadata(&self) -> Option<&HashMap<String, Vec<u8>>> {
+        self.returned_result().map(|output| &output.metadata)
+    }
+
+    pub fn returned_error(&self) -> Option<&ErrorStatus> {
+        self.result
+            .as_ref()
+            .and_then(|result| result.error.as_ref())
+    }
+
+    fn returned_result(&self) -> Option<&Output> {
+        self.result
+            .as_ref()
+            .and_then(|result| result.result.as_ref())
+    }
+}
+
+impl Fixtures {
+    /// Path of the file inside the ref fixture. Paths that escape the ref directory are rejected.
+    fn resolve(&self, id: &str, path: &str) -> Result<PathBuf, Status> {
+        let mut resolved = match self.refs.get(id) {
+            Some(dir) => dir.clone(),
+            None => return Err(Status::not_found(format!("unknown ref '{}'", id))),
+        };
+
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::RootDir | Component::CurDir => {}
+
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(Status::invalid_argument(format!(
+                        "{}: path escapes the ref",
+                        path
+                    )));
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+#[tonic::async_trait]
+impl LlbBridge for MockService {
+    async fn resolve_image_config(
+        &self,
+        request: Request<ResolveImageConfigRequest>,
+    ) -> Result<Response<ResolveImageConfigResponse>, Status> {
+        let request = request.into_inner();
+
+        match self.fixtures.images.get(&request.r#ref) {
+            Some((digest, path)) => Ok(Response::new(ResolveImageConfigResponse {
+                digest: digest.clone(),
+                config: std::fs::read(path).map_err(|error| into_status(path, error))?,
+            })),
+
+            None => Err(Status::not_found(format!("{}: not found", request.r#ref))),
+        }
+    }
+
+    async fn solve(
+        &self,
+        request: Request<SolveRequest>,
+    ) -> Result<Response<SolveResponse>, Status> {
+        let mut report = self.report.lock().unwrap();
+        report.solves.push(request.into_inner());
+
+        let id = {
+            self.solve_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| format!("solve-{}", report.solves.len()))
+        };
+
+        Ok(Response::new(SolveResponse {
+            result: Some(Output {
+                result: Some(RefResult::Ref(id)),
+                metadata: HashMap::new(),
+            }),
+
+            ..Default::default()
+        }))
+    }
+
+    async fn read_file(
+        &self,
+        request: Request<ReadFileRequest>,
+    ) -> Result<Response<ReadFileResponse>, Status> {
+        let request = request.into_inner();
+        let path = self.fixtures.resolve(&request.r#ref, &request.file_path)?;
+        let mut data = std::fs::read(&path).map_err(|error| into_status(&path, error))?;
+
+        if let Some(range) = request.range {
+            let start = (range.offset.max(0) as usize).min(data.len());
+            let end = start
+                .saturating_add(range.length.max(0) as usize)
+                .min(data.len());
+
+            data = data[start..end].to_vec();
+        }
+
+        Ok(Response::new(ReadFileResponse { data }))
+    }
+
+    async fn read_dir(
+        &self,
+        request: Request<ReadDirRequest>,
+    ) -> Result<Response<ReadDirResponse>, Status> {
+        let request = request.into_inner();
+        let path = self.fixtures.resolve(&request.r#ref, &request.dir_path)?;
+        let pattern = components(Path::new(&request.include_pattern));
+
+        let mut entries = vec![];
+
+        for entry in std::fs::read_dir(&path).map_err(|error| into_status(&path, error))? {
+            let entry = entry.map_err(|error| into_status(&path, error))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if !pattern.is_empty() && !glob_match(&pattern, &components(Path::new(&name))) {
+                continue;
+            }
+
+            entries.push(stat(&entry.path(), name)?);
+        }
+
+        entries.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+        Ok(Response::new(ReadDirResponse { entries }))
+    }
+
+    async fn stat_file(
+        &self,
+        request: Request<StatFileRequest>,
+    ) -> Result<Response<StatFileResponse>, Status> {
+        let request = request.into_inner();
+        let path = self.fixtures.resolve(&request.r#ref, &request.path)?;
+
+        Ok(Response::new(StatFileResponse {
+            stat: Some(stat(&path, request.path)?),
+        }))
+    }
+
+    async fn ping(&self, _: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        Ok(Response::new(self.fixtures.pong.clone()))
+    }
+
+    async fn r#return(
+        &self,
+        request: Request<ReturnRequest>,
+    ) -> Result<Response<ReturnResponse>, Status> {
+        self.report.lock().unwrap().result = Some(request.into_inner());
+
+        Ok(Response::new(ReturnResponse {}))
+    }
+
+    /// The frontend is never started with inputs from the client.
+    async fn inputs(&self, _: Request<InputsRequest>) -> Result<Response<InputsResponse>, Status> {
+        Ok(Response::new(InputsResponse::default()))
+    }
+}
+
+impl Connected for MockSocket {}
+
+impl AsyncRead for MockSocket {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockSocket {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+fn default_pong() -> PongResponse {
+    PongResponse {
+        frontend_api_caps: {
+            FRONTEND_API_CAPS
+                .iter()
+                .map(|id| ApiCap {
+                    id: String::from(*id),
+                    enabled: true,
+                    ..Default::default()
+                })
+                .collect()
+        },
+
+        llb_caps: vec![],
+
+        workers: vec![WorkerRecord {
+            id: "mock".into(),
+            platforms: vec![pb::Platform {
+                os: "linux".into(),
+                architecture: "amd64".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+fn stat(path: &Path, name: String) -> Result<Stat, Status> {
+    let metadata = std::fs::symlink_metadata(path).map_err(|error| into_status(path, error))?;
+    let mut mode = metadata.permissions().mode() & 0o777;
+    let mut linkname = String::new();
+
+    if metadata.is_dir() {
+        mode |= MODE_DIR;
+    }
+
+    if metadata.file_type().is_symlink() {
+        mode |= MODE_SYMLINK;
+        linkname = {
+            std::fs::read_link(path)
+                .map_err(|error| into_status(path, error))?
+                .display()
+                .to_string()
+        };
+    }
+
+    Ok(Stat {
+        path: name,
+        mode,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        size: metadata.len() as i64,
+        mod_time: metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec(),
+        linkname,
+
+        ..Default::default()
+    })
+}
+
+fn into_status(path: &Path, error: io::Error) -> Status {
+    match error.kind() {
+        // `BridgeError::is_not_found` relies on the code, the message mimics the daemon.
+        io::ErrorKind::NotFound => {
+            Status::not_found(format!("{}: no such file or directory", path.display()))
+        }
+
+        _ => Status::internal(format!("{}: {}", path.display(), error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buildkit_llb::prelude::*;
+
+    use super::*;
+    use crate::{Bridge, FrontendOutput, Options};
+
+    struct TestFrontend;
+
+    #[tonic::async_trait]
+    impl Frontend for TestFrontend {
+        async fn run(self, bridge: Bridge, options: Options) -> Result<FrontendOutput, Error> {
+            let context = bridge
+                .solve(Terminal::with(Source::local("context").output()))
+                .await?;
+
+            let filesystem = bridge.filesystem(&context);
+            let greeting = filesystem
+                .read_to_string(options.get("filename").unwrap())
+                .await?;
+
+            let sources = filesystem.glob("**/*.rs").await?;
+            let (_, spec) = {
+                bridge
+                    .resolve_image_config(&Source::image("rust:latest"), None)
+                    .await?
+            };
+
+            let operation = {
+                FileSystem::mkfile(OutputIdx(0), LayerPath::Scratch("/greeting"))
+                    .data(format!("{} ({} sources)", greeting.trim(), sources.len()).into_bytes())
+                    .into_operation()
+            };
+
+            let output = operation.output(0);
+
+            Ok(FrontendOutput::with_spec_and_ref(
+                spec,
+                bridge.solve(Terminal::with(output)).await?,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_bridge() {
+        let report = {
+            MockBridge::new()
+                .with_option("filename=/hello.txt")
+                .with_solve_result("context")
+                .with_ref("context", "tests/fixtures/context")
+                .with_image(
+                    "docker.io/library/rust:latest",
+                    "sha256:0123456789abcdef",
+                    "tests/oci-image-spec.json",
+                )
+                .run(TestFrontend)
+                .await
+                .unwrap()
+        };
+
+        assert!(report.returned_error().is_none());
+        assert_eq!(report.solves.len(), 2);
+        assert_eq!(report.returned_ref(), Some("solve-2"));
+        assert!(report
+            .returned_metadata()
+            .unwrap()
+            .contains_key("containerimage.config"));
+    }
+
+    #[tokio::test]
+    async fn missing_fixture() {
+        let report = {
+            MockBridge::new()
+                .with_option("filename=/missing.txt")
+                .with_solve_result("context")
+                .with_ref("context", "tests/fixtures/context")
+                .run(TestFrontend)
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(report.solves.len(), 1);
+        assert!(report.returned_ref().is_none());
+        assert!(report
+            .returned_error()
+            .unwrap()
+            .message
+            .contains("no such file or directory"));
+    }
+
+    #[tokio::test]
+    async fn escaping_fixture() {
+        let report = {
+            MockBridge::new()
+                .with_option("filename=/../context/hello.txt")
+                .with_solve_result("context")
+                .with_ref("context", "tests/fixtures/context")
+                .run(TestFrontend)
+                .await
+                .unwrap()
+        };
+
+        assert!(report.returned_ref().is_none());
+        assert!(report
+            .returned_error()
+            .unwrap()
+            .message
+            .contains("path escapes the ref"));
+    }
+
+    #[test]
+    fn fixture_paths() {
+        let fixtures = MockBridge::new().with_ref("context", "fixtures").fixtures;
+
+        assert_eq!(
+            fixtures
+                .resolve("context", "/./src/../main.rs")
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+
+        assert_eq!(
+            fixtures.resolve("context", "/src/./main.rs").unwrap(),
+            Path::new("fixtures/src/main.rs")
+        );
+
+        assert_eq!(
+            fixtures.resolve("other", "main.rs").unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt;
 use std::path::PathBuf;
 
+use buildkit_llb::ops::exec::{Command, EmptyShellError, Shell};
+use buildkit_llb::prelude::{OperationOutput, Platform, State};
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -9,6 +12,7 @@ use serde_json::Value;
 // https://github.com/opencontainers/image-spec/blob/v1.0.1/config.md
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ImageSpecification {
     /// An combined date and time at which the image was created.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -24,6 +28,10 @@ pub struct ImageSpecification {
     /// The name of the operating system which the image is built to run on.
     pub os: OperatingSystem,
 
+    /// The variant of the CPU architecture, e.g. `v7` for `linux/arm/v7`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+
     /// The execution parameters which should be used as a base when running a container using the image.
     /// This field can be `None`, in which case any execution parameters should be specified at creation of the container.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -89,9 +97,10 @@ pub enum OperatingSystem {
     Windows,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(from = "RawImageConfig")]
 #[serde(into = "RawImageConfig")]
+#[non_exhaustive]
 pub struct ImageConfig {
     /// The username or UID which is a platform-specific structure that allows specific control over which user the process run as.
     pub user: Option<String>,
@@ -119,6 +128,9 @@ pub struct ImageConfig {
 
     /// The field contains the system call signal that will be sent to the container to exit.
     pub stop_signal: Option<Signal>,
+
+    /// Shell for shell-form commands (Docker extension of the format).
+    pub shell: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -150,6 +162,9 @@ struct RawImageConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     stop_signal: Option<Signal>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shell: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -210,9 +225,9 @@ impl TryFrom<String> for ExposedPort {
     }
 }
 
-impl Into<String> for ExposedPort {
-    fn into(self) -> String {
-        match self {
+impl From<ExposedPort> for String {
+    fn from(port: ExposedPort) -> Self {
+        match port {
             ExposedPort::Tcp(port) => format!("{}/tcp", port),
             ExposedPort::Udp(port) => format!("{}/udp", port),
         }
@@ -271,6 +286,7 @@ impl From<RawImageConfig> for ImageConfig {
             working_dir: raw.working_dir,
             labels: raw.labels,
             stop_signal: raw.stop_signal,
+            shell: raw.shell,
 
             env: raw.env.map(|inner| {
                 inner
@@ -289,42 +305,39 @@ impl From<RawImageConfig> for ImageConfig {
                     .collect()
             }),
 
-            exposed_ports: raw
-                .exposed_ports
-                .map(|inner| inner.into_iter().map(|(port, _)| port).collect()),
+            exposed_ports: raw.exposed_ports.map(|inner| inner.into_keys().collect()),
 
-            volumes: raw
-                .volumes
-                .map(|inner| inner.into_iter().map(|(volume, _)| volume).collect()),
+            volumes: raw.volumes.map(|inner| inner.into_keys().collect()),
         }
     }
 }
 
-impl Into<RawImageConfig> for ImageConfig {
-    fn into(self) -> RawImageConfig {
+impl From<ImageConfig> for RawImageConfig {
+    fn from(config: ImageConfig) -> Self {
         RawImageConfig {
-            user: self.user,
-            entrypoint: self.entrypoint,
-            cmd: self.cmd,
-            working_dir: self.working_dir,
-            labels: self.labels,
-            stop_signal: self.stop_signal,
-
-            env: self.env.map(|inner| {
+            user: config.user,
+            entrypoint: config.entrypoint,
+            cmd: config.cmd,
+            working_dir: config.working_dir,
+            labels: config.labels,
+            stop_signal: config.stop_signal,
+            shell: config.shell,
+
+            env: config.env.map(|inner| {
                 inner
                     .into_iter()
                     .map(|(key, value)| format!("{}={}", key, value))
                     .collect()
             }),
 
-            exposed_ports: self.exposed_ports.map(|inner| {
+            exposed_ports: config.exposed_ports.map(|inner| {
                 inner
                     .into_iter()
                     .map(|port| (port, Value::Object(Default::default())))
                     .collect()
             }),
 
-            volumes: self.volumes.map(|inner| {
+            volumes: config.volumes.map(|inner| {
                 inner
                     .into_iter()
                     .map(|volume| (volume, Value::Object(Default::default())))
@@ -334,6 +347,174 @@ impl Into<RawImageConfig> for ImageConfig {
     }
 }
 
+impl ImageSpecification {
+    /// Specification of an image for the `os` and `architecture` without configuration, rootfs and history.
+    /// Other fields can be set afterwards, e.g. with `ImageSpecification::with_config`.
+    pub fn new(architecture: Architecture, os: OperatingSystem) -> Self {
+        Self {
+            created: None,
+            author: None,
+            architecture,
+            os,
+            variant: None,
+            config: None,
+            rootfs: None,
+            history: None,
+        }
+    }
+
+    /// Set the execution parameters of the image.
+    pub fn with_config(mut self, config: ImageConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Platform the image was built for.
+    pub fn platform(&self) -> Platform {
+        let platform = Platform::new(self.os.to_string(), self.architecture.to_string());
+
+        match self.variant {
+            Some(ref variant) => platform.with_variant(variant.as_str()),
+            None => platform,
+        }
+    }
+
+    /// Build a state on top of the image `root` with the image configuration and platform applied.
+    /// Fails if the image has an empty `Shell`.
+    pub fn state<'a>(&self, root: OperationOutput<'a>) -> Result<State<'a>, EmptyShellError> {
+        let state = State::with_root(root).platform(self.platform());
+
+        match self.config {
+            Some(ref config) => config.apply_to_state(state),
+            None => Ok(state),
+        }
+    }
+}
+
+impl ImageConfig {
+    /// Apply `Env`, `WorkingDir` and `User` of the image to the command.
+    pub fn apply_to_command<'a>(&self, mut command: Command<'a>) -> Command<'a> {
+        if let Some(ref env) = self.env {
+            command = command.env_iter(env);
+        }
+
+        if let Some(ref working_dir) = self.working_dir {
+            command = command.cwd(working_dir.clone());
+        }
+
+        if let Some(ref user) = self.user {
+            command = command.user(user.clone());
+        }
+
+        command
+    }
+
+    /// Shell for shell-form commands: `Shell` of the image or `/bin/sh -c` by default.
+    /// Fails if the image has an empty `Shell`.
+    pub fn shell(&self) -> Result<Shell, EmptyShellError> {
+        match self.shell {
+            Some(ref shell) => Shell::new(shell.iter().cloned()),
+            None => Ok(Shell::default()),
+        }
+    }
+
+    /// Apply `Env`, `WorkingDir`, `User` and `Shell` of the image to the state.
+    /// Fails if the image has an empty `Shell`.
+    pub fn apply_to_state<'a>(&self, mut state: State<'a>) -> Result<State<'a>, EmptyShellError> {
+        state = state.shell(self.shell()?);
+
+        if let Some(ref env) = self.env {
+            for (name, value) in env {
+                state = state.env(name.clone(), value.clone());
+            }
+        }
+
+        if let Some(ref working_dir) = self.working_dir {
+            state = state.workdir(working_dir);
+        }
+
+        if let Some(ref user) = self.user {
+            state = state.user(user.clone());
+        }
+
+        Ok(state)
+    }
+
+    /// Arguments `docker run` would execute: `Entrypoint` followed either by `args` or by `Cmd` when `args` is empty.
+    pub fn run_args<I, S>(&self, args: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args = args.into_iter().map(Into::into).collect::<Vec<String>>();
+        let entrypoint = self.entrypoint.iter().flatten().cloned();
+
+        if args.is_empty() {
+            entrypoint
+                .chain(self.cmd.iter().flatten().cloned())
+                .collect()
+        } else {
+            entrypoint.chain(args).collect()
+        }
+    }
+
+    /// Command with the image defaults that runs `Entrypoint` with `args` (or with `Cmd` when `args` is empty).
+    /// Returns `None` when there is nothing to run.
+    pub fn command<'a, I, S>(&self, args: I) -> Option<Command<'a>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = self.run_args(args).into_iter();
+        let command = Command::run(args.next()?).args(args.collect::<Vec<_>>());
+
+        Some(self.apply_to_command(command))
+    }
+
+    /// Command with the image defaults that runs `Entrypoint` and `Cmd` like `docker run` does.
+    pub fn default_command<'a>(&self) -> Option<Command<'a>> {
+        self.command(Vec::<String>::new())
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Architecture::Amd64 => "amd64",
+            Architecture::I386 => "386",
+            Architecture::ARM => "arm",
+            Architecture::ARM64 => "arm64",
+            Architecture::PPC64le => "ppc64le",
+            Architecture::PPC64 => "ppc64",
+            Architecture::Mips64le => "mips64le",
+            Architecture::Mips64 => "mips64",
+            Architecture::Mipsle => "mipsle",
+            Architecture::Mips => "mips",
+            Architecture::S390x => "s390x",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for OperatingSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OperatingSystem::Darwin => "darwin",
+            OperatingSystem::Dragonfly => "dragonfly",
+            OperatingSystem::Freebsd => "freebsd",
+            OperatingSystem::Linux => "linux",
+            OperatingSystem::Netbsd => "netbsd",
+            OperatingSystem::Openbsd => "openbsd",
+            OperatingSystem::Plan9 => "plan9",
+            OperatingSystem::Solaris => "solaris",
+            OperatingSystem::Windows => "windows",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
 #[test]
 fn serialization() {
     use pretty_assertions::assert_eq;
@@ -344,6 +525,7 @@ fn serialization() {
         author: Some("Alyssa P. Hacker <alyspdev@example.com>".into()),
         architecture: Architecture::Amd64,
         os: OperatingSystem::Linux,
+        variant: None,
         rootfs: Some(ImageRootfs {
             diff_type: RootfsType::Layers,
             diff_ids: vec![
@@ -399,6 +581,7 @@ fn serialization() {
                 .collect(),
             ),
             stop_signal: Some(Signal::SIGKILL),
+            shell: None,
         }),
     };
 
@@ -420,6 +603,7 @@ fn min_serialization() {
 
         architecture: Architecture::Amd64,
         os: OperatingSystem::Linux,
+        variant: None,
         rootfs: Some(ImageRootfs {
             diff_type: RootfsType::Layers,
             diff_ids: vec![
@@ -438,3 +622,119 @@ fn min_serialization() {
         ref_spec
     );
 }
+
+#[test]
+fn image_defaults() {
+    use buildkit_llb::prelude::*;
+    use std::path::Path;
+
+    let spec: ImageSpecification =
+        serde_json::from_str(include_str!("../tests/oci-image-spec.json")).unwrap();
+
+    let config = spec.config.as_ref().unwrap();
+
+    assert_eq!(
+        config.run_args(Vec::<String>::new()),
+        vec![
+            "/bin/my-app-binary",
+            "--foreground",
+            "--config",
+            "/etc/my-app.d/default.cfg"
+        ]
+    );
+
+    assert_eq!(
+        config.run_args(vec!["--help"]),
+        vec!["/bin/my-app-binary", "--help"]
+    );
+
+    let image = Source::image("example/my-app:latest").ref_counted();
+    let state = spec.state(image.output()).unwrap();
+
+    assert_eq!(state.get_platform(), Some(&Platform::new("linux", "amd64")));
+    assert_eq!(state.get_cwd(), Path::new("/home/alice"));
+    assert_eq!(state.get_user(), "alice");
+    assert_eq!(
+        state.get_env("PATH"),
+        Some("/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin")
+    );
+
+    let image = Source::image("example/my-app:latest");
+    let command_definition = Terminal::with(
+        config
+            .default_command()
+            .unwrap()
+            .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+            .output(0),
+    )
+    .into_definition();
+
+    let image = Source::image("example/my-app:latest");
+    let manual_definition = Terminal::with(
+        Command::run("/bin/my-app-binary")
+            .args(["--foreground", "--config", "/etc/my-app.d/default.cfg"])
+            .env(
+                "PATH",
+                "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+            )
+            .cwd("/home/alice")
+            .user("alice")
+            .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+            .output(0),
+    )
+    .into_definition();
+
+    assert_eq!(command_definition.def, manual_definition.def);
+}
+
+#[test]
+fn image_shell() {
+    let config: ImageConfig =
+        serde_json::from_str(r#"{ "Shell": ["powershell", "-command"] }"#).unwrap();
+
+    assert_eq!(config.shell().unwrap().args(), &["powershell", "-command"]);
+
+    let config: ImageConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(config.shell(), Ok(Shell::default()));
+
+    let config: ImageConfig = serde_json::from_str(r#"{ "Shell": [] }"#).unwrap();
+    assert_eq!(config.shell(), Err(EmptyShellError));
+}
+
+#[test]
+fn constructors() {
+    let config = ImageConfig {
+        cmd: Some(vec!["/bin/cat".into()]),
+        ..Default::default()
+    };
+
+    let spec = ImageSpecification::new(Architecture::ARM64, OperatingSystem::Linux)
+        .with_config(config.clone());
+
+    assert_eq!(spec.platform(), Platform::new("linux", "arm64"));
+    assert_eq!(spec.config, Some(config));
+    assert_eq!(
+        serde_json::to_string(&spec).unwrap(),
+        r#"{"architecture":"arm64","os":"linux","config":{"Cmd":["/bin/cat"]}}"#
+    );
+}
+
+#[test]
+fn platform_variant() {
+    let spec: ImageSpecification =
+        serde_json::from_str(r#"{"architecture":"arm","os":"linux","variant":"v7","rootfs":null}"#)
+            .unwrap();
+
+    assert_eq!(spec.variant, Some("v7".into()));
+    assert_eq!(
+        spec.platform(),
+        Platform::new("linux", "arm").with_variant("v7")
+    );
+    assert_eq!(spec.platform().to_string(), "linux/arm/v7");
+
+    let spec: ImageSpecification =
+        serde_json::from_str(include_str!("../tests/oci-image-spec-min.json")).unwrap();
+
+    assert_eq!(spec.variant, None);
+    assert_eq!(spec.platform(), Platform::new("linux", "amd64"));
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fmt;
 
+use buildkit_llb::prelude::Platform;
 use buildkit_proto::moby::buildkit::v1::frontend::CacheOptionsEntry as CacheOptionsEntryProto;
 use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use serde::Deserialize;
@@ -54,21 +55,73 @@ impl CacheOptionsEntry {
     }
 }
 
-impl Into<CacheOptionsEntryProto> for CacheOptionsEntry {
-    fn into(self) -> CacheOptionsEntryProto {
+impl From<CacheOptionsEntry> for CacheOptionsEntryProto {
+    fn from(entry: CacheOptionsEntry) -> Self {
         CacheOptionsEntryProto {
-            r#type: self.cache_type.into(),
-            attrs: self.attrs,
+            r#type: entry.cache_type.into(),
+            attrs: entry.attrs,
         }
     }
 }
 
-impl Into<String> for CacheType {
-    fn into(self) -> String {
-        match self {
+impl From<CacheType> for String {
+    fn from(cache_type: CacheType) -> Self {
+        match cache_type {
             CacheType::Local => "local".into(),
             CacheType::Registry => "registry".into(),
             CacheType::Inline => "inline".into(),
         }
     }
 }
+
+/// Deserialize a comma-separated list of platforms, e.g. `platform=linux/amd64,linux/arm64`.
+pub fn deserialize_platforms<'de, D>(deserializer: D) -> Result<Vec<Platform>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_list(deserializer)?
+        .iter()
+        .map(|platform| platform.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+/// Deserialize a comma-separated list, either already split or as a single string.
+pub(crate) fn deserialize_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct ListVisitor;
+
+    impl<'de> Visitor<'de> for ListVisitor {
+        type Value = Vec<String>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("comma-separated list")
+        }
+
+        fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+            Ok(vec![])
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(split_list(value))
+        }
+
+        fn visit_seq<M>(self, seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            Deserialize::deserialize(de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(ListVisitor)
+}
+
+pub(crate) fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
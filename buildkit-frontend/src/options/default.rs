@@ -1,7 +1,9 @@
 use std::collections::BTreeMap;
 use std::iter::once;
 
+use buildkit_llb::prelude::Platform;
 use either::Either;
+use failure::{Error, ResultExt};
 use serde::Deserialize;
 
 #[derive(Debug, PartialEq, Deserialize)]
@@ -16,6 +18,7 @@ enum OptionValue {
     Flag(bool),
     Single(String),
     Multiple(Vec<String>),
+    Map(BTreeMap<String, String>),
 }
 
 impl Options {
@@ -28,6 +31,7 @@ impl Options {
                 OptionValue::Flag(exists) => *exists,
                 OptionValue::Single(_) => true,
                 OptionValue::Multiple(_) => true,
+                OptionValue::Map(_) => true,
             },
 
             None => false,
@@ -43,6 +47,7 @@ impl Options {
                 OptionValue::Flag(flag) => *flag,
                 OptionValue::Single(_) => false,
                 OptionValue::Multiple(_) => false,
+                OptionValue::Map(_) => false,
             },
 
             None => false,
@@ -59,6 +64,7 @@ impl Options {
                 OptionValue::Flag(_) => false,
                 OptionValue::Single(single) => single == value.as_ref(),
                 OptionValue::Multiple(values) => values.iter().any(|item| item == value.as_ref()),
+                OptionValue::Map(_) => false,
             },
 
             None => false,
@@ -74,6 +80,7 @@ impl Options {
                 OptionValue::Flag(_) => None,
                 OptionValue::Single(value) => Some(value.as_str()),
                 OptionValue::Multiple(values) => values.iter().map(String::as_str).next(),
+                OptionValue::Map(_) => None,
             },
 
             None => None,
@@ -91,11 +98,44 @@ impl Options {
                 OptionValue::Multiple(values) => {
                     Some(Either::Right(values.iter().map(String::as_str)))
                 }
+                OptionValue::Map(_) => None,
             },
 
             None => None,
         }
     }
+
+    /// Build arguments passed as `build-arg:NAME=value`.
+    pub fn build_args(&self) -> Option<&BTreeMap<String, String>> {
+        self.namespace("build-arg")
+    }
+
+    /// Labels passed as `label:NAME=value`.
+    pub fn labels(&self) -> Option<&BTreeMap<String, String>> {
+        self.namespace("label")
+    }
+
+    /// Target platforms requested with the `platform` option.
+    pub fn platforms(&self) -> Result<Vec<Platform>, Error> {
+        match self.iter("platform") {
+            Some(platforms) => platforms
+                .map(|platform| {
+                    Ok(platform
+                        .parse::<Platform>()
+                        .context("Unable to parse platforms")?)
+                })
+                .collect(),
+
+            None => Ok(vec![]),
+        }
+    }
+
+    fn namespace(&self, name: &str) -> Option<&BTreeMap<String, String>> {
+        match self.inner.get(name) {
+            Some(OptionValue::Map(entries)) => Some(entries),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -116,7 +156,9 @@ mod tests {
             "name8=value1,value2,value3",
             "name9=value1,val=ue2,value3",
             "build-arg:name10",
-            "build-arg:name11=value",
+            "build-arg:name11=value,with,commas",
+            "build-arg:name5=from-user",
+            "label:name12=value",
         ]))
         .unwrap();
 
@@ -143,8 +185,26 @@ mod tests {
             OptionValue::Multiple(vec!["value1".into(), "val=ue2".into(), "value3".into()])
         );
 
-        assert_eq!(options.inner["name10"], OptionValue::Flag(true));
-        assert_eq!(options.inner["name11"], OptionValue::Single("value".into()));
+        assert!(!options.has("name10"));
+        assert_eq!(options.get("name5"), Some("value"));
+
+        assert_eq!(
+            options.build_args().unwrap(),
+            &vec![
+                ("name10".into(), "".into()),
+                ("name11".into(), "value,with,commas".into()),
+                ("name5".into(), "from-user".into()),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+        );
+
+        assert_eq!(
+            options.labels().unwrap(),
+            &vec![("name12".into(), "value".into())]
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+        );
     }
 
     #[test]
@@ -157,10 +217,10 @@ mod tests {
         ]))
         .unwrap();
 
-        assert_eq!(options.has("option1"), true);
-        assert_eq!(options.has("option2"), true);
-        assert_eq!(options.has("option3"), false);
-        assert_eq!(options.has("option4"), true);
+        assert!(options.has("option1"));
+        assert!(options.has("option2"));
+        assert!(!options.has("option3"));
+        assert!(options.has("option4"));
     }
 
     #[test]
@@ -172,14 +232,14 @@ mod tests {
         ]))
         .unwrap();
 
-        assert_eq!(options.has_value("option1", ""), false);
-        assert_eq!(options.has_value("option1", "any_other"), false);
-        assert_eq!(options.has_value("option2", ""), false);
-        assert_eq!(options.has_value("option2", "any_other"), false);
-        assert_eq!(options.has_value("option3", "true"), true);
-        assert_eq!(options.has_value("option3", "false"), true);
-        assert_eq!(options.has_value("option3", "any_other"), true);
-        assert_eq!(options.has_value("option3", "missing"), false);
+        assert!(!options.has_value("option1", ""));
+        assert!(!options.has_value("option1", "any_other"));
+        assert!(!options.has_value("option2", ""));
+        assert!(!options.has_value("option2", "any_other"));
+        assert!(options.has_value("option3", "true"));
+        assert!(options.has_value("option3", "false"));
+        assert!(options.has_value("option3", "any_other"));
+        assert!(!options.has_value("option3", "missing"));
     }
 
     #[test]
@@ -202,6 +262,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn platforms_method() {
+        let options = from_env::<Options, _>(into_env(vec![
+            "platform=linux/amd64,linux/arm/v7",
+            "broken=linux",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            options.platforms().unwrap(),
+            vec![
+                Platform::new("linux", "amd64"),
+                Platform::new("linux", "arm").with_variant("v7"),
+            ]
+        );
+
+        assert!(from_env::<Options, _>(into_env(vec!["platform=linux"]))
+            .unwrap()
+            .platforms()
+            .is_err());
+    }
+
     fn into_env(args: Vec<&'static str>) -> Vec<(String, String)> {
         args.into_iter()
             .enumerate()
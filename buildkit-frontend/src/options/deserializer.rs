@@ -1,7 +1,8 @@
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::iter::empty;
 
-use failure::Error;
+use failure::{bail, Error};
 use serde::de::value::{MapDeserializer, SeqDeserializer};
 use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
 use serde::forward_to_deserialize_any;
@@ -22,8 +23,35 @@ where
         })
     };
 
+    let mut vals = vec![];
+    let mut namespaces: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+
+    for value in pairs {
+        match extract_namespaced(value) {
+            Some((namespace, name, value)) => {
+                namespaces.entry(namespace).or_default().push((name, value))
+            }
+
+            None => vals.push(extract_name_and_value(value)),
+        }
+    }
+
+    // The plain option and the namespaced ones would end up as two entries with the same key.
+    if let Some((name, _)) = vals.iter().find(|(name, _)| namespaces.contains_key(name)) {
+        bail!(
+            "Option '{0}' can't be used together with '{0}:*' options",
+            name
+        );
+    }
+
+    vals.extend(
+        namespaces
+            .into_iter()
+            .map(|(namespace, entries)| (namespace, EnvValue::Map(entries))),
+    );
+
     let deserializer = EnvDeserializer {
-        vals: pairs.map(|value| extract_name_and_value(&value)),
+        vals: vals.into_iter(),
     };
 
     T::deserialize(deserializer).map_err(Error::from)
@@ -39,22 +67,39 @@ enum EnvValue<'de> {
     Flag,
     Json(&'de str),
     Text(&'de str),
+    Map(Vec<(&'de str, &'de str)>),
 }
 
+/// Newtype name `RawOptions` values are requested with, to get the option text as it was passed.
+pub(super) const RAW_VALUE_TOKEN: &str = "$buildkit_frontend::options::RawValue";
+
+/// Options with these prefixes (e.g. `build-arg:NAME=value`) are collected into nested maps
+/// keyed by the prefix, so they never clash with the frontend's own options.
+const NAMESPACES: &[&str] = &["build-arg", "label"];
+
 #[derive(Debug)]
 struct EnvItem<'de>(&'de str);
 
-fn extract_name_and_value(mut raw_value: &str) -> (&str, EnvValue) {
-    if raw_value.starts_with("build-arg:") {
-        raw_value = raw_value.trim_start_matches("build-arg:");
-    }
+fn extract_namespaced(raw_value: &str) -> Option<(&str, &str, &str)> {
+    NAMESPACES.iter().find_map(|namespace| {
+        let name_and_value = raw_value.strip_prefix(namespace)?.strip_prefix(':')?;
+        let mut parts = name_and_value.splitn(2, '=');
+
+        Some((
+            *namespace,
+            parts.next().unwrap(),
+            parts.next().unwrap_or_default(),
+        ))
+    })
+}
 
+fn extract_name_and_value(raw_value: &str) -> (&str, EnvValue<'_>) {
     let mut parts = raw_value.splitn(2, '=');
     let name = parts.next().unwrap();
 
     match parts.next() {
         None => (name, EnvValue::Flag),
-        Some(text) if text.is_empty() => (name, EnvValue::Flag),
+        Some("") => (name, EnvValue::Flag),
         Some(text) if &text[0..1] == "[" || &text[0..1] == "{" => (name, EnvValue::Json(text)),
         Some(text) => (name, EnvValue::Text(text)),
     }
@@ -123,6 +168,7 @@ macro_rules! forward_parsed_values_env_value {
                 match self {
                     EnvValue::Flag => self.deserialize_any(visitor),
                     EnvValue::Json(_) => self.deserialize_any(visitor),
+                    EnvValue::Map(_) => self.deserialize_any(visitor),
                     EnvValue::Text(contents) => {
                         match contents.parse::<$ty>() {
                             Ok(val) => val.into_deserializer().$method(visitor),
@@ -157,6 +203,7 @@ impl<'de> de::Deserializer<'de> for EnvValue<'de> {
         match self {
             EnvValue::Flag => visitor.visit_bool(true),
             EnvValue::Json(contents) => EnvItem(contents).json(visitor),
+            EnvValue::Map(entries) => visitor.visit_map(MapDeserializer::new(entries.into_iter())),
             EnvValue::Text(contents) => {
                 if !contents.contains(',') {
                     EnvItem(contents).infer(visitor)
@@ -171,6 +218,7 @@ impl<'de> de::Deserializer<'de> for EnvValue<'de> {
         match self {
             EnvValue::Flag => SeqDeserializer::new(empty::<&'de str>()).deserialize_seq(visitor),
             EnvValue::Json(contents) => EnvItem(contents).json(visitor),
+            EnvValue::Map(_) => self.deserialize_any(visitor),
             EnvValue::Text(contents) => {
                 SeqDeserializer::new(contents.split(',')).deserialize_seq(visitor)
             }
@@ -181,6 +229,26 @@ impl<'de> de::Deserializer<'de> for EnvValue<'de> {
         visitor.visit_some(self)
     }
 
+    /// Only `RawOptions` asks for the value as it was passed, without guessing flags and lists.
+    /// Other newtypes keep going through `deserialize_any`.
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            EnvValue::Flag if name == RAW_VALUE_TOKEN => visitor.visit_borrowed_str(""),
+            EnvValue::Json(contents) if name == RAW_VALUE_TOKEN => {
+                visitor.visit_borrowed_str(contents)
+            }
+            EnvValue::Text(contents) if name == RAW_VALUE_TOKEN => {
+                visitor.visit_borrowed_str(contents)
+            }
+
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
     forward_parsed_values_env_value! {
         bool => deserialize_bool,
         u8 => deserialize_u8,
@@ -205,7 +273,6 @@ impl<'de> de::Deserializer<'de> for EnvValue<'de> {
         identifier
         ignored_any
         map
-        newtype_struct
         str
         string
         struct
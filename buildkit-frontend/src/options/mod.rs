@@ -1,8 +1,12 @@
 mod default;
 mod deserializer;
+mod raw;
+mod standard;
 
 pub use self::default::Options;
 pub use self::deserializer::from_env;
+pub use self::raw::RawOptions;
+pub use self::standard::{ExtraHost, NetworkMode, NoCache, StandardOptions};
 
 pub mod common;
 
@@ -11,6 +15,7 @@ mod tests {
     use std::path::PathBuf;
 
     use super::*;
+    use buildkit_llb::prelude::Platform;
     use serde::Deserialize;
 
     #[derive(Debug, Deserialize, PartialEq)]
@@ -18,7 +23,7 @@ mod tests {
     #[serde(field_identifier, rename_all = "lowercase")]
     enum Debug {
         All,
-        LLB,
+        Llb,
         Frontend,
     }
 
@@ -33,6 +38,9 @@ mod tests {
 
         #[serde(default)]
         cache_imports: Vec<common::CacheOptionsEntry>,
+
+        #[serde(default, deserialize_with = "common::deserialize_platforms")]
+        platform: Vec<Platform>,
     }
 
     #[test]
@@ -54,6 +62,10 @@ mod tests {
                 "BUILDKIT_FRONTEND_OPT_3".into(),
                 "verbosity=12345678".into(),
             ),
+            (
+                "BUILDKIT_FRONTEND_OPT_4".into(),
+                "platform=linux/amd64,linux/arm64".into(),
+            ),
         ];
 
         assert_eq!(
@@ -62,12 +74,17 @@ mod tests {
                 filename: Some(PathBuf::from("/path/to/Dockerfile")),
                 verbosity: 12_345_678,
 
-                debug: vec![Debug::LLB, Debug::Frontend],
+                debug: vec![Debug::Llb, Debug::Frontend],
 
                 cache_imports: vec![common::CacheOptionsEntry {
                     cache_type: common::CacheType::Local,
                     attrs: vec![("src".into(), "cache".into())].into_iter().collect()
                 }],
+
+                platform: vec![
+                    Platform::new("linux", "amd64"),
+                    Platform::new("linux", "arm64")
+                ],
             }
         );
     }
@@ -97,6 +114,7 @@ mod tests {
                 verbosity: 12_345_678,
                 debug: vec![Debug::All],
                 cache_imports: vec![],
+                platform: vec![],
             }
         );
     }
@@ -118,7 +136,81 @@ mod tests {
                 verbosity: 12_345_678,
                 debug: vec![],
                 cache_imports: vec![],
+                platform: vec![],
             }
         );
     }
+
+    #[test]
+    fn namespace_clash() {
+        let options = |values: Vec<&str>| {
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(idx, value)| (format!("BUILDKIT_FRONTEND_OPT_{}", idx), value.into()))
+                .collect::<Vec<_>>()
+        };
+
+        for env in [
+            options(vec!["label=plain", "label:maintainer=me"]),
+            options(vec!["label:maintainer=me", "label=plain"]),
+            options(vec!["build-arg", "build-arg:VERSION=1.0"]),
+        ] {
+            let error = from_env::<Options, _>(env).unwrap_err();
+            assert!(error.to_string().contains("can't be used together"));
+        }
+
+        let env = options(vec!["label=plain", "build-arg:VERSION=1.0"]);
+        let parsed = from_env::<Options, _>(env).unwrap();
+
+        assert_eq!(parsed.get("label"), Some("plain"));
+        assert_eq!(parsed.build_args().unwrap()["VERSION"], "1.0");
+    }
+
+    #[test]
+    fn raw_values_are_separate() {
+        let env = vec![
+            (
+                "BUILDKIT_FRONTEND_OPT_0".into(),
+                "platform=linux/amd64,linux/arm64".into(),
+            ),
+            ("BUILDKIT_FRONTEND_OPT_1".into(), "no-cache=".into()),
+            ("BUILDKIT_FRONTEND_OPT_2".into(), "target=release".into()),
+            (
+                "BUILDKIT_FRONTEND_OPT_3".into(),
+                "multi-platform=true".into(),
+            ),
+            (
+                "BUILDKIT_FRONTEND_OPT_4".into(),
+                "build-arg:VERSION=1.0".into(),
+            ),
+        ];
+
+        let raw = from_env::<RawOptions, _>(env.clone()).unwrap();
+
+        assert_eq!(raw.get("platform"), Some("linux/amd64,linux/arm64"));
+        assert_eq!(raw.get("no-cache"), Some(""));
+        assert_eq!(raw.get("multi-platform"), Some("true"));
+
+        let options = from_env::<Options, _>(env.clone()).unwrap();
+
+        assert_eq!(
+            options.iter("platform").unwrap().collect::<Vec<_>>(),
+            vec!["linux/amd64", "linux/arm64"]
+        );
+        assert!(options.is_flag_set("no-cache"));
+        assert!(options.is_flag_set("multi-platform"));
+        assert_eq!(options.get("target"), Some("release"));
+        assert_eq!(options.build_args().unwrap()["VERSION"], "1.0");
+
+        let standard = from_env::<StandardOptions, _>(env.into_iter().take(3)).unwrap();
+
+        assert_eq!(standard.platform.len(), 2);
+        assert_eq!(standard.no_cache, Some(NoCache::All));
+        assert_eq!(standard.target, Some("release".into()));
+
+        // String fields don't take lists, as before `RawOptions` existed.
+        let env = vec![("BUILDKIT_FRONTEND_OPT_0".into(), "target=a,b".into())];
+        assert!(from_env::<StandardOptions, _>(env).is_err());
+    }
 }
@@ -0,0 +1,209 @@
+use std::collections::btree_map::{self, BTreeMap};
+use std::fmt;
+
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+
+use super::deserializer::RAW_VALUE_TOKEN;
+
+/// Options exactly as BuildKit passed them, e.g. to forward to another frontend with `Bridge::solve_frontend`.
+///
+/// Namespaced options keep their prefix: `build-arg:VERSION` rather than a nested map.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RawOptions {
+    inner: BTreeMap<String, String>,
+}
+
+impl RawOptions {
+    pub fn get<S>(&self, name: S) -> Option<&str>
+    where
+        S: AsRef<str>,
+    {
+        self.inner.get(name.as_ref()).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.inner
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Add or replace an option. Flags have empty values.
+    pub fn with<K, V>(mut self, name: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.inner.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add or replace the `build-arg:NAME` option.
+    pub fn build_arg<K, V>(self, name: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: Into<String>,
+    {
+        self.with(format!("build-arg:{}", name.as_ref()), value)
+    }
+
+    /// Add or replace the `label:NAME` option.
+    pub fn label<K, V>(self, name: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: Into<String>,
+    {
+        self.with(format!("label:{}", name.as_ref()), value)
+    }
+
+    /// Remove an option, e.g. to let the other frontend use its default.
+    pub fn without<S>(mut self, name: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.inner.remove(name.as_ref());
+        self
+    }
+}
+
+impl IntoIterator for RawOptions {
+    type Item = (String, String);
+    type IntoIter = btree_map::IntoIter<String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<'de> Deserialize<'de> for RawOptions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct OptionsVisitor;
+
+        impl<'de> Visitor<'de> for OptionsVisitor {
+            type Value = RawOptions;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("frontend options")
+            }
+
+            fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let mut inner = BTreeMap::new();
+
+                while let Some(name) = map.next_key::<String>()? {
+                    match map.next_value::<RawValue>()? {
+                        RawValue::Single(value) => {
+                            inner.insert(name, value);
+                        }
+
+                        RawValue::Namespace(entries) => {
+                            for (entry, value) in entries {
+                                inner.insert(format!("{}:{}", name, entry), value);
+                            }
+                        }
+                    }
+                }
+
+                Ok(RawOptions { inner })
+            }
+        }
+
+        deserializer.deserialize_map(OptionsVisitor)
+    }
+}
+
+enum RawValue {
+    Single(String),
+    Namespace(BTreeMap<String, String>),
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = RawValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("option value")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawValue::Single(value.into()))
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(RawValue::Namespace)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_VALUE_TOKEN, ValueVisitor)
+    }
+}
+
+#[test]
+fn raw_options() {
+    use super::from_env;
+
+    let env = vec![
+        (
+            "BUILDKIT_FRONTEND_OPT_0".into(),
+            "filename=Dockerfile".into(),
+        ),
+        ("BUILDKIT_FRONTEND_OPT_1".into(), "no-cache=".into()),
+        (
+            "BUILDKIT_FRONTEND_OPT_2".into(),
+            "multi-platform=true".into(),
+        ),
+        (
+            "BUILDKIT_FRONTEND_OPT_3".into(),
+            "platform=linux/amd64,linux/arm64".into(),
+        ),
+        (
+            "BUILDKIT_FRONTEND_OPT_4".into(),
+            r#"cache-imports=[{"Type":"local"}]"#.into(),
+        ),
+        (
+            "BUILDKIT_FRONTEND_OPT_5".into(),
+            "build-arg:VERSION=1.0".into(),
+        ),
+    ];
+
+    let options = from_env::<RawOptions, _>(env).unwrap();
+
+    assert_eq!(options.get("filename"), Some("Dockerfile"));
+    assert_eq!(options.get("no-cache"), Some(""));
+    assert_eq!(options.get("multi-platform"), Some("true"));
+    assert_eq!(options.get("platform"), Some("linux/amd64,linux/arm64"));
+    assert_eq!(options.get("cache-imports"), Some(r#"[{"Type":"local"}]"#));
+    assert_eq!(options.get("build-arg:VERSION"), Some("1.0"));
+
+    let options = options.build_arg("COMMIT", "abcdef").without("no-cache");
+
+    assert_eq!(
+        options.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("build-arg:COMMIT".into(), "abcdef".into()),
+            ("build-arg:VERSION".into(), "1.0".into()),
+            ("cache-imports".into(), r#"[{"Type":"local"}]"#.into()),
+            ("filename".into(), "Dockerfile".into()),
+            ("multi-platform".into(), "true".into()),
+            ("platform".into(), "linux/amd64,linux/arm64".into()),
+        ]
+    );
+}
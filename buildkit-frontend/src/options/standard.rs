@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use buildkit_llb::prelude::{Platform, ResolveMode};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use super::common::{self, deserialize_list, split_list, CacheOptionsEntry};
+
+/// Options BuildKit and docker CLI conventionally pass to frontends.
+///
+/// Can be used as `Frontend<StandardOptions>` or flattened into custom options with `#[serde(flatten)]`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StandardOptions {
+    /// Path to the build file inside the `dockerfile` context.
+    pub filename: Option<PathBuf>,
+
+    /// Build stage to produce.
+    pub target: Option<String>,
+
+    /// Target platforms.
+    #[serde(default, deserialize_with = "common::deserialize_platforms")]
+    pub platform: Vec<Platform>,
+
+    /// Disable caching for all or some of the stages.
+    #[serde(default)]
+    pub no_cache: Option<NoCache>,
+
+    /// Images to import cache from (legacy `--cache-from`).
+    #[serde(default, deserialize_with = "CacheOptionsEntry::from_legacy_list")]
+    pub cache_from: Vec<CacheOptionsEntry>,
+
+    #[serde(default)]
+    pub cache_imports: Vec<CacheOptionsEntry>,
+
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub image_resolve_mode: Option<ResolveMode>,
+
+    /// Additional `/etc/hosts` entries.
+    #[serde(default, deserialize_with = "deserialize_parsed_list")]
+    pub add_hosts: Vec<ExtraHost>,
+
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub force_network_mode: Option<NetworkMode>,
+
+    /// Build arguments passed as `build-arg:NAME=value`.
+    #[serde(default, rename = "build-arg")]
+    pub build_args: BTreeMap<String, String>,
+
+    /// Labels passed as `label:NAME=value`.
+    #[serde(default, rename = "label")]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Value of the `no-cache` option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoCache {
+    /// Every stage has to be rebuilt.
+    All,
+
+    /// Only the named stages have to be rebuilt.
+    Stages(Vec<String>),
+}
+
+/// Host to IP address mapping, e.g. `registry.local=10.0.0.1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtraHost {
+    pub host: String,
+    pub ip: IpAddr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkMode {
+    None,
+    Host,
+    Sandbox,
+}
+
+impl StandardOptions {
+    /// Cache sources from both `cache-from` and `cache-imports`.
+    pub fn cache_entries(&self) -> impl Iterator<Item = &CacheOptionsEntry> {
+        self.cache_from.iter().chain(self.cache_imports.iter())
+    }
+
+    /// Whether the cache has to be ignored for the build stage.
+    pub fn is_cache_disabled(&self, stage: &str) -> bool {
+        match self.no_cache {
+            Some(NoCache::All) => true,
+            Some(NoCache::Stages(ref stages)) => stages.iter().any(|item| item == stage),
+            None => false,
+        }
+    }
+}
+
+impl FromStr for ExtraHost {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(2, ['=', ':']);
+
+        match (parts.next(), parts.next()) {
+            (Some(host), Some(ip)) if !host.is_empty() => Ok(ExtraHost {
+                host: host.into(),
+                ip: ip
+                    .parse()
+                    .map_err(|_| format!("invalid host IP '{}'", ip))?,
+            }),
+
+            _ => Err(format!(
+                "invalid host entry '{}', expected 'host=ip'",
+                value
+            )),
+        }
+    }
+}
+
+impl FromStr for NetworkMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(NetworkMode::None),
+            "host" => Ok(NetworkMode::Host),
+            "sandbox" => Ok(NetworkMode::Sandbox),
+
+            other => Err(format!("invalid network mode '{}'", other)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NoCache {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NoCacheVisitor;
+
+        impl<'de> Visitor<'de> for NoCacheVisitor {
+            type Value = NoCache;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("flag or a list of stages")
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E> {
+                if value {
+                    Ok(NoCache::All)
+                } else {
+                    Ok(NoCache::Stages(vec![]))
+                }
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> {
+                match value {
+                    "" => Ok(NoCache::All),
+                    stages => Ok(NoCache::Stages(split_list(stages))),
+                }
+            }
+
+            fn visit_seq<M>(self, seq: M) -> Result<Self::Value, M::Error>
+            where
+                M: SeqAccess<'de>,
+            {
+                Ok(NoCache::Stages(Deserialize::deserialize(
+                    de::value::SeqAccessDeserializer::new(seq),
+                )?))
+            }
+        }
+
+        deserializer.deserialize_any(NoCacheVisitor)
+    }
+}
+
+fn deserialize_parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(de::Error::custom)
+}
+
+fn deserialize_parsed_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    deserialize_list(deserializer)?
+        .iter()
+        .map(|item| item.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::from_env;
+    use super::*;
+
+    #[test]
+    fn standard_options() {
+        let options = from_env::<StandardOptions, _>(into_env(vec![
+            "filename=build/Dockerfile",
+            "target=release",
+            "platform=linux/amd64,linux/arm64",
+            "no-cache=builder,tests",
+            "cache-from=registry.local/cache:latest",
+            "image-resolve-mode=pull",
+            "add-hosts=registry.local=10.0.0.1,mirror.local=10.0.0.2",
+            "force-network-mode=none",
+            "build-arg:target=debug",
+            "build-arg:RUSTFLAGS=-C target-cpu=native",
+            "label:org.opencontainers.image.title=app",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            options,
+            StandardOptions {
+                filename: Some("build/Dockerfile".into()),
+                target: Some("release".into()),
+                platform: vec![
+                    Platform::new("linux", "amd64"),
+                    Platform::new("linux", "arm64")
+                ],
+                no_cache: Some(NoCache::Stages(vec!["builder".into(), "tests".into()])),
+                cache_from: vec![CacheOptionsEntry {
+                    cache_type: common::CacheType::Registry,
+                    attrs: vec![("ref".into(), "registry.local/cache:latest".into())]
+                        .into_iter()
+                        .collect(),
+                }],
+                cache_imports: vec![],
+                image_resolve_mode: Some(ResolveMode::ForcePull),
+                add_hosts: vec![
+                    ExtraHost {
+                        host: "registry.local".into(),
+                        ip: "10.0.0.1".parse().unwrap(),
+                    },
+                    ExtraHost {
+                        host: "mirror.local".into(),
+                        ip: "10.0.0.2".parse().unwrap(),
+                    },
+                ],
+                force_network_mode: Some(NetworkMode::None),
+                build_args: vec![
+                    ("target".into(), "debug".into()),
+                    ("RUSTFLAGS".into(), "-C target-cpu=native".into()),
+                ]
+                .into_iter()
+                .collect(),
+                labels: vec![("org.opencontainers.image.title".into(), "app".into())]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+
+        assert!(options.is_cache_disabled("builder"));
+        assert!(!options.is_cache_disabled("runtime"));
+    }
+
+    #[test]
+    fn defaults() {
+        let options = from_env::<StandardOptions, _>(into_env(vec!["no-cache"])).unwrap();
+
+        assert_eq!(
+            options,
+            StandardOptions {
+                no_cache: Some(NoCache::All),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn flattened() {
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct CustomOptions {
+            verbose: bool,
+
+            #[serde(flatten)]
+            standard: StandardOptions,
+        }
+
+        let options = from_env::<CustomOptions, _>(into_env(vec![
+            "verbose=true",
+            "platform=linux/arm64",
+            "target=tests",
+            "build-arg:VERSION=1.0",
+        ]))
+        .unwrap();
+
+        assert!(options.verbose);
+        assert_eq!(options.standard.target, Some("tests".into()));
+        assert_eq!(
+            options.standard.platform,
+            vec![Platform::new("linux", "arm64")]
+        );
+        assert_eq!(options.standard.build_args["VERSION"], "1.0");
+    }
+
+    fn into_env(args: Vec<&'static str>) -> Vec<(String, String)> {
+        args.into_iter()
+            .enumerate()
+            .map(|(index, option)| {
+                (
+                    format!("BUILDKIT_FRONTEND_OPT_{}", index),
+                    String::from(option),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use buildkit_llb::prelude::Platform;
+use buildkit_proto::moby::buildkit::v1::frontend::{
+    result::Result as RefResult, RefMap, Result as Output,
+};
+use failure::{Error, ResultExt};
+use serde::Serialize;
+
+use crate::oci::ImageSpecification;
+use crate::utils::OutputRef;
+
+const IMAGE_CONFIG_KEY: &str = "containerimage.config";
+const PLATFORMS_KEY: &str = "refs.platforms";
+
+/// Result of a frontend: either a single ref or a ref per platform for multi-arch images.
+pub struct FrontendOutput {
+    refs: OutputRefs,
+    metadata: HashMap<String, Vec<u8>>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum OutputRefs {
+    Single(OutputRef, Option<ImageSpecification>),
+    Platforms(Vec<(Platform, OutputRef, Option<ImageSpecification>)>),
+}
+
+/// Builder of a multi-platform result, created with `FrontendOutput::with_platforms`.
+/// Always has at least one platform, and turns into `FrontendOutput` with `into()`.
+pub struct MultiPlatformOutput {
+    refs: Vec<(Platform, OutputRef, Option<ImageSpecification>)>,
+}
+
+impl FrontendOutput {
+    pub fn with_ref(output: OutputRef) -> Self {
+        Self {
+            refs: OutputRefs::Single(output, None),
+            metadata: HashMap::new(),
+        }
+    }
+
+    pub fn with_spec_and_ref(spec: ImageSpecification, output: OutputRef) -> Self {
+        Self {
+            refs: OutputRefs::Single(output, Some(spec)),
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Multi-platform result with the first ref (and optionally its image configuration) built for the `platform`.
+    /// Refs for other platforms are added with `MultiPlatformOutput::add_platform`.
+    pub fn with_platforms(
+        platform: Platform,
+        output: OutputRef,
+        spec: Option<ImageSpecification>,
+    ) -> MultiPlatformOutput {
+        MultiPlatformOutput {
+            refs: vec![(platform, output, spec)],
+        }
+    }
+
+    /// Attach a JSON-encoded metadata entry to the result.
+    pub fn add_metadata<S, T>(self, key: S, value: &T) -> Result<Self, Error>
+    where
+        S: Into<String>,
+        T: Serialize + ?Sized,
+    {
+        let key = key.into();
+        let value = {
+            serde_json::to_vec(value)
+                .with_context(|_| format!("Unable to serialize metadata entry '{}'", key))?
+        };
+
+        Ok(self.add_raw_metadata(key, value))
+    }
+
+    /// Attach a metadata entry to the result as is.
+    /// Entries take precedence over the ones the output generates (e.g. `containerimage.config`).
+    pub fn add_raw_metadata<S>(mut self, key: S, value: Vec<u8>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+
+    pub(crate) fn into_result(self) -> Result<Output, Error> {
+        let mut metadata = HashMap::new();
+
+        let result = match self.refs {
+            OutputRefs::Single(output, spec) => {
+                if let Some(spec) = spec {
+                    metadata.insert(IMAGE_CONFIG_KEY.into(), serde_json::to_vec(&spec)?);
+                }
+
+                RefResult::Ref(output.0)
+            }
+
+            OutputRefs::Platforms(refs) => {
+                let mut exported = ExportedPlatforms { platforms: vec![] };
+                let mut ref_map = HashMap::new();
+
+                for (platform, output, spec) in refs {
+                    let id = platform.to_string();
+
+                    if let Some(spec) = spec {
+                        metadata.insert(
+                            format!("{}/{}", IMAGE_CONFIG_KEY, id),
+                            serde_json::to_vec(&spec)?,
+                        );
+                    }
+
+                    ref_map.insert(id.clone(), output.0);
+                    exported.platforms.push(ExportedPlatform {
+                        id,
+                        platform: platform.into(),
+                    });
+                }
+
+                metadata.insert(PLATFORMS_KEY.into(), serde_json::to_vec(&exported)?);
+                RefResult::Refs(RefMap { refs: ref_map })
+            }
+        };
+
+        metadata.extend(self.metadata);
+
+        Ok(Output {
+            result: Some(result),
+            metadata,
+        })
+    }
+}
+
+impl MultiPlatformOutput {
+    /// Add a ref (and optionally its image configuration) built for the `platform`.
+    /// A ref that was already added for the same platform is replaced.
+    pub fn add_platform(
+        mut self,
+        platform: Platform,
+        output: OutputRef,
+        spec: Option<ImageSpecification>,
+    ) -> Self {
+        match self.refs.iter_mut().find(|(added, ..)| *added == platform) {
+            Some(entry) => *entry = (platform, output, spec),
+            None => self.refs.push((platform, output, spec)),
+        }
+
+        self
+    }
+}
+
+impl From<MultiPlatformOutput> for FrontendOutput {
+    fn from(output: MultiPlatformOutput) -> Self {
+        Self {
+            refs: OutputRefs::Platforms(output.refs),
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+// https://github.com/moby/buildkit/blob/v0.7.0/exporter/containerimage/exptypes/types.go
+#[derive(Serialize)]
+struct ExportedPlatforms {
+    platforms: Vec<ExportedPlatform>,
+}
+
+#[derive(Serialize)]
+struct ExportedPlatform {
+    id: String,
+    platform: OciPlatform,
+}
+
+#[derive(Serialize)]
+struct OciPlatform {
+    architecture: String,
+    os: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variant: Option<String>,
+}
+
+impl From<Platform> for OciPlatform {
+    fn from(platform: Platform) -> Self {
+        Self {
+            architecture: platform.architecture,
+            os: platform.os,
+            variant: platform.variant,
+        }
+    }
+}
+
+#[test]
+fn multi_platform_result() {
+    let output = FrontendOutput::from(
+        FrontendOutput::with_platforms(
+            Platform::new("linux", "amd64"),
+            OutputRef("ref-amd64".into()),
+            None,
+        )
+        .add_platform(
+            Platform::new("linux", "arm").with_variant("v7"),
+            OutputRef("ref-arm".into()),
+            None,
+        ),
+    );
+
+    let output = output.into_result().unwrap();
+
+    match output.result {
+        Some(RefResult::Refs(map)) => {
+            assert_eq!(map.refs["linux/amd64"], "ref-amd64");
+            assert_eq!(map.refs["linux/arm/v7"], "ref-arm");
+        }
+
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    assert_eq!(
+        String::from_utf8(output.metadata[PLATFORMS_KEY].clone()).unwrap(),
+        concat!(
+            r#"{"platforms":["#,
+            r#"{"id":"linux/amd64","platform":{"architecture":"amd64","os":"linux"}},"#,
+            r#"{"id":"linux/arm/v7","platform":{"architecture":"arm","os":"linux","variant":"v7"}}"#,
+            r#"]}"#
+        )
+    );
+}
+
+#[test]
+fn duplicated_platform() {
+    let output = FrontendOutput::from(
+        FrontendOutput::with_platforms(
+            Platform::new("linux", "amd64"),
+            OutputRef("ref-old".into()),
+            None,
+        )
+        .add_platform(
+            Platform::new("linux", "arm64"),
+            OutputRef("ref-arm64".into()),
+            None,
+        )
+        .add_platform(
+            Platform::new("linux", "amd64"),
+            OutputRef("ref-amd64".into()),
+            None,
+        ),
+    );
+
+    let output = output.into_result().unwrap();
+
+    match output.result {
+        Some(RefResult::Refs(map)) => {
+            assert_eq!(map.refs.len(), 2);
+            assert_eq!(map.refs["linux/amd64"], "ref-amd64");
+            assert_eq!(map.refs["linux/arm64"], "ref-arm64");
+        }
+
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    assert_eq!(
+        String::from_utf8(output.metadata[PLATFORMS_KEY].clone()).unwrap(),
+        concat!(
+            r#"{"platforms":["#,
+            r#"{"id":"linux/amd64","platform":{"architecture":"amd64","os":"linux"}},"#,
+            r#"{"id":"linux/arm64","platform":{"architecture":"arm64","os":"linux"}}"#,
+            r#"]}"#
+        )
+    );
+}
+
+#[test]
+fn custom_metadata() {
+    use serde_json::json;
+
+    let output = {
+        FrontendOutput::with_ref(OutputRef("ref".into()))
+            .add_metadata("build-info", &json!({ "rustc": "1.42.0" }))
+            .unwrap()
+            .add_raw_metadata("exporter-hint", b"raw".to_vec())
+            .into_result()
+            .unwrap()
+    };
+
+    assert_eq!(
+        output.metadata["build-info"],
+        br#"{"rustc":"1.42.0"}"#.to_vec()
+    );
+    assert_eq!(output.metadata["exporter-hint"], b"raw".to_vec());
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use buildkit_proto::fsutil::mode::{MODE_DIR, MODE_SYMLINK, MODE_TYPE};
+use buildkit_proto::fsutil::types::Stat;
+use chrono::prelude::*;
+
+/// Metadata of a file inside a solved reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStat {
+    /// Path of the file. Entries of `Bridge::read_dir` have paths relative to the directory.
+    pub path: PathBuf,
+
+    /// File mode in Go's `os.FileMode` format: permission bits together with file type bits.
+    pub mode: u32,
+
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+
+    /// Last modification time.
+    pub modified: DateTime<Utc>,
+
+    /// Target of a symbolic link.
+    pub link_target: Option<PathBuf>,
+
+    /// Extended attributes of the file.
+    pub xattrs: HashMap<String, Vec<u8>>,
+}
+
+impl FileStat {
+    pub fn is_dir(&self) -> bool {
+        self.mode & MODE_DIR != 0
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & MODE_SYMLINK != 0
+    }
+
+    /// Whether the entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.mode & MODE_TYPE == 0
+    }
+
+    /// Unix permission bits, e.g. `0o755`.
+    pub fn permissions(&self) -> u32 {
+        self.mode & 0o777
+    }
+}
+
+impl From<Stat> for FileStat {
+    fn from(raw: Stat) -> Self {
+        Self {
+            path: raw.path.into(),
+            mode: raw.mode,
+            uid: raw.uid,
+            gid: raw.gid,
+            size: raw.size as u64,
+            modified: Utc.timestamp_nanos(raw.mod_time),
+            xattrs: raw.xattrs,
+
+            link_target: if raw.linkname.is_empty() {
+                None
+            } else {
+                Some(raw.linkname.into())
+            },
+        }
+    }
+}
+
+#[test]
+fn conversion() {
+    let file = FileStat::from(Stat {
+        path: "Cargo.toml".into(),
+        mode: 0o644,
+        uid: 1000,
+        gid: 1000,
+        size: 512,
+        mod_time: 1_583_280_000_000_000_000,
+        ..Default::default()
+    });
+
+    assert!(file.is_file());
+    assert!(!file.is_dir());
+    assert_eq!(file.permissions(), 0o644);
+    assert_eq!(file.size, 512);
+    assert_eq!(file.link_target, None);
+    assert_eq!(
+        Some(file.modified),
+        Utc.timestamp_opt(1_583_280_000, 0).single()
+    );
+
+    let dir = FileStat::from(Stat {
+        path: "src".into(),
+        mode: MODE_DIR | 0o755,
+        ..Default::default()
+    });
+
+    assert!(dir.is_dir());
+    assert!(!dir.is_file());
+    assert_eq!(dir.permissions(), 0o755);
+
+    let link = FileStat::from(Stat {
+        path: "latest".into(),
+        mode: MODE_SYMLINK | 0o777,
+        linkname: "v1.0".into(),
+        ..Default::default()
+    });
+
+    assert!(link.is_symlink());
+    assert_eq!(link.link_target, Some(PathBuf::from("v1.0")));
+}
@@ -2,17 +2,24 @@ use std::io::{self, stdin, stdout};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+use futures::ready;
 use pin_project::pin_project;
 use tokio::io::*;
 use tonic::transport::Uri;
 
+/// Gateway connection over the process stdin and stdout.
+///
+/// Shutting the socket down permanently redirects the process stdout to stderr:
+/// stdout can be a pipe, and replacing the descriptor is the only way to close it
+/// without letting an unrelated file take over descriptor 1. Anything printed to stdout
+/// after the frontend returned ends up in the stderr log instead of the protocol stream.
 #[pin_project]
 pub struct StdioSocket {
     #[pin]
     reader: PollEvented<async_stdio::EventedStdin>,
 
-    #[pin]
-    writer: PollEvented<async_stdio::EventedStdout>,
+    /// `None` once stdout is shut down.
+    writer: Option<PollEvented<async_stdio::EventedStdout>>,
 }
 
 pub async fn stdio_connector(_: Uri) -> io::Result<StdioSocket> {
@@ -23,7 +30,9 @@ impl StdioSocket {
     pub fn try_new() -> io::Result<Self> {
         Ok(StdioSocket {
             reader: PollEvented::new(async_stdio::EventedStdin::try_new(stdin())?)?,
-            writer: PollEvented::new(async_stdio::EventedStdout::try_new(stdout())?)?,
+            writer: Some(PollEvented::new(async_stdio::EventedStdout::try_new(
+                stdout(),
+            )?)?),
         })
     }
 }
@@ -40,27 +49,49 @@ impl AsyncRead for StdioSocket {
 
 impl AsyncWrite for StdioSocket {
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
-        self.project().writer.poll_write(cx, buf)
+        match self.project().writer {
+            Some(writer) => Pin::new(writer).poll_write(cx, buf),
+            None => Poll::Ready(Err(shut_down())),
+        }
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        self.project().writer.poll_flush(cx)
+        match self.project().writer {
+            Some(writer) => Pin::new(writer).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
     }
 
+    /// Flush and half-close stdout, so the peer sees EOF while stdin can still be read.
+    /// The descriptor is deregistered from the reactor before it's touched.
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        self.project().writer.poll_shutdown(cx)
+        let writer = self.project().writer;
+
+        if let Some(inner) = writer.as_mut() {
+            ready!(Pin::new(inner).poll_flush(cx))?;
+        }
+
+        if let Some(inner) = writer.take() {
+            inner.into_inner()?.close()?;
+        }
+
+        Poll::Ready(Ok(()))
     }
 }
 
+fn shut_down() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "stdout is already shut down")
+}
+
 mod async_stdio {
     use std::io::{self, Read, Stdin, Stdout, Write};
-    use std::os::unix::io::AsRawFd;
+    use std::os::unix::io::{AsRawFd, RawFd};
 
     use mio::event::Evented;
     use mio::unix::EventedFd;
     use mio::{Poll, PollOpt, Ready, Token};
 
-    use libc::{fcntl, F_GETFL, F_SETFL, O_NONBLOCK};
+    use libc::{dup2, fcntl, shutdown, ENOTSOCK, F_GETFL, F_SETFL, O_NONBLOCK, SHUT_WR};
 
     pub struct EventedStdin(Stdin);
     pub struct EventedStdout(Stdout);
@@ -79,6 +110,32 @@ mod async_stdio {
 
             Ok(EventedStdout(stdout))
         }
+
+        /// Signal EOF to the peer. Must be called only after the descriptor is deregistered from the reactor.
+        pub fn close(self) -> io::Result<()> {
+            release(self.0.as_raw_fd(), io::stderr().as_raw_fd())
+        }
+    }
+
+    /// Shut down the write half of `fd` and point it to `replacement`.
+    ///
+    /// A socket is shut down explicitly, because stdin can refer to the same socket and keep it open.
+    /// For a pipe `dup2` closes our end of it. Either way `fd` stays a valid descriptor,
+    /// so it can't be reused by an unrelated file that would receive writes meant for stdout.
+    pub(super) fn release(fd: RawFd, replacement: RawFd) -> io::Result<()> {
+        if unsafe { shutdown(fd, SHUT_WR) } < 0 {
+            let error = io::Error::last_os_error();
+
+            if error.raw_os_error() != Some(ENOTSOCK) {
+                return Err(error);
+            }
+        }
+
+        if unsafe { dup2(replacement, fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
     }
 
     impl Evented for EventedStdin {
@@ -163,3 +220,38 @@ mod async_stdio {
         Ok(())
     }
 }
+
+#[test]
+fn release_write_half() {
+    use std::io::{Read, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::os::unix::net::UnixStream;
+
+    let (mut local, mut peer) = UnixStream::pair().unwrap();
+    let (replacement, mut replacement_peer) = UnixStream::pair().unwrap();
+
+    // Like stdout sharing the socket with stdin.
+    let mut writer = unsafe { UnixStream::from_raw_fd(libc::dup(local.as_raw_fd())) };
+
+    writer.write_all(b"result").unwrap();
+    async_stdio::release(writer.as_raw_fd(), replacement.as_raw_fd()).unwrap();
+
+    // The peer gets the data and EOF, though the socket is still open for reading.
+    let mut received = vec![];
+    peer.read_to_end(&mut received).unwrap();
+    assert_eq!(received, b"result");
+
+    peer.write_all(b"ack").unwrap();
+    let mut ack = [0; 3];
+    local.read_exact(&mut ack).unwrap();
+    assert_eq!(&ack, b"ack");
+
+    // Later writes go to the replacement instead of the socket.
+    writer.write_all(b"log").unwrap();
+    drop(writer);
+    drop(replacement);
+
+    let mut redirected = vec![];
+    replacement_peer.read_to_end(&mut redirected).unwrap();
+    assert_eq!(redirected, b"log");
+}
@@ -0,0 +1,184 @@
+//! Redirection to another frontend image with the `# syntax=` directive, like the Dockerfile frontend does.
+
+use std::collections::HashMap;
+
+/// The Dockerfile frontend built into BuildKit.
+pub const DOCKERFILE_FRONTEND: &str = "dockerfile.v0";
+
+/// Frontend that runs a frontend image passed with the `source` option.
+pub const GATEWAY_FRONTEND: &str = "gateway.v0";
+
+/// Value of the `# syntax=` directive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Syntax {
+    /// Frontend image reference, e.g. `docker/dockerfile:experimental`.
+    pub image: String,
+
+    /// The whole directive value, including optional arguments after the image.
+    pub cmdline: String,
+}
+
+impl Syntax {
+    /// Find the `syntax` directive among the parser directives on top of the file.
+    /// Directives end at the first line that is not one, including empty lines.
+    pub fn detect(contents: &str) -> Option<Self> {
+        contents
+            .lines()
+            .map(directive)
+            .take_while(Option::is_some)
+            .flatten()
+            .find(|(name, _)| name.eq_ignore_ascii_case("syntax"))
+            .and_then(|(_, cmdline)| {
+                let image = cmdline.split_whitespace().next()?;
+
+                Some(Self {
+                    image: image.into(),
+                    cmdline: cmdline.into(),
+                })
+            })
+    }
+
+    /// Options for `GATEWAY_FRONTEND` to run the image with the rest of the `options`.
+    pub fn gateway_options<O>(&self, options: O) -> HashMap<String, String>
+    where
+        O: IntoIterator<Item = (String, String)>,
+    {
+        let mut options: HashMap<_, _> = options.into_iter().collect();
+
+        options.insert("source".into(), self.image.clone());
+        options.insert("cmdline".into(), self.cmdline.clone());
+        options
+    }
+}
+
+/// Parse a `# name=value` line.
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.trim().strip_prefix('#')?.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let value = parts.next()?.trim();
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) || value.is_empty() {
+        return None;
+    }
+
+    Some((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use buildkit_llb::prelude::*;
+    use failure::Error;
+
+    use super::*;
+    use crate::mock::MockBridge;
+    use crate::options::RawOptions;
+    use crate::{Bridge, Frontend, FrontendOutput};
+
+    /// Injects a build argument and a label and hands the build over to the Dockerfile frontend.
+    struct Wrapper;
+
+    #[tonic::async_trait]
+    impl Frontend<RawOptions> for Wrapper {
+        async fn run(self, bridge: Bridge, options: RawOptions) -> Result<FrontendOutput, Error> {
+            let options = {
+                options
+                    .build_arg("VERSION", "1.0")
+                    .label("org.example.wrapped", "true")
+            };
+
+            let dockerfile = {
+                bridge
+                    .solve(Terminal::with(Source::local("dockerfile").output()))
+                    .await?
+            };
+
+            let contents = {
+                bridge
+                    .filesystem(&dockerfile)
+                    .read_to_string(options.get("filename").unwrap_or("Dockerfile"))
+                    .await?
+            };
+
+            let context = Source::local("context");
+            let inputs = vec![(String::from("context"), Terminal::with(context.output()))];
+
+            let output = match Syntax::detect(&contents) {
+                Some(syntax) => {
+                    let options = syntax.gateway_options(options);
+                    bridge
+                        .solve_frontend(GATEWAY_FRONTEND, options, inputs)
+                        .await?
+                }
+
+                None => {
+                    bridge
+                        .solve_frontend(DOCKERFILE_FRONTEND, options, inputs)
+                        .await?
+                }
+            };
+
+            Ok(FrontendOutput::with_ref(output))
+        }
+    }
+
+    #[test]
+    fn detection() {
+        let syntax =
+            Syntax::detect("# syntax = docker/dockerfile:1.1 --debug\nFROM alpine\n").unwrap();
+
+        assert_eq!(syntax.image, "docker/dockerfile:1.1");
+        assert_eq!(syntax.cmdline, "docker/dockerfile:1.1 --debug");
+
+        let contents = "# escape=`\n#SYNTAX=registry.example.com/frontend\n";
+        assert_eq!(
+            Syntax::detect(contents).unwrap().image,
+            "registry.example.com/frontend"
+        );
+
+        assert_eq!(Syntax::detect("FROM alpine\n# syntax=ignored\n"), None);
+        assert_eq!(Syntax::detect("# a comment\n# syntax=ignored\n"), None);
+        assert_eq!(Syntax::detect("\n# syntax=ignored\n"), None);
+    }
+
+    #[test]
+    fn gateway_options() {
+        let syntax = Syntax::detect("# syntax=docker/dockerfile:experimental").unwrap();
+        let options = syntax.gateway_options(vec![("filename".into(), "Dockerfile".into())]);
+
+        assert_eq!(options["source"], "docker/dockerfile:experimental");
+        assert_eq!(options["cmdline"], "docker/dockerfile:experimental");
+        assert_eq!(options["filename"], "Dockerfile");
+    }
+
+    #[tokio::test]
+    async fn forwarding() {
+        let report = {
+            MockBridge::new()
+                .with_option("filename=Dockerfile")
+                .with_option("build-arg:USER=root")
+                .with_solve_result("dockerfile")
+                .with_ref("dockerfile", "tests/fixtures/dockerfile")
+                .run(Wrapper)
+                .await
+                .unwrap()
+        };
+
+        assert!(report.returned_error().is_none());
+        assert_eq!(report.solves.len(), 2);
+        assert_eq!(report.returned_ref(), Some("solve-2"));
+
+        let request = &report.solves[1];
+
+        assert_eq!(request.frontend, GATEWAY_FRONTEND);
+        assert_eq!(
+            request.frontend_opt["source"],
+            "docker/dockerfile:experimental"
+        );
+        assert_eq!(request.frontend_opt["filename"], "Dockerfile");
+        assert_eq!(request.frontend_opt["build-arg:USER"], "root");
+        assert_eq!(request.frontend_opt["build-arg:VERSION"], "1.0");
+        assert_eq!(request.frontend_opt["label:org.example.wrapped"], "true");
+        assert!(request.frontend_inputs.contains_key("context"));
+        assert!(request.definition.is_none());
+    }
+}
@@ -0,0 +1,248 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use log::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::oneshot;
+use tokio::time;
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::service_fn;
+
+/// How long to wait for the connection to close after the result is returned.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport that reports when the HTTP/2 connection is done with it.
+struct Transport<IO> {
+    inner: IO,
+    closed: Option<oneshot::Sender<()>>,
+}
+
+/// Resolves once the connection has released its transport.
+pub(crate) struct Closed(oneshot::Receiver<()>);
+
+/// Open a channel over the single `io` transport. It can't be reconnected.
+pub(crate) async fn connect<IO>(io: IO) -> Result<(Channel, Closed), tonic::transport::Error>
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    let transport = Arc::new(Mutex::new(Some(Transport {
+        inner: io,
+        closed: Some(sender),
+    })));
+
+    let connector = service_fn(move |_: Uri| {
+        let transport = transport.lock().unwrap().take();
+
+        async move {
+            transport.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotConnected, "transport is already used")
+            })
+        }
+    });
+
+    let channel = {
+        Endpoint::from_static("http://[::]:50051")
+            .connect_with_connector(connector)
+            .await?
+    };
+
+    Ok((channel, Closed(receiver)))
+}
+
+impl Closed {
+    /// Wait until the last channel handle is dropped and the connection is shut down.
+    pub(crate) async fn wait(self) {
+        match time::timeout(SHUTDOWN_TIMEOUT, self.0).await {
+            Ok(_) => debug!("connection is closed"),
+            Err(_) => warn!(
+                "connection is still in use after {:?}, exiting anyway",
+                SHUTDOWN_TIMEOUT
+            ),
+        }
+    }
+}
+
+impl<IO> AsyncRead for Transport<IO>
+where
+    IO: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<IO> AsyncWrite for Transport<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+impl<IO> Drop for Transport<IO> {
+    fn drop(&mut self) {
+        if let Some(closed) = self.closed.take() {
+            closed.send(()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use bytes::{Bytes, BytesMut};
+    use prost::Message;
+    use tokio::io::{duplex, DuplexStream};
+
+    use buildkit_proto::moby::buildkit::v1::frontend::{
+        result::Result as RefResult, ReturnRequest,
+    };
+
+    use super::*;
+    use crate::utils::OutputRef;
+    use crate::{Bridge, Frontend, FrontendOutput, Options};
+
+    struct TestFrontend;
+
+    #[tonic::async_trait]
+    impl Frontend for TestFrontend {
+        async fn run(self, _: Bridge, _: Options) -> Result<FrontendOutput, failure::Error> {
+            Ok(FrontendOutput::with_ref(OutputRef("test-ref".into())))
+        }
+    }
+
+    /// Client end of the in-memory connection that remembers being released.
+    struct TrackedStream {
+        inner: DuplexStream,
+        dropped: Arc<AtomicBool>,
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown() {
+        let (client, server) = duplex(64 * 1024);
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let server = tokio::spawn(serve_return(server));
+        let client = TrackedStream {
+            inner: client,
+            dropped: dropped.clone(),
+        };
+
+        crate::run_frontend_with_io(TestFrontend, client)
+            .await
+            .unwrap();
+
+        assert!(dropped.load(Ordering::SeqCst));
+
+        // The server only finishes once the client closed the connection.
+        let request = server.await.unwrap();
+
+        assert!(request.error.is_none());
+        assert_eq!(
+            request.result.and_then(|result| result.result),
+            Some(RefResult::Ref("test-ref".into()))
+        );
+    }
+
+    /// Accept `Return` calls until the client goes away and report the last one.
+    async fn serve_return(socket: DuplexStream) -> ReturnRequest {
+        let mut connection = h2::server::handshake(socket).await.unwrap();
+        let mut received = None;
+
+        while let Some(request) = connection.accept().await {
+            let (request, mut respond) = request.unwrap();
+
+            assert_eq!(
+                request.uri().path(),
+                "/moby.buildkit.v1.frontend.LLBBridge/Return"
+            );
+
+            let mut body = request.into_body();
+            let mut message = BytesMut::new();
+
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.unwrap();
+
+                body.flow_control().release_capacity(chunk.len()).unwrap();
+                message.extend_from_slice(&chunk);
+            }
+
+            // Skip the gRPC message prefix: compression flag and length.
+            received = Some(ReturnRequest::decode(&message[5..]).unwrap());
+
+            let response = {
+                http::Response::builder()
+                    .header("content-type", "application/grpc")
+                    .body(())
+                    .unwrap()
+            };
+
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+
+            let mut stream = respond.send_response(response, false).unwrap();
+            stream
+                .send_data(Bytes::from_static(&[0, 0, 0, 0, 0]), false)
+                .unwrap();
+            stream.send_trailers(trailers).unwrap();
+        }
+
+        received.expect("no Return call")
+    }
+
+    impl AsyncRead for TrackedStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for TrackedStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl Drop for TrackedStream {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::env;
+use std::fs;
+use std::io::{stdin, Read};
+use std::process::exit;
+
+use buildkit_llb::diff::diff_definitions;
+use buildkit_proto::pb;
+use prost::Message;
+
+// Explain the difference between two serialized definitions (`-` reads one of them from stdin):
+//
+//   cargo run --example=scratch > old.pb
+//   cargo run --example=llb-diff -- old.pb new.pb
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    if args.len() != 2 {
+        eprintln!("Usage: llb-diff <old definition> <new definition>");
+        exit(2);
+    }
+
+    let differences = match diff_definitions(&read(&args[0]), &read(&args[1])) {
+        Ok(differences) => differences,
+        Err(error) => {
+            eprintln!("Unable to compare definitions: {}", error);
+            exit(2);
+        }
+    };
+
+    for difference in &differences {
+        println!("{}", difference);
+    }
+
+    if !differences.is_empty() {
+        exit(1);
+    }
+}
+
+fn read(path: &str) -> pb::Definition {
+    let bytes = if path == "-" {
+        let mut bytes = vec![];
+        stdin()
+            .read_to_end(&mut bytes)
+            .expect("Unable to read stdin");
+        bytes
+    } else {
+        fs::read(path).unwrap_or_else(|error| panic!("Unable to read '{}': {}", path, error))
+    };
+
+    pb::Definition::decode(bytes.as_slice()).expect("Unable to decode the definition")
+}
@@ -0,0 +1,45 @@
+use std::io::stdout;
+
+use buildkit_llb::prelude::*;
+
+fn main() {
+    let mut graph = Graph::new();
+    let output = build_graph(&mut graph).unwrap();
+
+    graph.write_definition(output, stdout()).unwrap()
+}
+
+fn build_graph(graph: &mut Graph) -> Result<OutputHandle, GraphError> {
+    let builder_image = graph.add(
+        Source::image("library/alpine:latest").custom_name("Using alpine:latest as a builder"),
+    );
+
+    let command = graph.add(
+        Command::run("/bin/sh")
+            .args(["-c", "echo 'test string 5' > /out/file0"])
+            .custom_name("create a dummy file")
+            .mount(Mount::ReadOnlyLayer(graph.output(builder_image)?, "/"))
+            .mount(Mount::Scratch(OutputIdx(0), "/out")),
+    );
+
+    let command_output = command.output(0)?;
+    let fs = graph.add(
+        FileSystem::sequence()
+            .custom_name("do multiple file system manipulations")
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(graph.output(command_output)?, "/file0"))
+                    .to(
+                        OutputIdx(0),
+                        LayerPath::Other(graph.output(command_output)?, "/file1"),
+                    ),
+            )
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Own(OwnOutputIdx(0), "/file0"))
+                    .to(OutputIdx(1), LayerPath::Own(OwnOutputIdx(0), "/file2")),
+            ),
+    );
+
+    fs.output(1)
+}
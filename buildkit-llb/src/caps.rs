@@ -0,0 +1,319 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::iter::FromIterator;
+
+use buildkit_proto::moby::buildkit::v1::apicaps::ApiCap;
+
+// https://github.com/moby/buildkit/blob/v0.7.0/solver/pb/caps.go
+
+/// LLB capability that an operation might require from the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    SourceImage,
+    SourceImageResolveMode,
+    SourceLocal,
+    SourceLocalUnique,
+    SourceLocalSessionId,
+    SourceLocalIncludePatterns,
+    SourceLocalFollowPaths,
+    SourceLocalExcludePatterns,
+    SourceLocalSharedKeyHint,
+    SourceGit,
+    SourceGitKeepDir,
+    SourceGitFullUrl,
+    SourceHttp,
+    SourceHttpChecksum,
+    SourceHttpPerm,
+    SourceHttpUidGid,
+
+    ExecMetaBase,
+    ExecMetaProxy,
+    ExecMetaNetwork,
+    ExecMetaSecurity,
+    ExecMetaSetsDefaultPath,
+    ExecMountBind,
+    ExecMountCache,
+    ExecMountCacheSharing,
+    ExecMountSelector,
+    ExecMountTmpfs,
+    ExecMountSecret,
+    ExecMountSsh,
+    ExecCgroupsMounted,
+
+    FileBase,
+
+    Constraints,
+    Platform,
+    MetaIgnoreCache,
+    MetaDescription,
+    MetaExportCache,
+}
+
+impl Capability {
+    /// Identifier of the capability as BuildKit knows it, e.g. `exec.mount.bind`.
+    pub fn id(self) -> &'static str {
+        use Capability::*;
+
+        match self {
+            SourceImage => "source.image",
+            SourceImageResolveMode => "source.image.resolvemode",
+            SourceLocal => "source.local",
+            SourceLocalUnique => "source.local.unique",
+            SourceLocalSessionId => "source.local.sessionid",
+            SourceLocalIncludePatterns => "source.local.includepatterns",
+            SourceLocalFollowPaths => "source.local.followpaths",
+            SourceLocalExcludePatterns => "source.local.excludepatterns",
+            SourceLocalSharedKeyHint => "source.local.sharedkeyhint",
+            SourceGit => "source.git",
+            SourceGitKeepDir => "source.git.keepgitdir",
+            SourceGitFullUrl => "source.git.fullurl",
+            SourceHttp => "source.http",
+            SourceHttpChecksum => "source.http.checksum",
+            SourceHttpPerm => "source.http.perm",
+            // The misspelling comes from BuildKit itself.
+            SourceHttpUidGid => "soruce.http.uidgid",
+
+            ExecMetaBase => "exec.meta.base",
+            ExecMetaProxy => "exec.meta.proxyenv",
+            ExecMetaNetwork => "exec.meta.network",
+            ExecMetaSecurity => "exec.meta.security",
+            ExecMetaSetsDefaultPath => "exec.meta.setsdefaultpath",
+            ExecMountBind => "exec.mount.bind",
+            ExecMountCache => "exec.mount.cache",
+            ExecMountCacheSharing => "exec.mount.cache.sharing",
+            ExecMountSelector => "exec.mount.selector",
+            ExecMountTmpfs => "exec.mount.tmpfs",
+            ExecMountSecret => "exec.mount.secret",
+            ExecMountSsh => "exec.mount.ssh",
+            ExecCgroupsMounted => "exec.cgroup",
+
+            FileBase => "file.base",
+
+            Constraints => "constraints",
+            Platform => "platform",
+            MetaIgnoreCache => "meta.ignorecache",
+            MetaDescription => "meta.description",
+            MetaExportCache => "meta.exportcache",
+        }
+    }
+
+    /// Find a known capability by its BuildKit identifier.
+    /// Returns `None` for identifiers this crate doesn't describe, e.g. ones introduced by newer BuildKit.
+    pub fn from_id(id: &str) -> Option<Self> {
+        ALL.iter().cloned().find(|cap| cap.id() == id)
+    }
+}
+
+const ALL: &[Capability] = {
+    use Capability::*;
+
+    &[
+        SourceImage,
+        SourceImageResolveMode,
+        SourceLocal,
+        SourceLocalUnique,
+        SourceLocalSessionId,
+        SourceLocalIncludePatterns,
+        SourceLocalFollowPaths,
+        SourceLocalExcludePatterns,
+        SourceLocalSharedKeyHint,
+        SourceGit,
+        SourceGitKeepDir,
+        SourceGitFullUrl,
+        SourceHttp,
+        SourceHttpChecksum,
+        SourceHttpPerm,
+        SourceHttpUidGid,
+        ExecMetaBase,
+        ExecMetaProxy,
+        ExecMetaNetwork,
+        ExecMetaSecurity,
+        ExecMetaSetsDefaultPath,
+        ExecMountBind,
+        ExecMountCache,
+        ExecMountCacheSharing,
+        ExecMountSelector,
+        ExecMountTmpfs,
+        ExecMountSecret,
+        ExecMountSsh,
+        ExecCgroupsMounted,
+        FileBase,
+        Constraints,
+        Platform,
+        MetaIgnoreCache,
+        MetaDescription,
+        MetaExportCache,
+    ]
+};
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl From<Capability> for String {
+    fn from(cap: Capability) -> String {
+        cap.id().into()
+    }
+}
+
+/// Set of capabilities required by a graph.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CapabilitySet {
+    inner: BTreeSet<Capability>,
+}
+
+impl CapabilitySet {
+    pub fn contains(&self, cap: Capability) -> bool {
+        self.inner.contains(&cap)
+    }
+
+    pub fn insert(&mut self, cap: Capability) -> bool {
+        self.inner.insert(cap)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        self.inner.iter().cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Compare the required capabilities with the ones the daemon reported (`LLBCaps` of the `Ping` response).
+    pub fn check(&self, supported: &[ApiCap]) -> Result<(), UnsupportedCapabilities> {
+        let missing = {
+            self.iter()
+                .filter_map(|cap| {
+                    match supported.iter().find(|supported| supported.id == cap.id()) {
+                        Some(supported) if supported.enabled => None,
+
+                        Some(disabled) if !disabled.disabled_reason_msg.is_empty() => {
+                            Some((cap, Some(disabled.disabled_reason_msg.clone())))
+                        }
+
+                        _ => Some((cap, None)),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(UnsupportedCapabilities { missing })
+        }
+    }
+}
+
+impl FromIterator<Capability> for CapabilitySet {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Capability>,
+    {
+        Self {
+            inner: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Capability> for CapabilitySet {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Capability>,
+    {
+        self.inner.extend(iter)
+    }
+}
+
+/// Capabilities required by a graph, but not provided (or disabled) by the daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedCapabilities {
+    /// Missing capabilities and the reasons why the daemon disabled them (if reported).
+    pub missing: Vec<(Capability, Option<String>)>,
+}
+
+impl fmt::Display for UnsupportedCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "this graph needs ")?;
+
+        for (idx, (cap, reason)) in self.missing.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+
+            match reason {
+                Some(reason) => write!(f, "{} ({})", cap, reason)?,
+                None => write!(f, "{}", cap)?,
+            }
+        }
+
+        write!(f, ", daemon lacks it")
+    }
+}
+
+impl std::error::Error for UnsupportedCapabilities {}
+
+#[test]
+fn identifiers() {
+    for cap in ALL {
+        assert_eq!(Capability::from_id(cap.id()), Some(*cap));
+    }
+
+    assert_eq!(
+        Capability::from_id("exec.mount.bind"),
+        Some(Capability::ExecMountBind)
+    );
+    assert_eq!(Capability::from_id("unknown.cap"), None);
+}
+
+#[test]
+fn check() {
+    let caps = vec![
+        Capability::ExecMountBind,
+        Capability::ExecMountTmpfs,
+        Capability::FileBase,
+    ]
+    .into_iter()
+    .collect::<CapabilitySet>();
+
+    let supported = vec![
+        ApiCap {
+            id: "exec.mount.bind".into(),
+            enabled: true,
+            ..Default::default()
+        },
+        ApiCap {
+            id: "file.base".into(),
+            enabled: false,
+            disabled_reason_msg: "disabled by administrator".into(),
+            ..Default::default()
+        },
+    ];
+
+    let error = caps.check(&supported).unwrap_err();
+
+    assert_eq!(
+        error.missing,
+        vec![
+            (Capability::ExecMountTmpfs, None),
+            (
+                Capability::FileBase,
+                Some("disabled by administrator".into())
+            ),
+        ]
+    );
+
+    assert_eq!(
+        error.to_string(),
+        "this graph needs exec.mount.tmpfs, file.base (disabled by administrator), daemon lacks it"
+    );
+
+    assert!(caps.check(&[]).is_err());
+    assert!(CapabilitySet::default().check(&[]).is_ok());
+}
@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use buildkit_proto::pb::{self, op::Op};
+
+use crate::inspect::{DefinitionGraph, InvalidDefinition, Vertex};
+use crate::utils::Platform;
+
+/// Field-level difference of two structurally matching vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Human-readable description of the vertex in the new definition, e.g. `exec 'compile crate-2'`.
+    pub vertex: String,
+
+    /// What has changed, e.g. ``env `FOO` `` or `source image tag`.
+    pub field: String,
+
+    /// Previous value. `None` when the field was added.
+    pub old: Option<String>,
+
+    /// Current value. `None` when the field was removed.
+    pub new: Option<String>,
+}
+
+/// Compare two definitions and explain why their digests differ.
+///
+/// Vertices are matched structurally from the terminal upwards: inputs are paired by their position.
+/// Matching vertices with equal digests (and so their whole subgraphs) are skipped,
+/// and vertices that differ only because of their inputs are not reported.
+pub fn diff_definitions(
+    old: &pb::Definition,
+    new: &pb::Definition,
+) -> Result<Vec<Difference>, InvalidDefinition> {
+    let old = DefinitionGraph::decode(old)?;
+    let new = DefinitionGraph::decode(new)?;
+
+    let mut differences = vec![];
+    let mut visited = HashSet::new();
+    let mut pending = vec![(0, 0)];
+
+    while let Some((old_position, new_position)) = pending.pop() {
+        if !visited.insert((old_position, new_position)) {
+            continue;
+        }
+
+        let old_vertex = &old.vertices()[old_position];
+        let new_vertex = &new.vertices()[new_position];
+
+        if old_vertex.digest == new_vertex.digest {
+            continue;
+        }
+
+        let mut changes = Changes::new(new_vertex);
+
+        if old_vertex.kind() != new_vertex.kind() {
+            changes.changed("operation", old_vertex.kind(), new_vertex.kind());
+            differences.extend(changes.differences);
+            continue;
+        }
+
+        compare_vertices(&mut changes, old_vertex, new_vertex);
+
+        let old_inputs = old.inputs(old_vertex).collect::<Vec<_>>();
+        let new_inputs = new.inputs(new_vertex).collect::<Vec<_>>();
+
+        for index in 0..old_inputs.len().max(new_inputs.len()) {
+            let field = format!("input {}", index);
+
+            match (old_inputs.get(index), new_inputs.get(index)) {
+                (Some((old_input, old_output)), Some((new_input, new_output))) => {
+                    if old_output != new_output {
+                        changes.changed(format!("{} output", field), old_output, new_output);
+                    }
+
+                    pending.push((*old_input, *new_input));
+                }
+
+                (Some((old_input, _)), None) => {
+                    changes.removed(field, describe(&old.vertices()[*old_input]));
+                }
+
+                (None, Some((new_input, _))) => {
+                    changes.added(field, describe(&new.vertices()[*new_input]));
+                }
+
+                (None, None) => unreachable!(),
+            }
+        }
+
+        let inputs_digests = |graph: &DefinitionGraph, vertex: &Vertex| {
+            graph
+                .inputs(vertex)
+                .map(|(position, _)| graph.vertices()[position].digest.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Something we don't compare field by field (e.g. a newer protocol field) has changed.
+        if changes.differences.is_empty()
+            && inputs_digests(&old, old_vertex) == inputs_digests(&new, new_vertex)
+        {
+            changes.changed("operation", &old_vertex.digest, &new_vertex.digest);
+        }
+
+        differences.extend(changes.differences);
+    }
+
+    Ok(differences)
+}
+
+/// Short description of the vertex: its custom name or the most distinctive field.
+pub fn describe(vertex: &Vertex) -> String {
+    if let Some(name) = vertex.custom_name() {
+        return format!("{} '{}'", vertex.kind(), name);
+    }
+
+    match vertex.op.op {
+        Some(Op::Exec(ref exec)) => {
+            let args = exec.meta.as_ref().map(|meta| meta.args.join(" "));
+            format!("exec '{}'", args.unwrap_or_default())
+        }
+
+        Some(Op::Source(ref source)) => format!("source '{}'", source.identifier),
+        _ => vertex.kind().into(),
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(
+                f,
+                "{} changed from `{}` to `{}` in {}",
+                self.field, old, new, self.vertex
+            ),
+
+            (None, Some(new)) => write!(f, "{} `{}` added in {}", self.field, new, self.vertex),
+            (Some(old), None) => write!(f, "{} `{}` removed in {}", self.field, old, self.vertex),
+            (None, None) => write!(f, "{} changed in {}", self.field, self.vertex),
+        }
+    }
+}
+
+/// Differences of a single vertex.
+struct Changes {
+    vertex: String,
+    differences: Vec<Difference>,
+}
+
+impl Changes {
+    fn new(vertex: &Vertex) -> Self {
+        Self {
+            vertex: describe(vertex),
+            differences: vec![],
+        }
+    }
+
+    fn changed<F, O, N>(&mut self, field: F, old: O, new: N)
+    where
+        F: Into<String>,
+        O: fmt::Display,
+        N: fmt::Display,
+    {
+        self.push(field, Some(old.to_string()), Some(new.to_string()));
+    }
+
+    fn added<F, N>(&mut self, field: F, new: N)
+    where
+        F: Into<String>,
+        N: fmt::Display,
+    {
+        self.push(field, None, Some(new.to_string()));
+    }
+
+    fn removed<F, O>(&mut self, field: F, old: O)
+    where
+        F: Into<String>,
+        O: fmt::Display,
+    {
+        self.push(field, Some(old.to_string()), None);
+    }
+
+    fn compare<F, T>(&mut self, field: F, old: &T, new: &T)
+    where
+        F: Into<String>,
+        T: fmt::Display + PartialEq,
+    {
+        self.compare_with(field, old, new, ToString::to_string);
+    }
+
+    /// Compare structured values (e.g. protobuf messages) that are only printable with `Debug`.
+    fn compare_debug<F, T>(&mut self, field: F, old: &T, new: &T)
+    where
+        F: Into<String>,
+        T: fmt::Debug + PartialEq,
+    {
+        self.compare_with(field, old, new, |value| format!("{:?}", value));
+    }
+
+    fn compare_with<F, T, R>(&mut self, field: F, old: &T, new: &T, render: R)
+    where
+        F: Into<String>,
+        T: PartialEq,
+        R: Fn(&T) -> String,
+    {
+        if old != new {
+            self.changed(field, render(old), render(new));
+        }
+    }
+
+    /// Compare entries of two maps one by one.
+    fn compare_map<F, T, R>(
+        &mut self,
+        field: F,
+        old: &BTreeMap<String, T>,
+        new: &BTreeMap<String, T>,
+        render: R,
+    ) where
+        F: Fn(&str) -> String,
+        T: PartialEq,
+        R: Fn(&T) -> String,
+    {
+        let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+
+        for key in keys {
+            let name = field(key.as_str());
+
+            match (old.get(key), new.get(key)) {
+                (Some(old), Some(new)) => self.compare_with(name, old, new, &render),
+                (Some(old), None) => self.removed(name, render(old)),
+                (None, Some(new)) => self.added(name, render(new)),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn push<F>(&mut self, field: F, old: Option<String>, new: Option<String>)
+    where
+        F: Into<String>,
+    {
+        self.differences.push(Difference {
+            vertex: self.vertex.clone(),
+            field: field.into(),
+            old,
+            new,
+        });
+    }
+}
+
+fn compare_vertices(changes: &mut Changes, old: &Vertex, new: &Vertex) {
+    let platform = |vertex: &Vertex| vertex.op.platform.clone().map(Platform::from);
+    changes.compare_with("platform", &platform(old), &platform(new), |platform| {
+        platform
+            .as_ref()
+            .map_or_else(|| "default".into(), ToString::to_string)
+    });
+
+    changes.compare_debug("constraints", &old.op.constraints, &new.op.constraints);
+
+    match (&old.op.op, &new.op.op) {
+        (Some(Op::Exec(old)), Some(Op::Exec(new))) => compare_exec(changes, old, new),
+        (Some(Op::Source(old)), Some(Op::Source(new))) => compare_source(changes, old, new),
+        (Some(Op::File(old)), Some(Op::File(new))) => compare_file(changes, old, new),
+        (Some(Op::Build(old)), Some(Op::Build(new))) => changes.compare_debug("build", old, new),
+        _ => {}
+    }
+
+    let (old, new) = (&old.metadata, &new.metadata);
+
+    changes.compare("ignore cache", &old.ignore_cache, &new.ignore_cache);
+    changes.compare_with(
+        "export cache",
+        &old.export_cache.as_ref().map(|cache| cache.value),
+        &new.export_cache.as_ref().map(|cache| cache.value),
+        |value| value.map_or_else(|| "default".into(), |value| value.to_string()),
+    );
+
+    changes.compare_map(
+        |key| format!("description `{}`", key),
+        &old.description.clone().into_iter().collect(),
+        &new.description.clone().into_iter().collect(),
+        ToString::to_string,
+    );
+
+    changes.compare_map(
+        |key| format!("cap `{}`", key),
+        &old.caps.clone().into_iter().collect(),
+        &new.caps.clone().into_iter().collect(),
+        ToString::to_string,
+    );
+}
+
+fn compare_exec(changes: &mut Changes, old: &pb::ExecOp, new: &pb::ExecOp) {
+    let default_meta = pb::Meta::default();
+    let old_meta = old.meta.as_ref().unwrap_or(&default_meta);
+    let new_meta = new.meta.as_ref().unwrap_or(&default_meta);
+
+    changes.compare_with("args", &old_meta.args, &new_meta.args, |args| {
+        args.join(" ")
+    });
+    changes.compare_map(
+        |name| format!("env `{}`", name),
+        &env_map(&old_meta.env),
+        &env_map(&new_meta.env),
+        ToString::to_string,
+    );
+
+    changes.compare("cwd", &old_meta.cwd, &new_meta.cwd);
+    changes.compare("user", &old_meta.user, &new_meta.user);
+    changes.compare_debug("proxy env", &old_meta.proxy_env, &new_meta.proxy_env);
+    changes.compare_debug("extra hosts", &old_meta.extra_hosts, &new_meta.extra_hosts);
+    changes.compare_with(
+        "network",
+        &old.network,
+        &new.network,
+        |mode| match pb::NetMode::from_i32(*mode) {
+            Some(pb::NetMode::Unset) => "unset".into(),
+            Some(pb::NetMode::Host) => "host".into(),
+            Some(pb::NetMode::None) => "none".into(),
+            None => format!("unknown ({})", mode),
+        },
+    );
+
+    changes.compare_with("security", &old.security, &new.security, |mode| {
+        match pb::SecurityMode::from_i32(*mode) {
+            Some(pb::SecurityMode::Sandbox) => "sandbox".into(),
+            Some(pb::SecurityMode::Insecure) => "insecure".into(),
+            None => format!("unknown ({})", mode),
+        }
+    });
+
+    // Mount inputs are compared as the op inputs, so only the way they are mounted matters here.
+    let mounts = |exec: &pb::ExecOp| {
+        exec.mounts
+            .iter()
+            .map(|mount| {
+                let mut mount = mount.clone();
+                mount.input = if mount.input < 0 { -1 } else { 0 };
+
+                (mount.dest.clone(), mount)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    changes.compare_map(
+        |dest| format!("mount `{}`", dest),
+        &mounts(old),
+        &mounts(new),
+        |mount| format!("{:?}", mount),
+    );
+}
+
+fn compare_source(changes: &mut Changes, old: &pb::SourceOp, new: &pb::SourceOp) {
+    match (
+        split_image_identifier(&old.identifier),
+        split_image_identifier(&new.identifier),
+    ) {
+        (Some((old_name, old_tag)), Some((new_name, new_tag))) if old_name == new_name => {
+            changes.compare("source image tag", &old_tag, &new_tag);
+        }
+
+        _ => changes.compare("source identifier", &old.identifier, &new.identifier),
+    }
+
+    changes.compare_map(
+        |key| format!("source attr `{}`", key),
+        &old.attrs.clone().into_iter().collect(),
+        &new.attrs.clone().into_iter().collect(),
+        ToString::to_string,
+    );
+}
+
+fn compare_file(changes: &mut Changes, old: &pb::FileOp, new: &pb::FileOp) {
+    for index in 0..old.actions.len().max(new.actions.len()) {
+        let field = format!("file action {}", index);
+
+        match (old.actions.get(index), new.actions.get(index)) {
+            (Some(old), Some(new)) => changes.compare_debug(field, &old.action, &new.action),
+            (Some(old), None) => changes.removed(field, format!("{:?}", old.action)),
+            (None, Some(new)) => changes.added(field, format!("{:?}", new.action)),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn env_map(env: &[String]) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|item| {
+            let mut parts = item.splitn(2, '=');
+            let name = parts.next().unwrap_or_default();
+            let value = parts.next().unwrap_or_default();
+
+            (name.into(), value.into())
+        })
+        .collect()
+}
+
+/// Split `docker-image://docker.io/library/alpine:latest` into the image name and the tag (or digest).
+fn split_image_identifier(identifier: &str) -> Option<(&str, &str)> {
+    let reference = identifier.strip_prefix("docker-image://")?;
+
+    if let Some(position) = reference.find('@') {
+        return Some((&reference[..position], &reference[position + 1..]));
+    }
+
+    let name_start = reference
+        .rfind('/')
+        .map(|position| position + 1)
+        .unwrap_or(0);
+
+    match reference[name_start..].find(':') {
+        Some(position) => Some((
+            &reference[..name_start + position],
+            &reference[name_start + position + 1..],
+        )),
+
+        None => Some((reference, "latest")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn build(tag: &str, level: &str) -> pb::Definition {
+        let context = Source::local("context");
+        let builder_image = Source::image(format!("rustlang/rust:{}", tag));
+
+        let compile = |name: &'static str| {
+            Command::run("rustc")
+                .args(["--crate-name", name])
+                .env("RUST_LOG", level)
+                .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
+                .mount(Mount::ReadOnlyLayer(context.output(), "/context"))
+                .mount(Mount::Scratch(OutputIdx(0), "/target"))
+                .custom_name(format!("compile {}", name))
+        };
+
+        let first_command = compile("crate-1");
+        let second_command = compile("crate-2");
+
+        let assembly_op = FileSystem::sequence()
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(first_command.output(0), "/target/crate-1"))
+                    .to(OutputIdx(0), LayerPath::Scratch("/crate-1")),
+            )
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(
+                        second_command.output(0),
+                        "/target/crate-2",
+                    ))
+                    .to(OutputIdx(1), LayerPath::Own(OwnOutputIdx(0), "/crate-2")),
+            );
+
+        Terminal::with(assembly_op.output(1)).into_definition()
+    }
+
+    #[test]
+    fn identical() {
+        let differences = diff_definitions(&build("nightly", "info"), &build("nightly", "info"));
+        assert_eq!(differences.unwrap(), vec![]);
+    }
+
+    #[test]
+    fn env_changes() {
+        let differences =
+            { diff_definitions(&build("nightly", "info"), &build("nightly", "debug")).unwrap() };
+
+        assert_eq!(
+            differences
+                .iter()
+                .map(ToString::to_string)
+                .collect::<BTreeSet<_>>(),
+            vec![
+                "env `RUST_LOG` changed from `info` to `debug` in exec 'compile crate-1'",
+                "env `RUST_LOG` changed from `info` to `debug` in exec 'compile crate-2'",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect()
+        );
+    }
+
+    #[test]
+    fn image_tag_changes() {
+        let differences =
+            { diff_definitions(&build("nightly", "info"), &build("1.42.0", "info")).unwrap() };
+
+        assert_eq!(
+            differences,
+            vec![Difference {
+                vertex: "source 'docker-image://docker.io/rustlang/rust:1.42.0'".into(),
+                field: "source image tag".into(),
+                old: Some("nightly".into()),
+                new: Some("1.42.0".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn image_identifiers() {
+        assert_eq!(
+            split_image_identifier("docker-image://docker.io/library/alpine:3.11"),
+            Some(("docker.io/library/alpine", "3.11"))
+        );
+
+        assert_eq!(
+            split_image_identifier("docker-image://localhost:5000/app"),
+            Some(("localhost:5000/app", "latest"))
+        );
+
+        assert_eq!(
+            split_image_identifier("docker-image://docker.io/library/alpine:3.11@sha256:abc"),
+            Some(("docker.io/library/alpine:3.11", "sha256:abc"))
+        );
+
+        assert_eq!(split_image_identifier("local://context"), None);
+    }
+}
@@ -0,0 +1,397 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use buildkit_proto::pb;
+
+use crate::ops::exec::Command;
+use crate::ops::fs::SequenceOperation;
+use crate::ops::source::{GitSource, HttpSource, ImageSource, LocalSource};
+use crate::ops::Terminal;
+use crate::serialization::Operation;
+use crate::utils::{OperationOutput, OutputIdx};
+
+static LAST_GRAPH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Lifetime-free graph builder. Operations are owned by the graph and referenced with copyable handles,
+/// so the graph can be freely moved between async functions or stored in structs.
+///
+/// Handles can be turned back into `OperationOutput` with `Graph::output`,
+/// so the graph can be mixed with operations built in a usual way.
+///
+/// A cloned graph gets its own identity: handles of the original can't be used with the clone.
+#[derive(Debug)]
+pub struct Graph {
+    id: u64,
+    ops: Vec<Arc<dyn Operation>>,
+}
+
+/// Copyable reference to an operation owned by a `Graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpHandle {
+    graph: u64,
+    index: usize,
+    outputs: u32,
+}
+
+/// Copyable reference to an output of an operation owned by a `Graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputHandle {
+    graph: u64,
+    index: usize,
+    output: u32,
+}
+
+/// Operation that can be owned by a `Graph`.
+pub trait GraphOperation {
+    /// What `Graph::add` returns: `OutputHandle` for single-output operations,
+    /// and `OpHandle` for operations with several outputs.
+    type Handle: Copy;
+
+    #[doc(hidden)]
+    fn into_node(self) -> GraphNode;
+
+    #[doc(hidden)]
+    fn handle(op: OpHandle) -> Self::Handle;
+}
+
+#[doc(hidden)]
+pub struct GraphNode {
+    op: Arc<dyn Operation>,
+    outputs: u32,
+}
+
+/// Error returned when a handle can't be resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    /// The handle was created by another graph.
+    ForeignHandle,
+
+    /// The operation doesn't have the requested output.
+    MissingOutput { output: u32, available: u32 },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::ForeignHandle => write!(f, "the handle belongs to another graph"),
+
+            GraphError::MissingOutput { output, available } => write!(
+                f,
+                "output {} requested, but the operation has only {}",
+                output, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self {
+            id: LAST_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            ops: vec![],
+        }
+    }
+
+    /// Take ownership of the operation and return a handle to it.
+    pub fn add<T>(&mut self, op: T) -> T::Handle
+    where
+        T: GraphOperation,
+    {
+        let node = op.into_node();
+        self.ops.push(node.op);
+
+        T::handle(OpHandle {
+            graph: self.id,
+            index: self.ops.len() - 1,
+            outputs: node.outputs,
+        })
+    }
+
+    /// Resolve the handle into an output that can be used with the usual operation builders.
+    pub fn output(&self, handle: OutputHandle) -> Result<OperationOutput<'static>, GraphError> {
+        if handle.graph != self.id {
+            return Err(GraphError::ForeignHandle);
+        }
+
+        Ok(OperationOutput::owned(
+            self.ops[handle.index].clone(),
+            OutputIdx(handle.output),
+        ))
+    }
+
+    /// Number of operations owned by the graph.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Serialize the graph with the `output` as a final result.
+    pub fn into_definition(self, output: OutputHandle) -> Result<pb::Definition, GraphError> {
+        Ok(Terminal::with(self.output(output)?).into_definition())
+    }
+
+    pub fn write_definition(self, output: OutputHandle, writer: impl Write) -> io::Result<()> {
+        let output = {
+            self.output(output)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?
+        };
+
+        Terminal::with(output).write_definition(writer)
+    }
+}
+
+impl Clone for Graph {
+    fn clone(&self) -> Self {
+        Self {
+            id: LAST_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            ops: self.ops.clone(),
+        }
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpHandle {
+    pub fn output(self, index: u32) -> Result<OutputHandle, GraphError> {
+        if index >= self.outputs {
+            return Err(GraphError::MissingOutput {
+                output: index,
+                available: self.outputs,
+            });
+        }
+
+        Ok(OutputHandle {
+            graph: self.graph,
+            index: self.index,
+            output: index,
+        })
+    }
+
+    /// Number of outputs the operation had when it was added to the graph.
+    pub fn outputs(self) -> u32 {
+        self.outputs
+    }
+
+    fn single_output(self) -> OutputHandle {
+        OutputHandle {
+            graph: self.graph,
+            index: self.index,
+            output: 0,
+        }
+    }
+}
+
+impl GraphOperation for Command<'static> {
+    type Handle = OpHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            outputs: self.output_count(),
+            op: Arc::new(self),
+        }
+    }
+
+    fn handle(op: OpHandle) -> OpHandle {
+        op
+    }
+}
+
+impl GraphOperation for SequenceOperation<'static> {
+    type Handle = OpHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            outputs: self.last_output_index().map_or(0, |last| last + 1),
+            op: Arc::new(self),
+        }
+    }
+
+    fn handle(op: OpHandle) -> OpHandle {
+        op
+    }
+}
+
+impl GraphOperation for ImageSource {
+    type Handle = OutputHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            op: Arc::new(self),
+            outputs: 1,
+        }
+    }
+
+    fn handle(op: OpHandle) -> OutputHandle {
+        op.single_output()
+    }
+}
+
+impl GraphOperation for GitSource {
+    type Handle = OutputHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            op: Arc::new(self),
+            outputs: 1,
+        }
+    }
+
+    fn handle(op: OpHandle) -> OutputHandle {
+        op.single_output()
+    }
+}
+
+impl GraphOperation for LocalSource {
+    type Handle = OutputHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            op: Arc::new(self),
+            outputs: 1,
+        }
+    }
+
+    fn handle(op: OpHandle) -> OutputHandle {
+        op.single_output()
+    }
+}
+
+impl GraphOperation for HttpSource {
+    type Handle = OutputHandle;
+
+    fn into_node(self) -> GraphNode {
+        GraphNode {
+            op: Arc::new(self),
+            outputs: 1,
+        }
+    }
+
+    fn handle(op: OpHandle) -> OutputHandle {
+        op.single_output()
+    }
+}
+
+#[test]
+fn serialization() {
+    use crate::prelude::*;
+
+    let mut graph = Graph::new();
+
+    let builder_image = graph.add(Source::image("library/alpine:latest"));
+    let command = graph.add(
+        Command::run("/bin/sh")
+            .args(["-c", "echo 'test string 5' > /out/file0"])
+            .mount(Mount::ReadOnlyLayer(
+                graph.output(builder_image).unwrap(),
+                "/",
+            ))
+            .mount(Mount::Scratch(OutputIdx(0), "/out")),
+    );
+
+    let command_output = graph.output(command.output(0).unwrap()).unwrap();
+    let fs = graph.add(
+        FileSystem::sequence().append(
+            FileSystem::copy()
+                .from(LayerPath::Other(command_output, "/file0"))
+                .to(OutputIdx(0), LayerPath::Scratch("/file1")),
+        ),
+    );
+
+    assert_eq!(graph.len(), 3);
+
+    let graph_definition = graph.into_definition(fs.output(0).unwrap()).unwrap();
+
+    let builder_image = Source::image("library/alpine:latest");
+    let command = Command::run("/bin/sh")
+        .args(["-c", "echo 'test string 5' > /out/file0"])
+        .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
+        .mount(Mount::Scratch(OutputIdx(0), "/out"));
+
+    let fs = FileSystem::sequence().append(
+        FileSystem::copy()
+            .from(LayerPath::Other(command.output(0), "/file0"))
+            .to(OutputIdx(0), LayerPath::Scratch("/file1")),
+    );
+
+    let terminal_definition = Terminal::with(fs.output(0)).into_definition();
+
+    assert_eq!(graph_definition.def, terminal_definition.def);
+    assert_eq!(graph_definition.metadata, terminal_definition.metadata);
+}
+
+#[test]
+fn foreign_handle() {
+    use crate::prelude::*;
+
+    let mut first = Graph::new();
+    let second = Graph::new();
+
+    let image = first.add(Source::image("library/alpine:latest"));
+
+    assert!(first.output(image).is_ok());
+    assert_eq!(second.output(image).unwrap_err(), GraphError::ForeignHandle);
+}
+
+#[test]
+fn cloned_graph() {
+    use crate::prelude::*;
+
+    let mut original = Graph::new();
+    let image = original.add(Source::image("library/alpine:latest"));
+
+    let mut cloned = original.clone();
+    let context = cloned.add(Source::local("context"));
+
+    assert_eq!(cloned.len(), 2);
+    assert_eq!(original.len(), 1);
+
+    assert_eq!(cloned.output(image).unwrap_err(), GraphError::ForeignHandle);
+    assert_eq!(
+        original.output(context).unwrap_err(),
+        GraphError::ForeignHandle
+    );
+}
+
+#[test]
+fn missing_output() {
+    use crate::prelude::*;
+
+    let mut graph = Graph::new();
+
+    let command = graph.add(
+        Command::run("/bin/true")
+            .mount(Mount::Scratch(OutputIdx(0), "/"))
+            .mount(Mount::Scratch(OutputIdx(1), "/out")),
+    );
+
+    let fs = graph.add(FileSystem::sequence());
+
+    assert_eq!(command.outputs(), 2);
+    assert!(command.output(1).is_ok());
+    assert_eq!(
+        command.output(2).unwrap_err(),
+        GraphError::MissingOutput {
+            output: 2,
+            available: 2,
+        }
+    );
+
+    assert_eq!(
+        fs.output(0).unwrap_err(),
+        GraphError::MissingOutput {
+            output: 0,
+            available: 0,
+        }
+    );
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use buildkit_proto::pb::{self, op::Op};
+use prost::{DecodeError, Message};
+
+use crate::serialization::Node;
+
+/// Decoded `pb::Definition` with vertices ordered from the terminal upwards.
+#[derive(Debug, Clone)]
+pub struct DefinitionGraph {
+    vertices: Vec<Vertex>,
+    positions: HashMap<String, usize>,
+}
+
+/// Single operation of a decoded definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vertex {
+    pub digest: String,
+    pub op: pb::Op,
+    pub metadata: pb::OpMetadata,
+}
+
+/// Reasons a `pb::Definition` can't be decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidDefinition {
+    /// There are no operations at all.
+    Empty,
+
+    /// The operation at `index` is not a valid `pb::Op`.
+    Decode { index: usize, error: DecodeError },
+
+    /// The operation refers to an input that is not a part of the definition.
+    MissingInput { digest: String, input: String },
+}
+
+impl DefinitionGraph {
+    /// Decode the definition. The last operation is treated as the terminal, like BuildKit does.
+    pub fn decode(definition: &pb::Definition) -> Result<Self, InvalidDefinition> {
+        let mut decoded = vec![];
+
+        for (index, bytes) in definition.def.iter().enumerate() {
+            let op = pb::Op::decode(bytes.as_slice())
+                .map_err(|error| InvalidDefinition::Decode { index, error })?;
+
+            let digest = Node::get_digest(bytes);
+            let metadata = definition
+                .metadata
+                .get(&digest)
+                .cloned()
+                .unwrap_or_default();
+
+            decoded.push(Vertex {
+                digest,
+                op,
+                metadata,
+            });
+        }
+
+        let terminal = decoded
+            .len()
+            .checked_sub(1)
+            .ok_or(InvalidDefinition::Empty)?;
+        let by_digest = {
+            decoded
+                .iter()
+                .enumerate()
+                .map(|(index, vertex)| (vertex.digest.clone(), index))
+                .collect::<HashMap<_, _>>()
+        };
+
+        for vertex in &decoded {
+            for input in &vertex.op.inputs {
+                if !by_digest.contains_key(&input.digest) {
+                    return Err(InvalidDefinition::MissingInput {
+                        digest: vertex.digest.clone(),
+                        input: input.digest.clone(),
+                    });
+                }
+            }
+        }
+
+        // Depth-first from the terminal, so the order doesn't depend on how the definition was serialized.
+        let mut order = vec![];
+        let mut visited = vec![false; decoded.len()];
+        let mut pending = vec![terminal];
+
+        while let Some(index) = pending.pop() {
+            if visited[index] {
+                continue;
+            }
+
+            visited[index] = true;
+            order.push(index);
+
+            for input in decoded[index].op.inputs.iter().rev() {
+                pending.push(by_digest[&input.digest]);
+            }
+        }
+
+        // Operations the terminal doesn't depend on are kept in their original order.
+        order.extend((0..decoded.len()).filter(|index| !visited[*index]));
+
+        let mut slots = decoded.into_iter().map(Some).collect::<Vec<_>>();
+        let vertices = {
+            order
+                .into_iter()
+                .map(|index| slots[index].take().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let positions = {
+            vertices
+                .iter()
+                .enumerate()
+                .map(|(position, vertex)| (vertex.digest.clone(), position))
+                .collect()
+        };
+
+        Ok(Self {
+            vertices,
+            positions,
+        })
+    }
+
+    /// Vertices starting from the terminal.
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn terminal(&self) -> &Vertex {
+        &self.vertices[0]
+    }
+
+    /// Position of the vertex in `vertices()`.
+    pub fn position(&self, digest: &str) -> Option<usize> {
+        self.positions.get(digest).cloned()
+    }
+
+    /// Positions of the input vertices together with the output indices they refer to.
+    pub fn inputs<'a>(&'a self, vertex: &'a Vertex) -> impl Iterator<Item = (usize, i64)> + 'a {
+        vertex
+            .op
+            .inputs
+            .iter()
+            .map(move |input| (self.positions[&input.digest], input.index))
+    }
+}
+
+impl Vertex {
+    /// Short name of the operation: `exec`, `source`, `file`, `build` or `terminal`.
+    pub fn kind(&self) -> &'static str {
+        match self.op.op {
+            Some(Op::Exec(_)) => "exec",
+            Some(Op::Source(_)) => "source",
+            Some(Op::File(_)) => "file",
+            Some(Op::Build(_)) => "build",
+            None => "terminal",
+        }
+    }
+
+    /// Custom name set with `OperationBuilder::custom_name`.
+    pub fn custom_name(&self) -> Option<&str> {
+        self.metadata
+            .description
+            .get("llb.customname")
+            .map(String::as_str)
+    }
+}
+
+impl fmt::Display for InvalidDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidDefinition::Empty => write!(f, "definition has no operations"),
+
+            InvalidDefinition::Decode { index, error } => {
+                write!(f, "unable to decode operation #{}: {}", index, error)
+            }
+
+            InvalidDefinition::MissingInput { digest, input } => {
+                write!(f, "operation {} refers to unknown input {}", digest, input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidDefinition {}
+
+#[test]
+fn decoding() {
+    use crate::prelude::*;
+
+    let image = Source::image("library/alpine:latest");
+    let command = Command::run("/bin/sh")
+        .args(["-c", "echo hello > /out/greeting"])
+        .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+        .mount(Mount::Scratch(OutputIdx(0), "/out"))
+        .custom_name("greet");
+
+    let definition = Terminal::with(command.output(0)).into_definition();
+    let graph = DefinitionGraph::decode(&definition).unwrap();
+
+    assert_eq!(
+        graph
+            .vertices()
+            .iter()
+            .map(Vertex::kind)
+            .collect::<Vec<_>>(),
+        vec!["terminal", "exec", "source"]
+    );
+
+    assert_eq!(graph.vertices()[1].custom_name(), Some("greet"));
+    assert_eq!(
+        graph.inputs(graph.terminal()).collect::<Vec<_>>(),
+        vec![(1, 0)]
+    );
+
+    assert_eq!(
+        DefinitionGraph::decode(&Default::default()).unwrap_err(),
+        InvalidDefinition::Empty
+    );
+}
@@ -10,14 +10,35 @@ mod serialization;
 /// Supported operations - building blocks of the LLB definition graph.
 pub mod ops;
 
+/// Typed LLB capabilities and their negotiation with the daemon.
+pub mod caps;
+
+/// Lifetime-free graph builder with copyable operation handles.
+pub mod graph;
+
+/// Semantic comparison of two definitions.
+pub mod diff;
+
+/// Decoding of serialized definitions back into a graph of operations.
+pub mod inspect;
+
+/// Golden snapshot testing of generated graphs.
+pub mod snapshot;
+
+/// High-level chainable state API, similar to Go's `llb.State`.
+pub mod state;
+
 /// Various helpers and types.
 pub mod utils;
 
 /// Convenient re-export of a commonly used things.
 pub mod prelude {
-    pub use crate::ops::exec::Mount;
+    pub use crate::caps::Capability;
+    pub use crate::graph::{Graph, GraphError, OpHandle, OutputHandle};
+    pub use crate::ops::exec::{Mount, Shell};
     pub use crate::ops::fs::LayerPath;
     pub use crate::ops::source::ResolveMode;
     pub use crate::ops::*;
-    pub use crate::utils::{OperationOutput, OutputIdx, OwnOutputIdx};
+    pub use crate::state::State;
+    pub use crate::utils::{OperationOutput, OutputIdx, OwnOutputIdx, Platform};
 }
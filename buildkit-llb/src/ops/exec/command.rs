@@ -4,16 +4,22 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use buildkit_proto::pb::{
-    self, op::Op, ExecOp, Input, MountType, NetMode, OpMetadata, SecurityMode,
+    self, op::Op, ExecOp, ExportCache, Input, MountType, NetMode, OpMetadata, SecurityMode,
 };
 use either::Either;
 
 use super::context::Context;
 use super::mount::Mount;
+use super::shell::Shell;
 
+use crate::caps::Capability;
 use crate::ops::{MultiBorrowedOutput, MultiOwnedOutput, OperationBuilder};
 use crate::serialization::{Context as SerializationCtx, Node, Operation, OperationId, Result};
-use crate::utils::{OperationOutput, OutputIdx};
+use crate::utils::{OperationOutput, OutputIdx, Platform};
+
+/// Output of a `Command` assigned by `Command::mount_scratch` or `Command::mount_layer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExecOutput(u32);
 
 /// Command execution operation. This is what a Dockerfile's `RUN` directive is translated to.
 #[derive(Debug, Clone)]
@@ -23,10 +29,12 @@ pub struct Command<'a> {
     context: Context,
     root_mount: Option<Mount<'a, PathBuf>>,
     other_mounts: Vec<Mount<'a, PathBuf>>,
+    platform: Option<Platform>,
 
     description: HashMap<String, String>,
     caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
 }
 
 impl<'a> Command<'a> {
@@ -40,13 +48,23 @@ impl<'a> Command<'a> {
             context: Context::new(name),
             root_mount: None,
             other_mounts: vec![],
+            platform: None,
 
             description: Default::default(),
             caps: Default::default(),
             ignore_cache: false,
+            export_cache: None,
         }
     }
 
+    /// Run the `script` with the default shell (`/bin/sh -c`).
+    pub fn shell<S>(script: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Shell::default().command(script)
+    }
+
     pub fn args<A, S>(mut self, args: A) -> Self
     where
         A: IntoIterator<Item = S>,
@@ -97,27 +115,65 @@ impl<'a> Command<'a> {
         self
     }
 
+    /// Sets the platform the command should be executed on.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self.caps.insert(Capability::Platform.into(), true);
+        self
+    }
+
     pub fn mount<P>(mut self, mount: Mount<'a, P>) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.add_mount(mount);
+        self
+    }
+
+    /// Mount an empty writable layer and return an automatically assigned output for it.
+    pub fn mount_scratch<P>(mut self, path: P) -> (Self, ExecOutput)
+    where
+        P: AsRef<Path>,
+    {
+        let output = self.next_output_index();
+
+        self.add_mount(Mount::Scratch(output, path));
+        (self, ExecOutput(output.0))
+    }
+
+    /// Mount a writable output of another operation and return an automatically assigned output for it.
+    pub fn mount_layer<P>(mut self, input: OperationOutput<'a>, path: P) -> (Self, ExecOutput)
+    where
+        P: AsRef<Path>,
+    {
+        let output = self.next_output_index();
+
+        self.add_mount(Mount::Layer(output, input, path));
+        (self, ExecOutput(output.0))
+    }
+
+    fn add_mount<P>(&mut self, mount: Mount<'a, P>)
     where
         P: AsRef<Path>,
     {
         match mount {
             Mount::Layer(..) | Mount::ReadOnlyLayer(..) | Mount::Scratch(..) => {
-                self.caps.insert("exec.mount.bind".into(), true);
+                self.caps.insert(Capability::ExecMountBind.into(), true);
             }
 
             Mount::ReadOnlySelector(..) => {
-                self.caps.insert("exec.mount.bind".into(), true);
-                self.caps.insert("exec.mount.selector".into(), true);
+                self.caps.insert(Capability::ExecMountBind.into(), true);
+                self.caps.insert(Capability::ExecMountSelector.into(), true);
             }
 
             Mount::SharedCache(..) => {
-                self.caps.insert("exec.mount.cache".into(), true);
-                self.caps.insert("exec.mount.cache.sharing".into(), true);
+                self.caps.insert(Capability::ExecMountCache.into(), true);
+                self.caps
+                    .insert(Capability::ExecMountCacheSharing.into(), true);
             }
 
             Mount::OptionalSshAgent(..) => {
-                self.caps.insert("exec.mount.ssh".into(), true);
+                self.caps.insert(Capability::ExecMountSsh.into(), true);
             }
         }
 
@@ -126,12 +182,42 @@ impl<'a> Command<'a> {
         } else {
             self.other_mounts.push(mount.into_owned());
         }
+    }
 
-        self
+    /// Number of outputs, including the gaps between the assigned indices.
+    pub(crate) fn output_count(&self) -> u32 {
+        self.next_output_index().0
+    }
+
+    fn next_output_index(&self) -> OutputIdx {
+        let last = {
+            self.root_mount
+                .iter()
+                .chain(self.other_mounts.iter())
+                .filter_map(|mount| match mount {
+                    Mount::Scratch(output, ..) | Mount::Layer(output, ..) => Some(output.0),
+                    _ => None,
+                })
+                .max()
+        };
+
+        OutputIdx(last.map_or(0, |last| last + 1))
+    }
+}
+
+impl ExecOutput {
+    pub fn index(self) -> OutputIdx {
+        OutputIdx(self.0)
+    }
+}
+
+impl From<ExecOutput> for OutputIdx {
+    fn from(output: ExecOutput) -> Self {
+        output.index()
     }
 }
 
-impl<'a, 'b: 'a> MultiBorrowedOutput<'b> for Command<'b> {
+impl<'b> MultiBorrowedOutput<'b> for Command<'b> {
     fn output(&'b self, index: u32) -> OperationOutput<'b> {
         // TODO: check if the requested index available.
         OperationOutput::borrowed(self, OutputIdx(index))
@@ -146,13 +232,13 @@ impl<'a> MultiOwnedOutput<'a> for Arc<Command<'a>> {
 }
 
 impl<'a> OperationBuilder<'a> for Command<'a> {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -160,6 +246,12 @@ impl<'a> OperationBuilder<'a> for Command<'a> {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl<'a> Operation for Command<'a> {
@@ -302,6 +394,7 @@ impl<'a> Operation for Command<'a> {
             })),
 
             inputs: inputs.into_iter().flatten().collect(),
+            platform: self.platform.clone().map(Into::into),
 
             ..Default::default()
         };
@@ -310,8 +403,7 @@ impl<'a> Operation for Command<'a> {
             description: self.description.clone(),
             caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
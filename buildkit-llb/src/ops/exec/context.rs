@@ -30,18 +30,18 @@ impl Context {
     }
 }
 
-impl Into<Meta> for Context {
-    fn into(self) -> Meta {
+impl From<Context> for Meta {
+    fn from(context: Context) -> Self {
         Meta {
             args: {
-                once(self.name.clone())
-                    .chain(self.args.iter().cloned())
+                once(context.name.clone())
+                    .chain(context.args.iter().cloned())
                     .collect()
             },
 
-            env: self.env,
-            cwd: self.cwd.to_string_lossy().into(),
-            user: self.user,
+            env: context.env,
+            cwd: context.cwd.to_string_lossy().into(),
+            user: context.user,
 
             ..Default::default()
         }
@@ -1,9 +1,11 @@
 mod command;
 mod context;
 mod mount;
+mod shell;
 
-pub use command::Command;
+pub use command::{Command, ExecOutput};
 pub use mount::Mount;
+pub use shell::{EmptyShellError, Shell};
 
 #[test]
 fn serialization() {
@@ -13,13 +15,13 @@ fn serialization() {
     crate::check_op!(
         {
             Command::run("/bin/sh")
-                .args(&["-c", "echo 'test string' > /out/file0"])
+                .args(["-c", "echo 'test string' > /out/file0"])
                 .env("HOME", "/root")
                 .custom_name("exec custom name")
         },
         |digest| { "sha256:dc9a5a3cd84bb1c7b633f1750fdfccd9d0a69d060f8e3babb297bc190e2d7484" },
         |description| { vec![("llb.customname", "exec custom name")] },
-        |caps| { vec![] },
+        |caps| { vec!["meta.description"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -46,6 +48,44 @@ fn serialization() {
     );
 }
 
+#[test]
+fn serialization_with_metadata() {
+    use crate::prelude::*;
+
+    crate::check_op!(
+        {
+            Command::run("/bin/sh")
+                .args(["-c", "echo 'test string' > /out/file0"])
+                .env("HOME", "/root")
+                .custom_name("exec custom name")
+                .description("com.example.step", "vendor")
+                .export_cache(false)
+        },
+        |digest| { "sha256:dc9a5a3cd84bb1c7b633f1750fdfccd9d0a69d060f8e3babb297bc190e2d7484" },
+        |description| {
+            vec![
+                ("llb.customname", "exec custom name"),
+                ("com.example.step", "vendor"),
+            ]
+        },
+        |caps| { vec!["meta.description", "meta.exportcache"] },
+        |export_cache| { Some(false) },
+    );
+
+    crate::check_op!(
+        Command::run("cargo").args(["build"]).export_cache(true),
+        |caps| { vec!["meta.exportcache"] },
+        |export_cache| { Some(true) },
+    );
+
+    crate::check_op!(
+        Command::run("cargo").args(["build"]),
+        |description| { vec![] },
+        |caps| { vec![] },
+        |export_cache| { None },
+    );
+}
+
 #[test]
 fn serialization_with_env_iter() {
     use crate::prelude::*;
@@ -53,7 +93,7 @@ fn serialization_with_env_iter() {
 
     crate::check_op!(
         {
-            Command::run("cargo").args(&["build"]).env_iter(vec![
+            Command::run("cargo").args(["build"]).env_iter(vec![
                 ("HOME", "/root"),
                 ("PATH", "/bin"),
                 ("CARGO_HOME", "/root/.cargo"),
@@ -94,7 +134,7 @@ fn serialization_with_cwd() {
     use buildkit_proto::pb::{op::Op, ExecOp, Meta, NetMode, SecurityMode};
 
     crate::check_op!(
-        Command::run("cargo").args(&["build"]).cwd("/rust-src"),
+        Command::run("cargo").args(["build"]).cwd("/rust-src"),
         |digest| { "sha256:b8120a0e1d1f7fcaa3d6c95db292d064524dc92c6cae8b97672d4e1eafcd03fa" },
         |description| { vec![] },
         |caps| { vec![] },
@@ -125,7 +165,7 @@ fn serialization_with_user() {
     use buildkit_proto::pb::{op::Op, ExecOp, Meta, NetMode, SecurityMode};
 
     crate::check_op!(
-        Command::run("cargo").args(&["build"]).user("builder"),
+        Command::run("cargo").args(["build"]).user("builder"),
         |digest| { "sha256:7631ea645e2126e9dbc5d9ae789e34301d9d5c80ce89bfa72bc9b82aa43b57c0" },
         |description| { vec![] },
         |caps| { vec![] },
@@ -162,7 +202,7 @@ fn serialization_with_mounts() {
     let final_image = Source::image("library/alpine:latest");
 
     let command = Command::run("cargo")
-        .args(&["build"])
+        .args(["build"])
         .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
         .mount(Mount::Scratch(OutputIdx(1), "/tmp"))
         .mount(Mount::ReadOnlySelector(
@@ -295,7 +335,7 @@ fn serialization_with_several_root_mounts() {
     let final_image = Source::image("library/alpine:latest");
 
     let command = Command::run("cargo")
-        .args(&["build"])
+        .args(["build"])
         .mount(Mount::Scratch(OutputIdx(0), "/tmp"))
         .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
         .mount(Mount::Scratch(OutputIdx(1), "/var"))
@@ -368,6 +408,40 @@ fn serialization_with_several_root_mounts() {
     );
 }
 
+#[test]
+fn serialization_with_auto_outputs() {
+    use crate::prelude::*;
+
+    let builder_image = Source::image("rustlang/rust:nightly");
+    let final_image = Source::image("library/alpine:latest");
+
+    let (command, tmp) = Command::run("cargo").args(["build"]).mount_scratch("/tmp");
+    let command = command.mount(Mount::ReadOnlyLayer(builder_image.output(), "/"));
+
+    let (command, var) = command.mount_scratch("/var");
+    let command = command.mount(Mount::ReadOnlyLayer(final_image.output(), "/"));
+
+    assert_eq!(tmp.index().0, 0);
+    assert_eq!(var.index().0, 1);
+
+    crate::check_op!(
+        command,
+        |digest| { "sha256:baa1bf591d2c47058b7361a0284fa8a3f1bd0fac8a93c87affa77ddc0a5026fd" },
+        |caps| { vec!["exec.mount.bind"] },
+    );
+
+    let (command, target) = {
+        Command::run("cargo")
+            .args(["build"])
+            .mount_layer(final_image.output(), "/target")
+    };
+
+    let (_, tmp) = command.mount_scratch("/tmp");
+
+    assert_eq!(target.index().0, 0);
+    assert_eq!(tmp.index().0, 1);
+}
+
 #[test]
 fn serialization_with_ssh_mounts() {
     use crate::prelude::*;
@@ -375,7 +449,7 @@ fn serialization_with_ssh_mounts() {
 
     let builder_image = Source::image("rustlang/rust:nightly");
     let command = Command::run("cargo")
-        .args(&["build"])
+        .args(["build"])
         .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
         .mount(Mount::OptionalSshAgent("/run/buildkit/ssh_agent.0"));
 
@@ -438,3 +512,26 @@ fn serialization_with_ssh_mounts() {
         },
     );
 }
+
+#[test]
+fn shell_form() {
+    use crate::prelude::*;
+
+    let definitions = vec![
+        (
+            Command::shell("echo $HOME"),
+            Command::run("/bin/sh").args(["-c", "echo $HOME"]),
+        ),
+        (
+            Shell::default().script(vec!["cd /src", "make"]),
+            Command::run("/bin/sh").args(["-c", "set -eu\ncd /src\nmake"]),
+        ),
+    ];
+
+    for (shell, exec) in definitions {
+        assert_eq!(
+            Terminal::with(shell.output(0)).into_definition().def,
+            Terminal::with(exec.output(0)).into_definition().def
+        );
+    }
+}
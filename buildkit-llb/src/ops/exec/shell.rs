@@ -0,0 +1,84 @@
+use std::fmt;
+use std::iter::once;
+
+use super::command::Command;
+
+/// Shell used to run shell-form commands (Dockerfile's `SHELL` directive). Defaults to `/bin/sh -c`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shell {
+    args: Vec<String>,
+}
+
+impl Shell {
+    /// Shell with the executable and its arguments, e.g. `["/bin/bash", "-c"]`.
+    /// The script is passed to the shell as the last argument.
+    pub fn new<A, S>(args: A) -> Result<Self, EmptyShellError>
+    where
+        A: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+
+        if args.is_empty() {
+            return Err(EmptyShellError);
+        }
+
+        Ok(Self { args })
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Command that runs the `script` with the shell.
+    pub fn command<'a, S>(&self, script: S) -> Command<'a>
+    where
+        S: Into<String>,
+    {
+        let script = script.into();
+
+        Command::run(self.args[0].clone()).args(self.args[1..].iter().chain(once(&script)))
+    }
+
+    /// Command that runs the lines as a single script, failing on the first error or unset variable (`set -eu`).
+    pub fn script<'a, I, S>(&self, lines: I) -> Command<'a>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut script = String::from("set -eu");
+
+        for line in lines {
+            script.push('\n');
+            script.push_str(line.as_ref());
+        }
+
+        self.command(script)
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self {
+            args: vec!["/bin/sh".into(), "-c".into()],
+        }
+    }
+}
+
+/// Error returned when a shell is created without the executable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmptyShellError;
+
+impl fmt::Display for EmptyShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "shell executable is not specified")
+    }
+}
+
+impl std::error::Error for EmptyShellError {}
+
+#[test]
+fn empty_shell() {
+    assert_eq!(Shell::new(Vec::<String>::new()), Err(EmptyShellError));
+    assert_eq!(Shell::new(vec!["/bin/sh", "-c"]).unwrap(), Shell::default());
+}
@@ -7,6 +7,7 @@ use buildkit_proto::pb;
 use super::path::{LayerPath, UnsetPath};
 use super::FileOperation;
 
+use crate::caps::Capability;
 use crate::serialization::{Context, Result};
 use crate::utils::OutputIdx;
 
@@ -32,7 +33,7 @@ type OpWithDestination<'a> =
 impl OpWithoutSource {
     pub(crate) fn new() -> OpWithoutSource {
         let mut caps = HashMap::<String, bool>::new();
-        caps.insert("file.base".into(), true);
+        caps.insert(Capability::FileBase.into(), true);
 
         CopyOperation {
             source: UnsetPath,
@@ -48,7 +49,7 @@ impl OpWithoutSource {
         }
     }
 
-    pub fn from<P>(self, source: LayerPath<'_, P>) -> OpWithSource
+    pub fn from<P>(self, source: LayerPath<'_, P>) -> OpWithSource<'_>
     where
         P: AsRef<Path>,
     {
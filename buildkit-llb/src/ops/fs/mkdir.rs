@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use buildkit_proto::pb;
@@ -15,9 +14,6 @@ pub struct MakeDirOperation<'a> {
     output: OutputIdx,
 
     make_parents: bool,
-
-    description: HashMap<String, String>,
-    caps: HashMap<String, bool>,
 }
 
 impl<'a> MakeDirOperation<'a> {
@@ -25,17 +21,11 @@ impl<'a> MakeDirOperation<'a> {
     where
         P: AsRef<Path>,
     {
-        let mut caps = HashMap::<String, bool>::new();
-        caps.insert("file.base".into(), true);
-
         MakeDirOperation {
             path: path.into_owned(),
             output,
 
             make_parents: false,
-
-            caps,
-            description: Default::default(),
         }
     }
 
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use buildkit_proto::pb;
@@ -15,9 +14,6 @@ pub struct MakeFileOperation<'a> {
     output: OutputIdx,
 
     data: Option<Vec<u8>>,
-
-    description: HashMap<String, String>,
-    caps: HashMap<String, bool>,
 }
 
 impl<'a> MakeFileOperation<'a> {
@@ -25,17 +21,11 @@ impl<'a> MakeFileOperation<'a> {
     where
         P: AsRef<Path>,
     {
-        let mut caps = HashMap::<String, bool>::new();
-        caps.insert("file.base".into(), true);
-
         MakeFileOperation {
             path: path.into_owned(),
             output,
 
             data: None,
-
-            caps,
-            description: Default::default(),
         }
     }
 
@@ -16,7 +16,7 @@ pub use self::copy::CopyOperation;
 pub use self::mkdir::MakeDirOperation;
 pub use self::mkfile::MakeFileOperation;
 pub use self::path::{LayerPath, UnsetPath};
-pub use self::sequence::SequenceOperation;
+pub use self::sequence::{FileOutput, SequenceOperation};
 
 /// Umbrella operation that handles file system related routines.
 /// Dockerfile's `COPY` directive is a partial case of this.
@@ -165,6 +165,96 @@ fn copy_serialization() {
     );
 }
 
+#[test]
+fn push_serialization() {
+    use crate::prelude::*;
+
+    let context = Source::local("context");
+    let builder_image = Source::image("rustlang/rust:nightly");
+
+    let (operation, manifest) = FileSystem::sequence().push(|output| {
+        FileSystem::copy()
+            .from(LayerPath::Other(context.output(), "Cargo.toml"))
+            .to(output, LayerPath::Scratch("Cargo.toml"))
+    });
+
+    let (operation, shell) = operation.push(|output| {
+        FileSystem::copy()
+            .from(LayerPath::Other(builder_image.output(), "/bin/sh"))
+            .to(output, LayerPath::Own(manifest.into(), "/bin/sh"))
+    });
+
+    let (operation, last) = operation.push(|output| {
+        FileSystem::copy()
+            .from(LayerPath::Own(shell.into(), "Cargo.toml"))
+            .to(output, LayerPath::Scratch("Cargo.toml"))
+    });
+
+    assert_eq!(last.index().0, 2);
+    assert_eq!(operation.last_output_index(), Some(2));
+
+    crate::check_op!(
+        operation,
+        |digest| { "sha256:c4f7fb723fa87f03788aaf660dc9110ad8748fc9971e13713f103b632c05ae96" },
+        |description| { vec![] },
+        |caps| { vec!["file.base"] },
+    );
+}
+
+#[test]
+fn mixed_push_serialization() {
+    use crate::prelude::*;
+
+    let context = Source::local("context");
+
+    let (operation, manifest) = {
+        FileSystem::sequence()
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(context.output(), "Cargo.toml"))
+                    .to(OutputIdx(3), LayerPath::Scratch("Cargo.toml")),
+            )
+            .push(|output| {
+                FileSystem::copy()
+                    .from(LayerPath::Other(context.output(), "Cargo.lock"))
+                    .to(output, LayerPath::Scratch("Cargo.lock"))
+            })
+    };
+
+    let (operation, last) = operation.push(|output| {
+        FileSystem::copy()
+            .from(LayerPath::Own(manifest.into(), "Cargo.lock"))
+            .to(output, LayerPath::Scratch("Cargo.lock"))
+    });
+
+    assert_eq!(manifest.index().0, 4);
+    assert_eq!(last.index().0, 5);
+
+    let manual = {
+        FileSystem::sequence()
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(context.output(), "Cargo.toml"))
+                    .to(OutputIdx(3), LayerPath::Scratch("Cargo.toml")),
+            )
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Other(context.output(), "Cargo.lock"))
+                    .to(OutputIdx(4), LayerPath::Scratch("Cargo.lock")),
+            )
+            .append(
+                FileSystem::copy()
+                    .from(LayerPath::Own(OwnOutputIdx(1), "Cargo.lock"))
+                    .to(OutputIdx(5), LayerPath::Scratch("Cargo.lock")),
+            )
+    };
+
+    assert_eq!(
+        Terminal::with(operation.output(5)).into_definition().def,
+        Terminal::with(manual.output(5)).into_definition().def,
+    );
+}
+
 #[test]
 fn copy_with_params_serialization() {
     use crate::prelude::*;
@@ -5,32 +5,45 @@ use buildkit_proto::pb::{self, op::Op};
 
 use super::FileOperation;
 
+use crate::caps::Capability;
 use crate::ops::*;
 use crate::serialization::{Context, Node, Operation, OperationId, Result};
-use crate::utils::{OperationOutput, OutputIdx};
+use crate::utils::{OperationOutput, OutputIdx, OwnOutputIdx, Platform};
+
+/// Output of a `SequenceOperation` assigned by `SequenceOperation::push`.
+/// Can be used as `OwnOutputIdx` by the following actions of the same sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileOutput {
+    output: u32,
+    action: u32,
+}
 
 #[derive(Debug)]
 pub struct SequenceOperation<'a> {
     id: OperationId,
     inner: Vec<Box<dyn FileOperation + 'a>>,
+    platform: Option<Platform>,
 
     description: HashMap<String, String>,
     caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
 }
 
 impl<'a> SequenceOperation<'a> {
     pub(crate) fn new() -> Self {
         let mut caps = HashMap::<String, bool>::new();
-        caps.insert("file.base".into(), true);
+        caps.insert(Capability::FileBase.into(), true);
 
         Self {
             id: OperationId::default(),
             inner: vec![],
+            platform: None,
 
             caps,
             description: Default::default(),
             ignore_cache: false,
+            export_cache: None,
         }
     }
 
@@ -44,18 +57,63 @@ impl<'a> SequenceOperation<'a> {
         self
     }
 
+    /// Sets the platform the actions should be executed on.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self.caps.insert(Capability::Platform.into(), true);
+        self
+    }
+
+    /// Append an action with automatically assigned output index.
+    /// The `action` closure receives the index, and the returned output refers to the action result.
+    pub fn push<F, T>(mut self, action: F) -> (Self, FileOutput)
+    where
+        F: FnOnce(OutputIdx) -> T,
+        T: FileOperation + 'a,
+    {
+        let output = OutputIdx(self.last_output_index().map_or(0, |last| last + 1));
+        let position = self.inner.len() as u32;
+
+        self.inner.push(Box::new(action(output)));
+
+        let output = FileOutput {
+            output: output.0,
+            action: position,
+        };
+
+        (self, output)
+    }
+
     pub fn last_output_index(&self) -> Option<u32> {
         // TODO: make sure the `inner` elements have monotonic indexes
 
         self.inner
             .iter()
-            .filter(|fs| fs.output() >= 0)
-            .last()
+            .rfind(|fs| fs.output() >= 0)
             .map(|fs| fs.output() as u32)
     }
 }
 
-impl<'a, 'b: 'a> MultiBorrowedOutput<'b> for SequenceOperation<'b> {
+impl FileOutput {
+    pub fn index(self) -> OutputIdx {
+        OutputIdx(self.output)
+    }
+}
+
+impl From<FileOutput> for OutputIdx {
+    fn from(output: FileOutput) -> Self {
+        output.index()
+    }
+}
+
+/// Own outputs are referenced by the position of the action in the sequence.
+impl From<FileOutput> for OwnOutputIdx {
+    fn from(output: FileOutput) -> Self {
+        OwnOutputIdx(output.action)
+    }
+}
+
+impl<'b> MultiBorrowedOutput<'b> for SequenceOperation<'b> {
     fn output(&'b self, index: u32) -> OperationOutput<'b> {
         // TODO: check if the requested index available.
         OperationOutput::borrowed(self, OutputIdx(index))
@@ -69,7 +127,7 @@ impl<'a> MultiOwnedOutput<'a> for Arc<SequenceOperation<'a>> {
     }
 }
 
-impl<'a, 'b: 'a> MultiBorrowedLastOutput<'b> for SequenceOperation<'b> {
+impl<'b> MultiBorrowedLastOutput<'b> for SequenceOperation<'b> {
     fn last_output(&'b self) -> Option<OperationOutput<'b>> {
         self.last_output_index().map(|index| self.output(index))
     }
@@ -82,13 +140,13 @@ impl<'a> MultiOwnedLastOutput<'a> for Arc<SequenceOperation<'a>> {
 }
 
 impl<'a> OperationBuilder<'a> for SequenceOperation<'a> {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -96,6 +154,12 @@ impl<'a> OperationBuilder<'a> for SequenceOperation<'a> {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl<'a> Operation for SequenceOperation<'a> {
@@ -116,13 +180,14 @@ impl<'a> Operation for SequenceOperation<'a> {
 
         let mut actions = vec![];
 
-        for (item, offset) in self.inner.iter().zip(input_offsets.into_iter()) {
+        for (item, offset) in self.inner.iter().zip(input_offsets) {
             actions.push(item.serialize_action(inputs.len(), offset)?);
         }
 
         let head = pb::Op {
             inputs,
             op: Some(Op::File(pb::FileOp { actions })),
+            platform: self.platform.clone().map(Into::into),
 
             ..Default::default()
         };
@@ -131,8 +196,7 @@ impl<'a> Operation for SequenceOperation<'a> {
             description: self.description.clone(),
             caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| pb::ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
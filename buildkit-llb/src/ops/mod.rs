@@ -41,11 +41,25 @@ pub trait OperationBuilder<'a> {
     /// Sets an operation display name.
     fn custom_name<S>(self, name: S) -> Self
     where
-        S: Into<String>;
+        S: Into<String>,
+        Self: Sized,
+    {
+        self.description("llb.customname", name)
+    }
+
+    /// Sets an arbitrary description entry. BuildKit doesn't parse them, except `llb.customname`.
+    fn description<K, V>(self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>;
 
     /// Sets caching behavior.
     fn ignore_cache(self, ignore: bool) -> Self;
 
+    /// Sets whether the operation result should be included into exported cache.
+    /// When not specified, the exporter decides on its own (depends on the cache export mode).
+    fn export_cache(self, export: bool) -> Self;
+
     /// Convert the operation into `Arc` so it can be shared when efficient borrowing is not possible.
     fn ref_counted(self) -> Arc<Self>
     where
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use buildkit_proto::pb::{self, op::Op, OpMetadata, SourceOp};
+use buildkit_proto::pb::{self, op::Op, ExportCache, OpMetadata, SourceOp};
 
+use crate::caps::Capability;
 use crate::ops::{OperationBuilder, SingleBorrowedOutput, SingleOwnedOutput};
 use crate::serialization::{Context, Node, Operation, OperationId, Result};
 use crate::utils::{OperationOutput, OutputIdx};
@@ -13,7 +14,9 @@ pub struct GitSource {
     remote: String,
     reference: Option<String>,
     description: HashMap<String, String>,
+    caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
 }
 
 impl GitSource {
@@ -34,12 +37,17 @@ impl GitSource {
             raw_url
         };
 
+        let mut caps = HashMap::<String, bool>::new();
+        caps.insert(Capability::SourceGit.into(), true);
+
         Self {
             id: OperationId::default(),
             remote,
             reference: None,
             description: Default::default(),
+            caps,
             ignore_cache: false,
+            export_cache: None,
         }
     }
 }
@@ -60,20 +68,20 @@ impl<'a> SingleBorrowedOutput<'a> for GitSource {
     }
 }
 
-impl<'a> SingleOwnedOutput<'static> for Arc<GitSource> {
+impl SingleOwnedOutput<'static> for Arc<GitSource> {
     fn output(&self) -> OperationOutput<'static> {
         OperationOutput::owned(self.clone(), OutputIdx(0))
     }
 }
 
 impl OperationBuilder<'static> for GitSource {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -81,6 +89,12 @@ impl OperationBuilder<'static> for GitSource {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl Operation for GitSource {
@@ -106,9 +120,9 @@ impl Operation for GitSource {
 
         let metadata = OpMetadata {
             description: self.description.clone(),
+            caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
@@ -121,7 +135,7 @@ fn serialization() {
         GitSource::new("any.url"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -136,7 +150,7 @@ fn serialization() {
         GitSource::new("any.url").custom_name("git custom name"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![("llb.customname", "git custom name")] },
-        |caps| { vec![] },
+        |caps| { vec!["meta.description", "source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -154,7 +168,7 @@ fn prefixes() {
         GitSource::new("http://any.url"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -169,7 +183,7 @@ fn prefixes() {
         GitSource::new("https://any.url"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -184,7 +198,7 @@ fn prefixes() {
         GitSource::new("git://any.url"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -199,7 +213,7 @@ fn prefixes() {
         GitSource::new("git@any.url"),
         |digest| { "sha256:ecde982e19ace932e5474e57b0ca71ba690ed7d28abff2a033e8f969e22bf2d8" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -217,7 +231,7 @@ fn with_reference() {
         GitSource::new("any.url").with_reference("abcdef"),
         |digest| { "sha256:f59aa7f8db62e0b5c2a1da396752ba8a2bb0b5d28ddcfdd1d4f822d26ebfe3cf" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.git"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use buildkit_proto::pb::{self, op::Op, OpMetadata, SourceOp};
+use buildkit_proto::pb::{self, op::Op, ExportCache, OpMetadata, SourceOp};
 
+use crate::caps::Capability;
 use crate::ops::{OperationBuilder, SingleBorrowedOutput, SingleOwnedOutput};
 use crate::serialization::{Context, Node, Operation, OperationId, Result};
 use crate::utils::{OperationOutput, OutputIdx};
@@ -13,7 +14,9 @@ pub struct HttpSource {
     url: String,
     file_name: Option<String>,
     description: HashMap<String, String>,
+    caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
 }
 
 impl HttpSource {
@@ -21,12 +24,17 @@ impl HttpSource {
     where
         S: Into<String>,
     {
+        let mut caps = HashMap::<String, bool>::new();
+        caps.insert(Capability::SourceHttp.into(), true);
+
         Self {
             id: OperationId::default(),
             url: url.into(),
             file_name: None,
             description: Default::default(),
+            caps,
             ignore_cache: false,
+            export_cache: None,
         }
     }
 }
@@ -47,20 +55,20 @@ impl<'a> SingleBorrowedOutput<'a> for HttpSource {
     }
 }
 
-impl<'a> SingleOwnedOutput<'static> for Arc<HttpSource> {
+impl SingleOwnedOutput<'static> for Arc<HttpSource> {
     fn output(&self) -> OperationOutput<'static> {
         OperationOutput::owned(self.clone(), OutputIdx(0))
     }
 }
 
 impl OperationBuilder<'static> for HttpSource {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -68,6 +76,12 @@ impl OperationBuilder<'static> for HttpSource {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl Operation for HttpSource {
@@ -93,9 +107,9 @@ impl Operation for HttpSource {
 
         let metadata = OpMetadata {
             description: self.description.clone(),
+            caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
@@ -108,7 +122,7 @@ fn serialization() {
         HttpSource::new("http://any.url/with/path"),
         |digest| { "sha256:22ec64461f39dd3b54680fc240b459248b1ced597f113b5d692abe9695860d12" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.http"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -123,7 +137,7 @@ fn serialization() {
         HttpSource::new("http://any.url/with/path").custom_name("git custom name"),
         |digest| { "sha256:22ec64461f39dd3b54680fc240b459248b1ced597f113b5d692abe9695860d12" },
         |description| { vec![("llb.customname", "git custom name")] },
-        |caps| { vec![] },
+        |caps| { vec!["meta.description", "source.http"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -138,7 +152,7 @@ fn serialization() {
         HttpSource::new("http://any.url/with/path").with_file_name("file.name"),
         |digest| { "sha256:e1fe6584287dfa2b065ed29fcf4f77bcf86fb54781832d2f45074fa1671df692" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.http"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
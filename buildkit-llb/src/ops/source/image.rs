@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use buildkit_proto::pb::{self, op::Op, OpMetadata, SourceOp};
+use buildkit_proto::pb::{self, op::Op, ExportCache, OpMetadata, SourceOp};
 use lazy_static::*;
 use regex::Regex;
 
+use crate::caps::Capability;
 use crate::ops::{OperationBuilder, SingleBorrowedOutput, SingleOwnedOutput};
 use crate::serialization::{Context, Node, Operation, OperationId, Result};
-use crate::utils::{OperationOutput, OutputIdx};
+use crate::utils::{OperationOutput, OutputIdx, Platform};
 
 #[derive(Debug)]
 pub struct ImageSource {
@@ -20,12 +22,16 @@ pub struct ImageSource {
     digest: Option<String>,
 
     description: HashMap<String, String>,
+    caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
     resolve_mode: Option<ResolveMode>,
+    platform: Option<Platform>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ResolveMode {
+    #[default]
     Default,
     ForcePull,
     PreferLocal,
@@ -41,12 +47,36 @@ impl fmt::Display for ResolveMode {
     }
 }
 
-impl Default for ResolveMode {
-    fn default() -> Self {
-        ResolveMode::Default
+impl FromStr for ResolveMode {
+    type Err = ParseResolveModeError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "default" => Ok(ResolveMode::Default),
+            "pull" => Ok(ResolveMode::ForcePull),
+            "local" => Ok(ResolveMode::PreferLocal),
+
+            other => Err(ParseResolveModeError(other.into())),
+        }
+    }
+}
+
+/// Error returned when a resolve mode string is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseResolveModeError(String);
+
+impl fmt::Display for ParseResolveModeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid resolve mode '{}', expected 'default', 'pull' or 'local'",
+            self.0
+        )
     }
 }
 
+impl std::error::Error for ParseResolveModeError {}
+
 lazy_static! {
     static ref TAG_EXPR: Regex = Regex::new(r":[\w][\w.-]+$").unwrap();
 }
@@ -108,6 +138,9 @@ impl ImageSource {
             name = format!("library/{}", name);
         }
 
+        let mut caps = HashMap::<String, bool>::new();
+        caps.insert(Capability::SourceImage.into(), true);
+
         Self {
             id: OperationId::default(),
 
@@ -117,13 +150,18 @@ impl ImageSource {
             digest,
 
             description: Default::default(),
+            caps,
             ignore_cache: false,
+            export_cache: None,
             resolve_mode: None,
+            platform: None,
         }
     }
 
     pub fn with_resolve_mode(mut self, mode: ResolveMode) -> Self {
         self.resolve_mode = Some(mode);
+        self.caps
+            .insert(Capability::SourceImageResolveMode.into(), true);
         self
     }
 
@@ -131,6 +169,17 @@ impl ImageSource {
         self.resolve_mode
     }
 
+    /// Sets the platform to pick from a multi-platform image.
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self.caps.insert(Capability::Platform.into(), true);
+        self
+    }
+
+    pub fn platform(&self) -> Option<&Platform> {
+        self.platform.as_ref()
+    }
+
     pub fn with_digest<S>(mut self, digest: S) -> Self
     where
         S: Into<String>,
@@ -171,20 +220,20 @@ impl<'a> SingleBorrowedOutput<'a> for ImageSource {
     }
 }
 
-impl<'a> SingleOwnedOutput<'static> for Arc<ImageSource> {
+impl SingleOwnedOutput<'static> for Arc<ImageSource> {
     fn output(&self) -> OperationOutput<'static> {
         OperationOutput::owned(self.clone(), OutputIdx(0))
     }
 }
 
 impl OperationBuilder<'static> for ImageSource {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -192,6 +241,12 @@ impl OperationBuilder<'static> for ImageSource {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl Operation for ImageSource {
@@ -212,14 +267,16 @@ impl Operation for ImageSource {
                 attrs,
             })),
 
+            platform: self.platform.clone().map(Into::into),
+
             ..Default::default()
         };
 
         let metadata = OpMetadata {
             description: self.description.clone(),
+            caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
@@ -232,7 +289,7 @@ fn serialization() {
         ImageSource::new("rustlang/rust:nightly"),
         |digest| { "sha256:dee2a3d7dd482dd8098ba543ff1dcb01efd29fcd16fdb0979ef556f38564543a" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -247,7 +304,7 @@ fn serialization() {
         ImageSource::new("library/alpine:latest"),
         |digest| { "sha256:0e6b31ceed3e6dc542018f35a53a0e857e6a188453d32a2a5bbe7aa2971c1220" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -262,7 +319,7 @@ fn serialization() {
         ImageSource::new("rustlang/rust:nightly").custom_name("image custom name"),
         |digest| { "sha256:dee2a3d7dd482dd8098ba543ff1dcb01efd29fcd16fdb0979ef556f38564543a" },
         |description| { vec![("llb.customname", "image custom name")] },
-        |caps| { vec![] },
+        |caps| { vec!["meta.description", "source.image"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -273,11 +330,18 @@ fn serialization() {
         },
     );
 
+    crate::check_op!(
+        ImageSource::new("rustlang/rust:nightly").export_cache(false),
+        |digest| { "sha256:dee2a3d7dd482dd8098ba543ff1dcb01efd29fcd16fdb0979ef556f38564543a" },
+        |caps| { vec!["meta.exportcache", "source.image"] },
+        |export_cache| { Some(false) },
+    );
+
     crate::check_op!(
         ImageSource::new("rustlang/rust:nightly").with_digest("sha256:123456"),
         |digest| { "sha256:a9837e26998d165e7b6433f8d40b36d259905295860fcbbc62bbce75a6c991c6" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -295,7 +359,7 @@ fn resolve_mode() {
         ImageSource::new("rustlang/rust:nightly").with_resolve_mode(ResolveMode::Default),
         |digest| { "sha256:792e246751e84b9a5e40c28900d70771a07e8cc920c1039cdddfc6bf69256dfe" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image", "source.image.resolvemode"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -310,7 +374,7 @@ fn resolve_mode() {
         ImageSource::new("rustlang/rust:nightly").with_resolve_mode(ResolveMode::ForcePull),
         |digest| { "sha256:0bd920010eab701bdce44c61d220e6943d56d3fb9a9fa4e773fc060c0d746122" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image", "source.image.resolvemode"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -325,7 +389,7 @@ fn resolve_mode() {
         ImageSource::new("rustlang/rust:nightly").with_resolve_mode(ResolveMode::PreferLocal),
         |digest| { "sha256:bd6797c8644d2663b29c36a8b3b63931e539be44ede5e56aca2da4f35f241f18" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.image", "source.image.resolvemode"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -337,6 +401,19 @@ fn resolve_mode() {
     );
 }
 
+#[test]
+fn resolve_mode_parsing() {
+    for mode in &[
+        ResolveMode::Default,
+        ResolveMode::ForcePull,
+        ResolveMode::PreferLocal,
+    ] {
+        assert_eq!(mode.to_string().parse::<ResolveMode>(), Ok(*mode));
+    }
+
+    assert!("always".parse::<ResolveMode>().is_err());
+}
+
 #[test]
 fn image_name() {
     crate::check_op!(ImageSource::new("rustlang/rust"), |op| {
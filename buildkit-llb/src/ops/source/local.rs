@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use buildkit_proto::pb::{self, op::Op, OpMetadata, SourceOp};
+use buildkit_proto::pb::{self, op::Op, ExportCache, OpMetadata, SourceOp};
 
+use crate::caps::Capability;
 use crate::ops::{OperationBuilder, SingleBorrowedOutput, SingleOwnedOutput};
 use crate::serialization::{Context, Node, Operation, OperationId, Result};
 use crate::utils::{OperationOutput, OutputIdx};
@@ -12,7 +13,9 @@ pub struct LocalSource {
     id: OperationId,
     name: String,
     description: HashMap<String, String>,
+    caps: HashMap<String, bool>,
     ignore_cache: bool,
+    export_cache: Option<bool>,
 
     exclude: Vec<String>,
     include: Vec<String>,
@@ -23,10 +26,15 @@ impl LocalSource {
     where
         S: Into<String>,
     {
+        let mut caps = HashMap::<String, bool>::new();
+        caps.insert(Capability::SourceLocal.into(), true);
+
         Self {
             id: OperationId::default(),
             name: name.into(),
+            caps,
             ignore_cache: false,
+            export_cache: None,
 
             ..Default::default()
         }
@@ -36,8 +44,9 @@ impl LocalSource {
     where
         S: Into<String>,
     {
-        // TODO: add `source.local.includepatterns` capability
         self.include.push(include.into());
+        self.caps
+            .insert(Capability::SourceLocalIncludePatterns.into(), true);
         self
     }
 
@@ -45,8 +54,9 @@ impl LocalSource {
     where
         S: Into<String>,
     {
-        // TODO: add `source.local.excludepatterns` capability
         self.exclude.push(exclude.into());
+        self.caps
+            .insert(Capability::SourceLocalExcludePatterns.into(), true);
         self
     }
 }
@@ -57,20 +67,20 @@ impl<'a> SingleBorrowedOutput<'a> for LocalSource {
     }
 }
 
-impl<'a> SingleOwnedOutput<'static> for Arc<LocalSource> {
+impl SingleOwnedOutput<'static> for Arc<LocalSource> {
     fn output(&self) -> OperationOutput<'static> {
         OperationOutput::owned(self.clone(), OutputIdx(0))
     }
 }
 
 impl OperationBuilder<'static> for LocalSource {
-    fn custom_name<S>(mut self, name: S) -> Self
+    fn description<K, V>(mut self, key: K, value: V) -> Self
     where
-        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        self.description
-            .insert("llb.customname".into(), name.into());
-
+        self.description.insert(key.into(), value.into());
+        self.caps.insert(Capability::MetaDescription.into(), true);
         self
     }
 
@@ -78,6 +88,12 @@ impl OperationBuilder<'static> for LocalSource {
         self.ignore_cache = ignore;
         self
     }
+
+    fn export_cache(mut self, export: bool) -> Self {
+        self.export_cache = Some(export);
+        self.caps.insert(Capability::MetaExportCache.into(), true);
+        self
+    }
 }
 
 impl Operation for LocalSource {
@@ -113,9 +129,9 @@ impl Operation for LocalSource {
 
         let metadata = OpMetadata {
             description: self.description.clone(),
+            caps: self.caps.clone(),
             ignore_cache: self.ignore_cache,
-
-            ..Default::default()
+            export_cache: self.export_cache.map(|value| ExportCache { value }),
         };
 
         Ok(Node::new(head, metadata))
@@ -128,7 +144,7 @@ fn serialization() {
         LocalSource::new("context"),
         |digest| { "sha256:a60212791641cbeaa3a49de4f7dff9e40ae50ec19d1be9607232037c1db16702" },
         |description| { vec![] },
-        |caps| { vec![] },
+        |caps| { vec!["source.local"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -143,7 +159,7 @@ fn serialization() {
         LocalSource::new("context").custom_name("context custom name"),
         |digest| { "sha256:a60212791641cbeaa3a49de4f7dff9e40ae50ec19d1be9607232037c1db16702" },
         |description| { vec![("llb.customname", "context custom name")] },
-        |caps| { vec![] },
+        |caps| { vec!["meta.description", "source.local"] },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -163,7 +179,13 @@ fn serialization() {
         },
         |digest| { "sha256:f6962b8bb1659c63a2c2c3e2a7ccf0326c87530dd70c514343f127e4c20460c4" },
         |description| { vec![("llb.customname", "context custom name")] },
-        |caps| { vec![] },
+        |caps| {
+            vec![
+                "meta.description",
+                "source.local",
+                "source.local.excludepatterns",
+            ]
+        },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -186,7 +208,13 @@ fn serialization() {
         },
         |digest| { "sha256:a7e628333262b810572f83193bbf8554e688abfb51d44ac30bdad7fa425f3839" },
         |description| { vec![("llb.customname", "context custom name")] },
-        |caps| { vec![] },
+        |caps| {
+            vec![
+                "meta.description",
+                "source.local",
+                "source.local.includepatterns",
+            ]
+        },
         |cached_tail| { vec![] },
         |inputs| { vec![] },
         |op| {
@@ -5,7 +5,7 @@ mod local;
 
 pub use self::git::GitSource;
 pub use self::http::HttpSource;
-pub use self::image::{ImageSource, ResolveMode};
+pub use self::image::{ImageSource, ParseResolveModeError, ResolveMode};
 pub use self::local::LocalSource;
 
 /// Provide an input for other operations. For example: `FROM` directive in Dockerfile.
@@ -4,7 +4,10 @@ use std::iter::once;
 use buildkit_proto::pb::{self, Input};
 use prost::Message;
 
+use crate::caps::{Capability, CapabilitySet};
 use crate::serialization::{Context, Node, Result};
+
+pub use crate::serialization::SerializationError;
 use crate::utils::OperationOutput;
 
 /// Final operation in the graph. Responsible for printing the complete LLB definition.
@@ -32,6 +35,22 @@ impl<'a> Terminal<'a> {
         pb::Definition { def, metadata }
     }
 
+    /// Collect the capabilities required by every operation in the graph.
+    /// Fails if an operation requires a capability that `Capability` doesn't describe.
+    pub fn required_caps(&self) -> std::result::Result<CapabilitySet, SerializationError> {
+        let mut cx = Context::default();
+        self.serialize(&mut cx)?;
+
+        cx.into_registered_nodes()
+            .flat_map(|node| node.metadata.caps.into_iter())
+            .filter(|(_, required)| *required)
+            .map(|(id, _)| {
+                Capability::from_id(&id)
+                    .ok_or_else(|| SerializationError::new(format!("unknown capability `{}`", id)))
+            })
+            .collect()
+    }
+
     pub fn write_definition(self, mut writer: impl Write) -> io::Result<()> {
         let mut bytes = Vec::new();
         self.into_definition().encode(&mut bytes).unwrap();
@@ -62,13 +81,13 @@ fn serialization() {
     let final_image = Source::image("library/alpine:latest");
 
     let first_command = Command::run("rustc")
-        .args(&["--crate-name", "crate-1"])
+        .args(["--crate-name", "crate-1"])
         .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
         .mount(Mount::ReadOnlyLayer(context.output(), "/context"))
         .mount(Mount::Scratch(OutputIdx(0), "/target"));
 
     let second_command = Command::run("rustc")
-        .args(&["--crate-name", "crate-2"])
+        .args(["--crate-name", "crate-2"])
         .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
         .mount(Mount::ReadOnlyLayer(context.output(), "/context"))
         .mount(Mount::Scratch(OutputIdx(0), "/target"));
@@ -104,7 +123,7 @@ fn serialization() {
         definition
             .def
             .iter()
-            .map(|bytes| Node::get_digest(&bytes))
+            .map(|bytes| Node::get_digest(bytes))
             .collect::<Vec<_>>(),
         crate::utils::test::to_vec(vec![
             "sha256:a60212791641cbeaa3a49de4f7dff9e40ae50ec19d1be9607232037c1db16702",
@@ -120,8 +139,8 @@ fn serialization() {
     let mut metadata_digests = {
         definition
             .metadata
-            .iter()
-            .map(|(digest, _)| digest.as_str())
+            .keys()
+            .map(|digest| digest.as_str())
             .collect::<Vec<_>>()
     };
 
@@ -139,3 +158,63 @@ fn serialization() {
         ]
     );
 }
+
+#[test]
+fn required_caps() {
+    use crate::prelude::*;
+
+    let context = Source::local("context");
+    let builder_image = Source::image("rustlang/rust:nightly");
+
+    let command = Command::run("cargo")
+        .args(["build"])
+        .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
+        .mount(Mount::ReadOnlySelector(context.output(), "/src", "/crate"))
+        .mount(Mount::SharedCache("/root/.cargo"))
+        .mount(Mount::Scratch(OutputIdx(0), "/target"));
+
+    let assembly_op = FileSystem::sequence().append(
+        FileSystem::copy()
+            .from(LayerPath::Other(command.output(0), "/target/release/crate"))
+            .to(OutputIdx(0), LayerPath::Scratch("/crate")),
+    );
+
+    assert_eq!(
+        Terminal::with(assembly_op.output(0))
+            .required_caps()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![
+            Capability::SourceImage,
+            Capability::SourceLocal,
+            Capability::ExecMountBind,
+            Capability::ExecMountCache,
+            Capability::ExecMountCacheSharing,
+            Capability::ExecMountSelector,
+            Capability::FileBase,
+        ]
+    );
+
+    assert_eq!(
+        Terminal::with(context.output())
+            .required_caps()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![Capability::SourceLocal]
+    );
+
+    let git = Source::git("https://github.com/moby/buildkit.git");
+    let http = Source::http("https://example.com/archive.tar.gz");
+
+    assert!(Terminal::with(git.output())
+        .required_caps()
+        .unwrap()
+        .contains(Capability::SourceGit));
+
+    assert!(Terminal::with(http.output())
+        .required_caps()
+        .unwrap()
+        .contains(Capability::SourceHttp));
+}
@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
 mod id;
 mod operation;
@@ -8,7 +9,34 @@ pub(crate) use self::id::OperationId;
 pub(crate) use self::operation::Operation;
 pub(crate) use self::output::Node;
 
-pub(crate) type Result<T> = std::result::Result<T, ()>;
+pub(crate) type Result<T> = std::result::Result<T, SerializationError>;
+
+/// Error returned when the graph can't be serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerializationError {
+    reason: String,
+}
+
+impl SerializationError {
+    pub(crate) fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+
+    /// Explanation of what went wrong.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to serialize the graph: {}", self.reason)
+    }
+}
+
+impl std::error::Error for SerializationError {}
 
 #[derive(Default)]
 pub struct Context {
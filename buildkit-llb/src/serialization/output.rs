@@ -23,7 +23,7 @@ impl Node {
 
     pub fn get_digest(bytes: &[u8]) -> String {
         let mut hasher = Sha256::new();
-        hasher.input(&bytes);
+        hasher.input(bytes);
 
         format!("sha256:{:x}", hasher.result())
     }
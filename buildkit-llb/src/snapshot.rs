@@ -0,0 +1,576 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use buildkit_proto::pb::{self, file_action::Action, op::Op};
+
+use crate::inspect::{DefinitionGraph, InvalidDefinition, Vertex};
+
+/// Set the variable to `1` to overwrite snapshots with the actual graphs instead of comparing them.
+pub const BLESS_ENV: &str = "BUILDKIT_LLB_BLESS";
+
+/// Render the definition as readable text that doesn't change unless the graph does.
+///
+/// Vertices are numbered from the terminal (`#0`) upwards and inputs refer to them as `#N[output]`,
+/// so the text stays meaningful when digests change.
+pub fn render(definition: &pb::Definition) -> Result<String, InvalidDefinition> {
+    let graph = DefinitionGraph::decode(definition)?;
+    let mut text = String::new();
+
+    for (position, vertex) in graph.vertices().iter().enumerate() {
+        if position > 0 {
+            text.push('\n');
+        }
+
+        render_vertex(&mut text, &graph, position, vertex);
+    }
+
+    Ok(text)
+}
+
+/// Compare the definition with the snapshot file and panic with a diff if they don't match.
+///
+/// When `BUILDKIT_LLB_BLESS=1` is set, the snapshot is (re)written instead.
+/// Relative paths are resolved from the current directory, which is the crate root for `cargo test`.
+pub fn assert_snapshot<P>(definition: &pb::Definition, path: P)
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let actual = render(definition).expect("Unable to render the definition");
+
+    if env::var(BLESS_ENV)
+        .map(|value| value == "1")
+        .unwrap_or(false)
+    {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Unable to create the snapshot directory");
+        }
+
+        fs::write(path, &actual).expect("Unable to write the snapshot");
+        return;
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(error) => panic!(
+            "Unable to read the snapshot '{}': {}. Run with {}=1 to create it.",
+            path.display(),
+            error,
+            BLESS_ENV
+        ),
+    };
+
+    if expected != actual {
+        panic!(
+            "Definition doesn't match the snapshot '{}' (run with {}=1 to update it):\n{}",
+            path.display(),
+            BLESS_ENV,
+            diff(&expected, &actual)
+        );
+    }
+}
+
+/// Line diff of two rendered definitions. Unchanged lines are only kept around the changes
+/// and as vertex headers, so it's clear which vertex differs.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // Longest common subsequence table, suffix-based.
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(('-', expected[i]));
+            i += 1;
+        } else {
+            lines.push(('+', actual[j]));
+            j += 1;
+        }
+    }
+
+    let is_changed = |index: usize| lines.get(index).map(|line| line.0 != ' ').unwrap_or(false);
+    let mut output = String::new();
+    let mut skipped = false;
+
+    for (index, (marker, line)) in lines.iter().enumerate() {
+        let is_header = line.starts_with('#');
+        let is_context =
+            is_changed(index) || is_changed(index + 1) || (index > 0 && is_changed(index - 1));
+
+        if is_header || is_context {
+            if skipped {
+                output.push_str("  ...\n");
+                skipped = false;
+            }
+
+            let line = format!("{} {}", marker, line);
+            writeln!(output, "{}", line.trim_end()).unwrap();
+        } else {
+            skipped = true;
+        }
+    }
+
+    output
+}
+
+fn render_vertex(text: &mut String, graph: &DefinitionGraph, position: usize, vertex: &Vertex) {
+    match vertex.custom_name() {
+        Some(name) => writeln!(text, "#{} {} {:?}", position, vertex.kind(), name).unwrap(),
+        None => writeln!(text, "#{} {}", position, vertex.kind()).unwrap(),
+    }
+
+    let inputs = {
+        graph
+            .inputs(vertex)
+            .map(|(position, output)| format!("#{}[{}]", position, output))
+            .collect::<Vec<_>>()
+    };
+
+    if !inputs.is_empty() {
+        writeln!(text, "  inputs: {}", inputs.join(", ")).unwrap();
+    }
+
+    if let Some(ref platform) = vertex.op.platform {
+        writeln!(
+            text,
+            "  platform: {}",
+            crate::utils::Platform::from(platform.clone())
+        )
+        .unwrap();
+    }
+
+    if let Some(ref constraints) = vertex.op.constraints {
+        writeln!(text, "  constraints: {:?}", constraints.filter).unwrap();
+    }
+
+    match vertex.op.op {
+        Some(Op::Exec(ref exec)) => render_exec(text, &inputs, exec),
+        Some(Op::Source(ref source)) => render_source(text, source),
+        Some(Op::File(ref file)) => render_file(text, &inputs, file),
+        Some(Op::Build(ref build)) => render_build(text, &inputs, build),
+        None => {}
+    }
+
+    render_metadata(text, &vertex.metadata);
+}
+
+fn render_exec(text: &mut String, inputs: &[String], exec: &pb::ExecOp) {
+    if let Some(ref meta) = exec.meta {
+        writeln!(text, "  args: {:?}", meta.args).unwrap();
+
+        if !meta.env.is_empty() {
+            writeln!(text, "  env: {:?}", meta.env).unwrap();
+        }
+
+        writeln!(text, "  cwd: {:?}", meta.cwd).unwrap();
+
+        if !meta.user.is_empty() {
+            writeln!(text, "  user: {:?}", meta.user).unwrap();
+        }
+
+        if let Some(ref proxy_env) = meta.proxy_env {
+            let proxies = [
+                ("http", &proxy_env.http_proxy),
+                ("https", &proxy_env.https_proxy),
+                ("ftp", &proxy_env.ftp_proxy),
+                ("no", &proxy_env.no_proxy),
+            ];
+
+            for (name, value) in proxies.iter().filter(|(_, value)| !value.is_empty()) {
+                writeln!(text, "  proxy {}: {:?}", name, value).unwrap();
+            }
+        }
+
+        for host in &meta.extra_hosts {
+            writeln!(text, "  extra host: {} {}", host.host, host.ip).unwrap();
+        }
+    }
+
+    if exec.network != 0 {
+        let network = match pb::NetMode::from_i32(exec.network) {
+            Some(pb::NetMode::Unset) => "unset".into(),
+            Some(pb::NetMode::Host) => "host".into(),
+            Some(pb::NetMode::None) => "none".into(),
+            None => format!("<unknown {}>", exec.network),
+        };
+
+        writeln!(text, "  network: {}", network).unwrap();
+    }
+
+    if exec.security != 0 {
+        let security = match pb::SecurityMode::from_i32(exec.security) {
+            Some(pb::SecurityMode::Sandbox) => "sandbox".into(),
+            Some(pb::SecurityMode::Insecure) => "insecure".into(),
+            None => format!("<unknown {}>", exec.security),
+        };
+
+        writeln!(text, "  security: {}", security).unwrap();
+    }
+
+    for mount in &exec.mounts {
+        write!(
+            text,
+            "  mount {:?}: {}",
+            mount.dest,
+            input_name(inputs, mount.input)
+        )
+        .unwrap();
+
+        if !mount.selector.is_empty() {
+            write!(text, ", selector {:?}", mount.selector).unwrap();
+        }
+
+        if mount.readonly {
+            text.push_str(", readonly");
+        }
+
+        if mount.output >= 0 {
+            write!(text, ", output {}", mount.output).unwrap();
+        }
+
+        if mount.mount_type != 0 {
+            match pb::MountType::from_i32(mount.mount_type) {
+                Some(pb::MountType::Bind) => text.push_str(", bind"),
+                Some(pb::MountType::Secret) => text.push_str(", secret"),
+                Some(pb::MountType::Ssh) => text.push_str(", ssh"),
+                Some(pb::MountType::Cache) => text.push_str(", cache"),
+                Some(pb::MountType::Tmpfs) => text.push_str(", tmpfs"),
+                None => write!(text, ", <unknown type {}>", mount.mount_type).unwrap(),
+            }
+        }
+
+        if let Some(ref cache) = mount.cache_opt {
+            let sharing = match pb::CacheSharingOpt::from_i32(cache.sharing) {
+                Some(pb::CacheSharingOpt::Shared) => "shared".into(),
+                Some(pb::CacheSharingOpt::Private) => "private".into(),
+                Some(pb::CacheSharingOpt::Locked) => "locked".into(),
+                None => format!("<unknown {}>", cache.sharing),
+            };
+
+            write!(text, " id {:?} sharing {}", cache.id, sharing).unwrap();
+        }
+
+        if let Some(ref secret) = mount.secret_opt {
+            write!(
+                text,
+                " id {:?} uid {} gid {} mode {:o}",
+                secret.id, secret.uid, secret.gid, secret.mode
+            )
+            .unwrap();
+
+            if secret.optional {
+                text.push_str(", optional");
+            }
+        }
+
+        if let Some(ref ssh) = mount.ssh_opt {
+            write!(
+                text,
+                " id {:?} uid {} gid {} mode {:o}",
+                ssh.id, ssh.uid, ssh.gid, ssh.mode
+            )
+            .unwrap();
+
+            if ssh.optional {
+                text.push_str(", optional");
+            }
+        }
+
+        text.push('\n');
+    }
+}
+
+fn render_source(text: &mut String, source: &pb::SourceOp) {
+    writeln!(text, "  identifier: {:?}", source.identifier).unwrap();
+
+    for (key, value) in source.attrs.iter().collect::<BTreeMap<_, _>>() {
+        writeln!(text, "  attr {}: {:?}", key, value).unwrap();
+    }
+}
+
+fn render_file(text: &mut String, inputs: &[String], file: &pb::FileOp) {
+    for (index, action) in file.actions.iter().enumerate() {
+        write!(
+            text,
+            "  action {}: {}",
+            index,
+            action_input_name(inputs, action.input)
+        )
+        .unwrap();
+
+        if action.secondary_input >= 0 {
+            write!(
+                text,
+                ", from {}",
+                action_input_name(inputs, action.secondary_input)
+            )
+            .unwrap();
+        }
+
+        if action.output >= 0 {
+            write!(text, ", output {}", action.output).unwrap();
+        }
+
+        match action.action {
+            Some(Action::Copy(ref copy)) => {
+                write!(text, ", copy {:?} to {:?}", copy.src, copy.dest).unwrap();
+                render_mode(text, copy.mode);
+
+                let flags = [
+                    (copy.follow_symlink, "follow symlinks"),
+                    (copy.dir_copy_contents, "contents"),
+                    (copy.attempt_unpack_docker_compatibility, "unpack"),
+                    (copy.create_dest_path, "create path"),
+                    (copy.allow_wildcard, "wildcard"),
+                    (copy.allow_empty_wildcard, "empty wildcard"),
+                ];
+
+                for (_, flag) in flags.iter().filter(|(enabled, _)| *enabled) {
+                    write!(text, ", {}", flag).unwrap();
+                }
+
+                render_owner_and_timestamp(text, inputs, &copy.owner, copy.timestamp);
+            }
+
+            Some(Action::Mkfile(ref mkfile)) => {
+                write!(text, ", mkfile {:?}", mkfile.path).unwrap();
+                render_mode(text, mkfile.mode);
+                render_owner_and_timestamp(text, inputs, &mkfile.owner, mkfile.timestamp);
+
+                write!(text, ", data {:?}", String::from_utf8_lossy(&mkfile.data)).unwrap();
+            }
+
+            Some(Action::Mkdir(ref mkdir)) => {
+                write!(text, ", mkdir {:?}", mkdir.path).unwrap();
+                render_mode(text, mkdir.mode);
+
+                if mkdir.make_parents {
+                    text.push_str(", parents");
+                }
+
+                render_owner_and_timestamp(text, inputs, &mkdir.owner, mkdir.timestamp);
+            }
+
+            Some(Action::Rm(ref rm)) => {
+                write!(text, ", rm {:?}", rm.path).unwrap();
+
+                if rm.allow_not_found {
+                    text.push_str(", allow not found");
+                }
+
+                if rm.allow_wildcard {
+                    text.push_str(", wildcard");
+                }
+            }
+
+            None => {}
+        }
+
+        text.push('\n');
+    }
+}
+
+/// Negative mode means the default one.
+fn render_mode(text: &mut String, mode: i32) {
+    if mode >= 0 {
+        write!(text, " mode {:o}", mode).unwrap();
+    }
+}
+
+fn render_owner_and_timestamp(
+    text: &mut String,
+    inputs: &[String],
+    owner: &Option<pb::ChownOpt>,
+    timestamp: i64,
+) {
+    if let Some(ref owner) = owner {
+        let name =
+            |user: &Option<pb::UserOpt>| match user.as_ref().and_then(|user| user.user.as_ref()) {
+                Some(pb::user_opt::User::ById(id)) => id.to_string(),
+                Some(pb::user_opt::User::ByName(named)) if named.input >= 0 => {
+                    format!(
+                        "{:?} from {}",
+                        named.name,
+                        action_input_name(inputs, named.input)
+                    )
+                }
+                Some(pb::user_opt::User::ByName(named)) => format!("{:?}", named.name),
+                None => "-".into(),
+            };
+
+        write!(text, ", owner {}:{}", name(&owner.user), name(&owner.group)).unwrap();
+    }
+
+    if timestamp >= 0 {
+        write!(text, ", timestamp {}", timestamp).unwrap();
+    }
+}
+
+fn render_build(text: &mut String, inputs: &[String], build: &pb::BuildOp) {
+    writeln!(text, "  builder: {}", input_name(inputs, build.builder)).unwrap();
+
+    for (name, input) in build.inputs.iter().collect::<BTreeMap<_, _>>() {
+        writeln!(
+            text,
+            "  build input {}: {}",
+            name,
+            input_name(inputs, input.input)
+        )
+        .unwrap();
+    }
+
+    if let Some(ref definition) = build.def {
+        writeln!(text, "  definition: {} ops", definition.def.len()).unwrap();
+    }
+
+    for (key, value) in build.attrs.iter().collect::<BTreeMap<_, _>>() {
+        writeln!(text, "  attr {}: {:?}", key, value).unwrap();
+    }
+}
+
+fn render_metadata(text: &mut String, metadata: &pb::OpMetadata) {
+    if metadata.ignore_cache {
+        text.push_str("  ignore cache\n");
+    }
+
+    if let Some(ref export_cache) = metadata.export_cache {
+        writeln!(text, "  export cache: {}", export_cache.value).unwrap();
+    }
+
+    for (key, value) in metadata.description.iter().collect::<BTreeMap<_, _>>() {
+        if key != "llb.customname" {
+            writeln!(text, "  description {}: {:?}", key, value).unwrap();
+        }
+    }
+
+    let caps = {
+        metadata
+            .caps
+            .iter()
+            .filter(|(_, required)| **required)
+            .map(|(id, _)| id.as_str())
+            .collect::<BTreeSet<_>>()
+    };
+
+    if !caps.is_empty() {
+        let caps = caps.into_iter().collect::<Vec<_>>();
+        writeln!(text, "  caps: {}", caps.join(", ")).unwrap();
+    }
+}
+
+fn input_name(inputs: &[String], index: i64) -> String {
+    if index < 0 {
+        return "scratch".into();
+    }
+
+    match inputs.get(index as usize) {
+        Some(input) => input.clone(),
+        None => format!("<invalid input {}>", index),
+    }
+}
+
+/// File actions refer to outputs of previous actions with indices after the op inputs.
+fn action_input_name(inputs: &[String], index: i64) -> String {
+    if index >= 0 && index as usize >= inputs.len() {
+        return format!("action {}", index as usize - inputs.len());
+    }
+
+    input_name(inputs, index)
+}
+
+#[test]
+fn rendering() {
+    use crate::prelude::*;
+
+    let image = Source::image("library/alpine:latest");
+    let command = Command::run("/bin/sh")
+        .args(["-c", "echo hello > /out/greeting"])
+        .env("LANG", "C.UTF-8")
+        .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+        .mount(Mount::Scratch(OutputIdx(0), "/out"))
+        .custom_name("greet");
+
+    let definition = Terminal::with(command.output(0)).into_definition();
+
+    assert_snapshot(&definition, "tests/snapshots/greet.llb");
+}
+
+#[test]
+fn file_actions_rendering() {
+    use crate::prelude::*;
+
+    let builder_image = Source::image("rustlang/rust:nightly");
+
+    let command = Command::run("cargo")
+        .args(["build"])
+        .mount(Mount::ReadOnlyLayer(builder_image.output(), "/"))
+        .mount(Mount::SharedCache("/root/.cargo"))
+        .mount(Mount::Scratch(OutputIdx(0), "/target"));
+
+    let operation = FileSystem::sequence()
+        .append(FileSystem::mkdir(OutputIdx(0), LayerPath::Scratch("/bin")).make_parents(true))
+        .append(
+            FileSystem::copy()
+                .from(LayerPath::Other(command.output(0), "/target/release/crate"))
+                .to(OutputIdx(1), LayerPath::Own(OwnOutputIdx(0), "/bin/crate"))
+                .follow_symlinks(true)
+                .create_path(true),
+        )
+        .append(
+            FileSystem::mkfile(OutputIdx(2), LayerPath::Own(OwnOutputIdx(1), "/etc/motd"))
+                .data(b"hello".to_vec()),
+        );
+
+    let definition = Terminal::with(operation.output(2)).into_definition();
+
+    assert_snapshot(&definition, "tests/snapshots/file-actions.llb");
+}
+
+#[test]
+fn diffing() {
+    let expected = concat!(
+        "#0 terminal\n",
+        "  inputs: #1[0]\n",
+        "\n",
+        "#1 exec\n",
+        "  args: [\"true\"]\n",
+        "  cwd: \"/\"\n",
+    );
+
+    let actual = expected.replace("true", "false");
+
+    assert_eq!(
+        diff(expected, &actual),
+        concat!(
+            "  #0 terminal\n",
+            "  ...\n",
+            "  #1 exec\n",
+            "-   args: [\"true\"]\n",
+            "+   args: [\"false\"]\n",
+            "    cwd: \"/\"\n",
+        )
+    );
+}
@@ -0,0 +1,435 @@
+use std::path::{Path, PathBuf};
+
+use crate::ops::exec::{Command, Mount, Shell};
+use crate::ops::fs::{FileSystem, LayerPath, SequenceOperation};
+use crate::ops::source::Source;
+use crate::ops::{MultiOwnedOutput, OperationBuilder, SingleOwnedOutput};
+use crate::utils::{OperationOutput, OutputIdx, Platform};
+
+/// Chainable build state: the current root filesystem together with environment,
+/// working directory, user and platform that are applied to every command.
+///
+/// Every step produces a new state, so a state can be used as a base for several branches.
+#[derive(Debug, Clone)]
+pub struct State<'a> {
+    root: Option<OperationOutput<'a>>,
+    image: Option<String>,
+
+    env: Vec<(String, String)>,
+    cwd: PathBuf,
+    user: String,
+    shell: Shell,
+    platform: Option<Platform>,
+}
+
+impl<'a> State<'a> {
+    /// Empty state (equivalent of Dockerfile's `FROM scratch`).
+    pub fn scratch() -> Self {
+        Self {
+            root: None,
+            image: None,
+
+            env: vec![],
+            cwd: PathBuf::from("/"),
+            user: "root".into(),
+            shell: Shell::default(),
+            platform: None,
+        }
+    }
+
+    /// State on top of an output of another operation.
+    pub fn with_root(root: OperationOutput<'a>) -> Self {
+        Self {
+            root: Some(root),
+            ..Self::scratch()
+        }
+    }
+
+    /// State on top of a Docker image (equivalent of Dockerfile's `FROM`).
+    /// A platform set right afterwards is used to pick the image from a multi-platform manifest.
+    pub fn image<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+
+        Self {
+            image: Some(name.clone()),
+            ..Self::with_root(Source::image(name).ref_counted().output())
+        }
+    }
+
+    /// Root filesystem of the state. `None` for the empty state.
+    pub fn output(&self) -> Option<OperationOutput<'a>> {
+        self.root.clone()
+    }
+
+    pub fn get_env<S>(&self, name: S) -> Option<&str>
+    where
+        S: AsRef<str>,
+    {
+        self.env
+            .iter()
+            .find(|(key, _)| key == name.as_ref())
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    pub fn get_user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn get_shell(&self) -> &Shell {
+        &self.shell
+    }
+
+    pub fn get_platform(&self) -> Option<&Platform> {
+        self.platform.as_ref()
+    }
+
+    /// Set an environment variable for the next commands (overrides the previous value).
+    pub fn env<S, Q>(mut self, name: S, value: Q) -> Self
+    where
+        S: Into<String>,
+        Q: Into<String>,
+    {
+        let name = name.into();
+        let value = value.into();
+
+        match self.env.iter_mut().find(|(key, _)| *key == name) {
+            Some(existing) => existing.1 = value,
+            None => self.env.push((name, value)),
+        }
+
+        self
+    }
+
+    /// Change the working directory. Relative paths are resolved against the current one.
+    pub fn workdir<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.cwd = self.cwd.join(path);
+        self
+    }
+
+    pub fn user<S>(mut self, user: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.user = user.into();
+        self
+    }
+
+    /// Change the shell used by `run_shell` and `run_script`.
+    pub fn shell(mut self, shell: Shell) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    /// Change the platform of the next operations.
+    /// The image of the state is re-resolved for the platform, unless any operation was already applied on top.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        if let Some(ref name) = self.image {
+            let image = Source::image(name.as_str()).with_platform(platform.clone());
+            self.root = Some(image.ref_counted().output());
+        }
+
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Run a command (exec form) and return the state with the modified root filesystem.
+    pub fn run<S, A, T>(&self, name: S, args: A) -> Self
+    where
+        S: Into<String>,
+        A: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.run_with(name, |command| command.args(args))
+    }
+
+    /// Run a command that can be customized (arguments, additional mounts, name, etc.) with the `configure` closure.
+    /// The root filesystem is mounted as a writable layer with `OutputIdx(0)`.
+    pub fn run_with<S, F>(&self, name: S, configure: F) -> Self
+    where
+        S: Into<String>,
+        F: FnOnce(Command<'a>) -> Command<'a>,
+    {
+        self.run_command(Command::run(name), configure)
+    }
+
+    /// Run a command (shell form) with the shell of the state.
+    pub fn run_shell<S>(&self, script: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.run_command(self.shell.command(script), |command| command)
+    }
+
+    /// Run the lines as a single `set -eu` script with the shell of the state.
+    pub fn run_script<I, S>(&self, lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.run_command(self.shell.script(lines), |command| command)
+    }
+
+    fn run_command<F>(&self, command: Command<'a>, configure: F) -> Self
+    where
+        F: FnOnce(Command<'a>) -> Command<'a>,
+    {
+        let mut command = {
+            command
+                .env_iter(self.env.iter().map(|(key, value)| (key, value)))
+                .cwd(&self.cwd)
+                .user(self.user.clone())
+        };
+
+        if let Some(ref platform) = self.platform {
+            command = command.platform(platform.clone());
+        }
+
+        command = match self.root {
+            Some(ref root) => command.mount(Mount::Layer(OutputIdx(0), root.clone(), "/")),
+            None => command.mount(Mount::Scratch(OutputIdx(0), "/")),
+        };
+
+        self.with_new_root(configure(command).ref_counted().output(0))
+    }
+
+    /// Copy a path from another state. Relative paths are resolved against working directories of the states.
+    pub fn copy_from<P, Q>(&self, source: &State<'a>, from: P, to: Q) -> Self
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let from = source.cwd.join(from);
+        let to = self.cwd.join(to);
+
+        let from = match source.root {
+            Some(ref root) => LayerPath::Other(root.clone(), from),
+            None => LayerPath::Scratch(from),
+        };
+
+        let operation = {
+            FileSystem::copy()
+                .from(from)
+                .to(OutputIdx(0), self.layer_path(to))
+                .create_path(true)
+                .into_operation()
+        };
+
+        self.with_file_operation(operation)
+    }
+
+    /// Create a directory (with its parents). Relative paths are resolved against the working directory.
+    pub fn mkdir<P>(&self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.cwd.join(path);
+        let operation = {
+            FileSystem::mkdir(OutputIdx(0), self.layer_path(path))
+                .make_parents(true)
+                .into_operation()
+        };
+
+        self.with_file_operation(operation)
+    }
+
+    /// Create a file with the contents. Relative paths are resolved against the working directory.
+    pub fn mkfile<P>(&self, path: P, data: Vec<u8>) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.cwd.join(path);
+        let operation = {
+            FileSystem::mkfile(OutputIdx(0), self.layer_path(path))
+                .data(data)
+                .into_operation()
+        };
+
+        self.with_file_operation(operation)
+    }
+
+    fn layer_path(&self, path: PathBuf) -> LayerPath<'a, PathBuf> {
+        match self.root {
+            Some(ref root) => LayerPath::Other(root.clone(), path),
+            None => LayerPath::Scratch(path),
+        }
+    }
+
+    fn with_file_operation(&self, mut operation: SequenceOperation<'a>) -> Self {
+        if let Some(ref platform) = self.platform {
+            operation = operation.platform(platform.clone());
+        }
+
+        self.with_new_root(operation.ref_counted().output(0))
+    }
+
+    fn with_new_root(&self, root: OperationOutput<'a>) -> Self {
+        Self {
+            root: Some(root),
+            image: None,
+
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+            user: self.user.clone(),
+            shell: self.shell.clone(),
+            platform: self.platform.clone(),
+        }
+    }
+}
+
+#[test]
+fn run_serialization() {
+    use crate::prelude::*;
+
+    let state = {
+        State::image("library/alpine:latest")
+            .env("PATH", "/bin")
+            .env("HOME", "/root")
+            .env("PATH", "/usr/bin")
+            .workdir("/src")
+            .workdir("crate")
+            .user("builder")
+            .platform(Platform::new("linux", "arm64"))
+            .run("cargo", ["build"])
+    };
+
+    assert_eq!(state.get_env("PATH"), Some("/usr/bin"));
+    assert_eq!(state.get_cwd(), Path::new("/src/crate"));
+    assert_eq!(state.get_user(), "builder");
+
+    let state_definition = Terminal::with(state.output().unwrap()).into_definition();
+
+    let image =
+        Source::image("library/alpine:latest").with_platform(Platform::new("linux", "arm64"));
+    let command = {
+        Command::run("cargo")
+            .env("PATH", "/usr/bin")
+            .env("HOME", "/root")
+            .cwd("/src/crate")
+            .user("builder")
+            .platform(Platform::new("linux", "arm64"))
+            .mount(Mount::Layer(OutputIdx(0), image.output(), "/"))
+            .args(["build"])
+    };
+
+    let manual_definition = Terminal::with(command.output(0)).into_definition();
+
+    assert_eq!(state_definition.def, manual_definition.def);
+}
+
+#[test]
+fn fs_serialization() {
+    use crate::prelude::*;
+
+    let context = State::with_root(Source::local("context").ref_counted().output());
+    let state = {
+        State::scratch().workdir("/app").mkdir("bin").copy_from(
+            &context,
+            "Cargo.toml",
+            "Cargo.toml",
+        )
+    };
+
+    let state_definition = Terminal::with(state.output().unwrap()).into_definition();
+
+    let context = Source::local("context");
+    let mkdir = FileSystem::mkdir(OutputIdx(0), LayerPath::Scratch("/app/bin"))
+        .make_parents(true)
+        .into_operation();
+
+    let copy = FileSystem::copy()
+        .from(LayerPath::Other(context.output(), "/Cargo.toml"))
+        .to(
+            OutputIdx(0),
+            LayerPath::Other(mkdir.output(0), "/app/Cargo.toml"),
+        )
+        .create_path(true)
+        .into_operation();
+
+    let manual_definition = Terminal::with(copy.output(0)).into_definition();
+
+    assert_eq!(state_definition.def, manual_definition.def);
+}
+
+#[test]
+fn shell_serialization() {
+    use crate::prelude::*;
+
+    let state = {
+        State::image("library/alpine:latest")
+            .shell(Shell::new(vec!["/bin/bash", "-o", "pipefail", "-c"]).unwrap())
+            .run_script(vec!["cd /src", "make"])
+    };
+
+    let state_definition = Terminal::with(state.output().unwrap()).into_definition();
+
+    let image = Source::image("library/alpine:latest");
+    let command = {
+        Command::run("/bin/bash")
+            .args(["-o", "pipefail", "-c", "set -eu\ncd /src\nmake"])
+            .mount(Mount::Layer(OutputIdx(0), image.output(), "/"))
+    };
+
+    let manual_definition = Terminal::with(command.output(0)).into_definition();
+
+    assert_eq!(state_definition.def, manual_definition.def);
+}
+
+#[test]
+fn platform_serialization() {
+    use crate::inspect::DefinitionGraph;
+    use crate::prelude::*;
+
+    let arm = Platform::new("linux", "arm").with_variant("v7");
+
+    let platform_of = |state: &State| {
+        let definition = Terminal::with(state.output().unwrap()).into_definition();
+        let graph = DefinitionGraph::decode(&definition).unwrap();
+        let root = &graph.vertices()[1];
+
+        (
+            root.op.platform.clone().map(Platform::from),
+            root.metadata.caps.contains_key("platform"),
+        )
+    };
+
+    let image = State::image("library/alpine:latest").platform(arm.clone());
+    assert_eq!(platform_of(&image), (Some(arm.clone()), true));
+
+    let image_definition = Terminal::with(image.output().unwrap()).into_definition();
+    let manual_image = Source::image("library/alpine:latest").with_platform(arm.clone());
+    let manual_definition = Terminal::with(manual_image.output()).into_definition();
+    assert_eq!(image_definition.def, manual_definition.def);
+
+    let exec = image.run("make", ["all"]);
+    assert_eq!(platform_of(&exec), (Some(arm.clone()), true));
+
+    let mkdir = image.mkdir("/out");
+    assert_eq!(platform_of(&mkdir), (Some(arm.clone()), true));
+
+    let mkfile = image.mkfile("/out/file", b"data".to_vec());
+    assert_eq!(platform_of(&mkfile), (Some(arm.clone()), true));
+
+    let copy = State::scratch()
+        .platform(arm.clone())
+        .copy_from(&exec, "/out", "/out");
+    assert_eq!(platform_of(&copy), (Some(arm.clone()), true));
+
+    let image = State::image("library/alpine:latest");
+    assert_eq!(platform_of(&image), (None, false));
+    assert_eq!(platform_of(&image.mkdir("/out")), (None, false));
+
+    // The image is kept as is once there are operations on top of it.
+    let late = image.mkdir("/out").platform(arm.clone());
+    assert_eq!(platform_of(&late), (None, false));
+    assert_eq!(platform_of(&late.run("make", ["all"])), (Some(arm), true));
+}
@@ -1,5 +1,9 @@
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use buildkit_proto::pb;
+
 use crate::serialization::Operation;
 
 #[derive(Copy, Clone, Debug)]
@@ -35,7 +39,7 @@ impl<'a> OperationOutput<'a> {
     pub(crate) fn operation(&self) -> &dyn Operation {
         match self.kind {
             OperationOutputKind::Owned(ref op, ..) => op.as_ref(),
-            OperationOutputKind::Borrowed(ref op, ..) => *op,
+            OperationOutputKind::Borrowed(op, ..) => op,
         }
     }
 
@@ -48,50 +52,176 @@ impl<'a> OperationOutput<'a> {
     }
 }
 
-impl Into<i64> for OutputIdx {
-    fn into(self) -> i64 {
-        self.0.into()
+impl From<OutputIdx> for i64 {
+    fn from(idx: OutputIdx) -> Self {
+        idx.0.into()
+    }
+}
+impl From<&OutputIdx> for i64 {
+    fn from(idx: &OutputIdx) -> Self {
+        idx.0.into()
+    }
+}
+
+impl From<OwnOutputIdx> for i64 {
+    fn from(idx: OwnOutputIdx) -> Self {
+        idx.0.into()
+    }
+}
+impl From<&OwnOutputIdx> for i64 {
+    fn from(idx: &OwnOutputIdx) -> Self {
+        idx.0.into()
+    }
+}
+
+impl From<OutputIdx> for i32 {
+    fn from(idx: OutputIdx) -> Self {
+        idx.0 as i32
     }
 }
-impl Into<i64> for &OutputIdx {
-    fn into(self) -> i64 {
-        self.0.into()
+impl From<&OutputIdx> for i32 {
+    fn from(idx: &OutputIdx) -> Self {
+        idx.0 as i32
     }
 }
 
-impl Into<i64> for OwnOutputIdx {
-    fn into(self) -> i64 {
-        self.0.into()
+impl From<OwnOutputIdx> for i32 {
+    fn from(idx: OwnOutputIdx) -> Self {
+        idx.0 as i32
     }
 }
-impl Into<i64> for &OwnOutputIdx {
-    fn into(self) -> i64 {
-        self.0.into()
+impl From<&OwnOutputIdx> for i32 {
+    fn from(idx: &OwnOutputIdx) -> Self {
+        idx.0 as i32
+    }
+}
+
+/// Target platform of an operation, e.g. `linux/amd64` or `linux/arm/v7`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Platform {
+    pub os: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+}
+
+/// Error returned when a platform string doesn't look like `os/arch[/variant]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsePlatformError(String);
+
+impl Platform {
+    pub fn new<S, Q>(os: S, architecture: Q) -> Self
+    where
+        S: Into<String>,
+        Q: Into<String>,
+    {
+        Self {
+            os: os.into(),
+            architecture: architecture.into(),
+            variant: None,
+        }
+    }
+
+    pub fn with_variant<S>(mut self, variant: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.variant = Some(variant.into());
+        self
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+
+        if let Some(ref variant) = self.variant {
+            write!(f, "/{}", variant)?;
+        }
+
+        Ok(())
     }
 }
 
-impl Into<i32> for OutputIdx {
-    fn into(self) -> i32 {
-        self.0 as i32
+impl FromStr for Platform {
+    type Err = ParsePlatformError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parts = value.trim().split('/').collect::<Vec<_>>();
+
+        match parts.as_slice() {
+            [os, architecture] if !os.is_empty() && !architecture.is_empty() => {
+                Ok(Platform::new(*os, *architecture))
+            }
+
+            [os, architecture, variant]
+                if !os.is_empty() && !architecture.is_empty() && !variant.is_empty() =>
+            {
+                Ok(Platform::new(*os, *architecture).with_variant(*variant))
+            }
+
+            _ => Err(ParsePlatformError(value.into())),
+        }
     }
 }
-impl Into<i32> for &OutputIdx {
-    fn into(self) -> i32 {
-        self.0 as i32
+
+impl fmt::Display for ParsePlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid platform '{}', expected 'os/arch[/variant]'",
+            self.0
+        )
     }
 }
 
-impl Into<i32> for OwnOutputIdx {
-    fn into(self) -> i32 {
-        self.0 as i32
+impl std::error::Error for ParsePlatformError {}
+
+impl From<Platform> for pb::Platform {
+    fn from(platform: Platform) -> Self {
+        pb::Platform {
+            os: platform.os,
+            architecture: platform.architecture,
+            variant: platform.variant.unwrap_or_default(),
+
+            ..Default::default()
+        }
     }
 }
-impl Into<i32> for &OwnOutputIdx {
-    fn into(self) -> i32 {
-        self.0 as i32
+
+impl From<pb::Platform> for Platform {
+    fn from(platform: pb::Platform) -> Self {
+        Self {
+            os: platform.os,
+            architecture: platform.architecture,
+            variant: Some(platform.variant).filter(|variant| !variant.is_empty()),
+        }
     }
 }
 
+#[test]
+fn platform_parsing() {
+    assert_eq!(
+        "linux/amd64".parse::<Platform>(),
+        Ok(Platform::new("linux", "amd64"))
+    );
+
+    assert_eq!(
+        "linux/arm/v7".parse::<Platform>(),
+        Ok(Platform::new("linux", "arm").with_variant("v7"))
+    );
+
+    assert!("linux".parse::<Platform>().is_err());
+    assert!("linux/".parse::<Platform>().is_err());
+    assert!("linux/arm/v7/extra".parse::<Platform>().is_err());
+
+    assert_eq!(
+        Platform::new("linux", "arm64")
+            .with_variant("v8")
+            .to_string(),
+        "linux/arm64/v8"
+    );
+}
+
 #[cfg(test)]
 pub mod test {
     #[macro_export]
@@ -99,12 +229,12 @@ pub mod test {
         ($op:expr, $(|$name:ident| $value:expr,)*) => ($crate::check_op!($op, $(|$name| $value),*));
         ($op:expr, $(|$name:ident| $value:expr),*) => {{
             #[allow(unused_imports)]
-            use crate::serialization::{Context, Operation};
+            use $crate::serialization::{Context, Operation};
 
             let mut context = Context::default();
             let serialized = $op.serialize(&mut context).unwrap();
 
-            $(crate::check_op_property!(serialized, context, $name, $value));*
+            $($crate::check_op_property!(serialized, context, $name, $value));*
         }};
     }
 
@@ -142,15 +272,15 @@ pub mod test {
             );
         }};
 
-        ($serialized:expr, $context:expr, cached_tail, $value:expr) => {
+        ($serialized:expr, $context:expr, cached_tail, $value:expr) => {{
             assert_eq!(
                 $context
                     .registered_nodes_iter()
                     .map(|node| node.digest.clone())
                     .collect::<Vec<_>>(),
-                crate::utils::test::to_vec($value),
+                $crate::utils::test::to_vec($value),
             );
-        };
+        }};
 
         ($serialized:expr, $context:expr, caps, $value:expr) => {{
             let mut caps = $serialized
@@ -161,19 +291,26 @@ pub mod test {
                 .collect::<Vec<_>>();
 
             caps.sort();
-            assert_eq!(caps, crate::utils::test::to_vec($value));
+            assert_eq!(caps, $crate::utils::test::to_vec($value));
         }};
 
-        ($serialized:expr, $context:expr, description, $value:expr) => {
+        ($serialized:expr, $context:expr, description, $value:expr) => {{
             assert_eq!(
                 $serialized.metadata.description,
-                crate::utils::test::to_map($value),
+                $crate::utils::test::to_map($value),
             );
-        };
+        }};
 
-        ($serialized:expr, $context:expr, digest, $value:expr) => {
+        ($serialized:expr, $context:expr, digest, $value:expr) => {{
             assert_eq!($serialized.digest, $value);
-        };
+        }};
+
+        ($serialized:expr, $context:expr, export_cache, $value:expr) => {{
+            assert_eq!(
+                $serialized.metadata.export_cache.map(|export| export.value),
+                $value
+            );
+        }};
     }
 
     use std::collections::HashMap;
@@ -1,11 +1,24 @@
-const DEFS: &[&str] = &["proto/github.com/moby/buildkit/frontend/gateway/pb/gateway.proto"];
+const DEFS: &[&str] = &[
+    "proto/github.com/moby/buildkit/frontend/gateway/pb/gateway.proto",
+    "proto/github.com/moby/buildkit/api/services/control/control.proto",
+    "proto/github.com/moby/buildkit/session/filesync/filesync.proto",
+    "proto/github.com/moby/buildkit/session/secrets/secrets.proto",
+    "proto/github.com/moby/buildkit/session/sshforward/ssh.proto",
+    "proto/github.com/grpc/grpc-proto/grpc/health/v1/health.proto",
+];
 const PATHS: &[&str] = &["proto"];
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::configure()
+    let mut builder = tonic_build::configure()
         .build_client(true)
-        .build_server(false)
-        .compile(DEFS, PATHS)?;
+        .build_server(true);
+
+    // LLB types can be serialized, e.g. to dump definitions as JSON.
+    if std::env::var_os("CARGO_FEATURE_SERDE").is_some() {
+        builder = builder.type_attribute(".pb", "#[derive(serde::Serialize)]");
+    }
+
+    builder.compile(DEFS, PATHS)?;
 
     Ok(())
 }
@@ -0,0 +1,29 @@
+//! Bits of Go's `os.FileMode`, which fsutil and the gateway use for `Stat::mode`.
+
+pub const MODE_DIR: u32 = 1 << 31;
+pub const MODE_APPEND: u32 = 1 << 30;
+pub const MODE_EXCLUSIVE: u32 = 1 << 29;
+pub const MODE_TEMPORARY: u32 = 1 << 28;
+pub const MODE_SYMLINK: u32 = 1 << 27;
+pub const MODE_DEVICE: u32 = 1 << 26;
+pub const MODE_NAMED_PIPE: u32 = 1 << 25;
+pub const MODE_SOCKET: u32 = 1 << 24;
+pub const MODE_SETUID: u32 = 1 << 23;
+pub const MODE_SETGID: u32 = 1 << 22;
+pub const MODE_CHAR_DEVICE: u32 = 1 << 21;
+pub const MODE_STICKY: u32 = 1 << 20;
+pub const MODE_IRREGULAR: u32 = 1 << 19;
+
+/// Mask of the file type bits.
+pub const MODE_TYPE: u32 = {
+    MODE_DIR
+        | MODE_SYMLINK
+        | MODE_NAMED_PIPE
+        | MODE_SOCKET
+        | MODE_DEVICE
+        | MODE_CHAR_DEVICE
+        | MODE_IRREGULAR
+};
+
+/// Mask of the Unix permission bits.
+pub const MODE_PERM: u32 = 0o777;
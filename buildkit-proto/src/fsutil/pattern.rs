@@ -0,0 +1,72 @@
+//! Docker-style path patterns: `*` and `?` within a path component and `**` for any number of components.
+
+/// Whether the path components match the pattern components.
+pub fn glob_match<P, S>(pattern: &[P], path: &[S]) -> bool
+where
+    P: AsRef<str>,
+    S: AsRef<str>,
+{
+    match pattern.split_first() {
+        None => path.is_empty(),
+
+        Some((head, tail)) if head.as_ref() == "**" => {
+            glob_match(tail, path) || (!path.is_empty() && glob_match(pattern, &path[1..]))
+        }
+
+        Some((head, tail)) => match path.split_first() {
+            Some((name, rest)) => {
+                segment_match(head.as_ref(), name.as_ref()) && glob_match(tail, rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_match(pattern: &str, name: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    let mut name_chars = name.chars();
+
+    match pattern_chars.next() {
+        None => name.is_empty(),
+
+        Some('*') => {
+            segment_match(pattern_chars.as_str(), name)
+                || (name_chars.next().is_some() && segment_match(pattern, name_chars.as_str()))
+        }
+
+        Some('?') => {
+            name_chars.next().is_some()
+                && segment_match(pattern_chars.as_str(), name_chars.as_str())
+        }
+
+        Some(expected) => {
+            name_chars.next() == Some(expected)
+                && segment_match(pattern_chars.as_str(), name_chars.as_str())
+        }
+    }
+}
+
+#[test]
+fn matching() {
+    let check = |pattern: &str, path: &str| {
+        glob_match(
+            &pattern.split('/').collect::<Vec<_>>(),
+            &path.split('/').collect::<Vec<_>>(),
+        )
+    };
+
+    assert!(check("**/*.toml", "Cargo.toml"));
+    assert!(check("**/*.toml", "crates/core/Cargo.toml"));
+    assert!(check("src/**", "src"));
+    assert!(check("src/*/mod.rs", "src/options/mod.rs"));
+    assert!(!check("src/*/mod.rs", "src/mod.rs"));
+    assert!(!check("src/*/mod.rs", "src/a/b/mod.rs"));
+
+    assert!(check("*.rs", "main.rs"));
+    assert!(check("*", ""));
+    assert!(!check("*.rs", "main.rs.orig"));
+
+    assert!(check("dat?.json", "data.json"));
+    assert!(check("??.txt", "ñé.txt"));
+    assert!(!check("?.txt", ".txt"));
+}
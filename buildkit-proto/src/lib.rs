@@ -3,6 +3,8 @@
 pub mod moby {
     pub mod buildkit {
         pub mod v1 {
+            include!(concat!(env!("OUT_DIR"), "/moby.buildkit.v1.rs"));
+
             pub mod frontend {
                 include!(concat!(env!("OUT_DIR"), "/moby.buildkit.v1.frontend.rs"));
             }
@@ -15,9 +17,37 @@ pub mod moby {
                 include!(concat!(env!("OUT_DIR"), "/moby.buildkit.v1.types.rs"));
             }
         }
+
+        pub mod secrets {
+            pub mod v1 {
+                include!(concat!(env!("OUT_DIR"), "/moby.buildkit.secrets.v1.rs"));
+            }
+        }
+    }
+
+    pub mod filesync {
+        pub mod v1 {
+            include!(concat!(env!("OUT_DIR"), "/moby.filesync.v1.rs"));
+        }
+    }
+
+    pub mod sshforward {
+        pub mod v1 {
+            include!(concat!(env!("OUT_DIR"), "/moby.sshforward.v1.rs"));
+        }
+    }
+}
+
+#[allow(clippy::all)]
+pub mod grpc {
+    pub mod health {
+        pub mod v1 {
+            include!(concat!(env!("OUT_DIR"), "/grpc.health.v1.rs"));
+        }
     }
 }
 
+#[allow(clippy::all)]
 pub mod google {
     pub mod rpc {
         include!(concat!(env!("OUT_DIR"), "/google.rpc.rs"));
@@ -32,4 +62,7 @@ pub mod fsutil {
     pub mod types {
         include!(concat!(env!("OUT_DIR"), "/fsutil.types.rs"));
     }
+
+    pub mod mode;
+    pub mod pattern;
 }
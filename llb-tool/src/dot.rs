@@ -0,0 +1,65 @@
+use std::io::{self, Write};
+
+use buildkit_llb::diff::describe;
+use buildkit_llb::inspect::DefinitionGraph;
+
+/// Print the graph in Graphviz format. Edges point from inputs to the operations using them.
+pub fn write<W: Write>(output: &mut W, graph: &DefinitionGraph) -> io::Result<()> {
+    writeln!(output, "digraph {{")?;
+
+    for vertex in graph.vertices() {
+        writeln!(
+            output,
+            "  {:?} [label={:?}];",
+            vertex.digest,
+            describe(vertex)
+        )?;
+    }
+
+    for vertex in graph.vertices() {
+        for (position, index) in graph.inputs(vertex) {
+            writeln!(
+                output,
+                "  {:?} -> {:?} [label=\"{}\"];",
+                graph.vertices()[position].digest,
+                vertex.digest,
+                index
+            )?;
+        }
+    }
+
+    writeln!(output, "}}")
+}
+
+#[test]
+fn graphviz() {
+    use buildkit_llb::prelude::*;
+
+    let image = Source::image("library/alpine:latest");
+    let command = Command::run("/bin/true")
+        .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+        .mount(Mount::Scratch(OutputIdx(0), "/out"))
+        .custom_name("check");
+
+    let graph =
+        { DefinitionGraph::decode(&Terminal::with(command.output(0)).into_definition()).unwrap() };
+
+    let mut output = vec![];
+    write(&mut output, &graph).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let digest = |position: usize| graph.vertices()[position].digest.clone();
+
+    assert!(output.starts_with("digraph {\n"));
+    assert!(output.contains(&format!("{:?} [label=\"exec 'check'\"];", digest(1))));
+    assert!(output.contains(&format!(
+        "{:?} -> {:?} [label=\"0\"];",
+        digest(2),
+        digest(1)
+    )));
+    assert!(output.contains(&format!(
+        "{:?} -> {:?} [label=\"0\"];",
+        digest(1),
+        digest(0)
+    )));
+}
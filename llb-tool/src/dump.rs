@@ -0,0 +1,34 @@
+use std::io::Write;
+
+use buildkit_llb::inspect::DefinitionGraph;
+use buildkit_proto::pb;
+use failure::Error;
+use serde::Serialize;
+
+/// Line of the dump with the same fields `buildctl debug dump-llb` prints.
+///
+/// Values use the protobuf serde encoding: enums are numbers, bytes are number arrays
+/// and field names are in snake case, so the output is not byte-compatible with `buildctl`.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Entry<'a> {
+    op: &'a pb::Op,
+    digest: &'a str,
+    op_metadata: &'a pb::OpMetadata,
+}
+
+/// Print every operation as a JSON line, dependencies first and the terminal last.
+pub fn write<W: Write>(output: &mut W, graph: &DefinitionGraph) -> Result<(), Error> {
+    for vertex in graph.vertices().iter().rev() {
+        let entry = Entry {
+            op: &vertex.op,
+            digest: &vertex.digest,
+            op_metadata: &vertex.metadata,
+        };
+
+        serde_json::to_writer(&mut *output, &entry)?;
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+#![deny(warnings)]
+#![deny(clippy::all)]
+
+use std::env;
+use std::fs;
+use std::io::{stdin, stdout, Read, Write};
+use std::process::exit;
+
+use buildkit_llb::diff::diff_definitions;
+use buildkit_llb::inspect::DefinitionGraph;
+use buildkit_proto::pb;
+use failure::{bail, Error, ResultExt};
+use prost::Message;
+
+mod dot;
+mod dump;
+mod stats;
+mod validate;
+
+const USAGE: &str = "\
+Usage: llb-tool <command> [definition]
+
+Commands:
+    dump [definition]        Print operations as JSON lines
+    dot [definition]         Print the graph in Graphviz format
+    stats [definition]       Print operation counts, depth, fan-in/fan-out and used capabilities
+    diff <old> <new>         Explain the difference between two definitions
+    validate [definition]    Check the definition for structural problems
+
+The definition is read from stdin when the path is omitted or `-`.";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    match run(&args) {
+        Ok(code) => exit(code),
+        Err(error) => {
+            eprintln!("error: {}", error);
+
+            for cause in error.iter_causes() {
+                eprintln!("  caused by: {}", cause);
+            }
+
+            exit(2);
+        }
+    }
+}
+
+/// Run the command and return the exit code.
+fn run(args: &[String]) -> Result<i32, Error> {
+    let stdout = stdout();
+    let mut output = stdout.lock();
+
+    let (command, paths) = match args.split_first() {
+        Some((command, paths)) => (command.as_str(), paths),
+        None => bail!("{}", USAGE),
+    };
+
+    match command {
+        "dump" => dump::write(&mut output, &decode(read_single(paths)?)?)?,
+        "dot" => dot::write(&mut output, &decode(read_single(paths)?)?)?,
+        "stats" => stats::Stats::new(&decode(read_single(paths)?)?).write(&mut output)?,
+
+        "diff" => {
+            let (old, new) = match paths {
+                [old, new] => (read(old)?, read(new)?),
+                _ => bail!("{}", USAGE),
+            };
+
+            let differences = diff_definitions(&old, &new)?;
+
+            for difference in &differences {
+                writeln!(output, "{}", difference)?;
+            }
+
+            if !differences.is_empty() {
+                return Ok(1);
+            }
+        }
+
+        "validate" => {
+            let problems = validate::validate(&read_single(paths)?);
+
+            for problem in &problems {
+                writeln!(output, "{}", problem)?;
+            }
+
+            if !problems.is_empty() {
+                return Ok(1);
+            }
+
+            writeln!(output, "definition is valid")?;
+        }
+
+        "help" | "--help" | "-h" => writeln!(output, "{}", USAGE)?,
+        other => bail!("unknown command '{}'\n\n{}", other, USAGE),
+    }
+
+    Ok(0)
+}
+
+fn read_single(paths: &[String]) -> Result<pb::Definition, Error> {
+    match paths {
+        [] => read("-"),
+        [path] => read(path),
+        _ => bail!("{}", USAGE),
+    }
+}
+
+fn read(path: &str) -> Result<pb::Definition, Error> {
+    let bytes = if path == "-" {
+        let mut bytes = vec![];
+        stdin()
+            .read_to_end(&mut bytes)
+            .context("Unable to read stdin")?;
+
+        bytes
+    } else {
+        fs::read(path).with_context(|_| format!("Unable to read '{}'", path))?
+    };
+
+    Ok(pb::Definition::decode(bytes.as_slice())
+        .with_context(|_| format!("Unable to decode the definition from '{}'", path))?)
+}
+
+fn decode(definition: pb::Definition) -> Result<DefinitionGraph, Error> {
+    Ok(DefinitionGraph::decode(&definition)?)
+}
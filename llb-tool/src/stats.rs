@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+
+use buildkit_llb::inspect::DefinitionGraph;
+
+/// Shape of the graph. The terminal is not counted as an operation.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Number of operations of every kind.
+    pub ops: BTreeMap<&'static str, usize>,
+
+    /// Number of operations in the longest chain.
+    pub depth: usize,
+
+    /// Largest number of inputs of a single operation.
+    pub max_fan_in: usize,
+
+    /// Largest number of operations using the same operation as an input.
+    pub max_fan_out: usize,
+
+    /// Capabilities required by the operations.
+    pub caps: BTreeSet<String>,
+}
+
+impl Stats {
+    pub fn new(graph: &DefinitionGraph) -> Self {
+        let vertices = graph.vertices();
+        let mut stats = Stats::default();
+        let mut dependents = vec![HashSet::new(); vertices.len()];
+
+        for (position, vertex) in vertices.iter().enumerate().skip(1) {
+            *stats.ops.entry(vertex.kind()).or_default() += 1;
+
+            let inputs = graph.inputs(vertex).collect::<Vec<_>>();
+            stats.max_fan_in = stats.max_fan_in.max(inputs.len());
+
+            for (input, _) in inputs {
+                dependents[input].insert(position);
+            }
+
+            stats.caps.extend(
+                vertex
+                    .metadata
+                    .caps
+                    .iter()
+                    .filter(|(_, required)| **required)
+                    .map(|(id, _)| id.clone()),
+            );
+        }
+
+        stats.max_fan_out = dependents.iter().map(HashSet::len).max().unwrap_or(0);
+
+        let mut depths = vec![None; vertices.len()];
+
+        stats.depth = depth(graph, 0, &mut depths) - 1;
+        stats
+    }
+
+    pub fn write<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "operations: {}", self.ops.values().sum::<usize>())?;
+
+        for (kind, count) in &self.ops {
+            writeln!(output, "  {}: {}", kind, count)?;
+        }
+
+        writeln!(output, "depth: {}", self.depth)?;
+        writeln!(output, "max fan-in: {}", self.max_fan_in)?;
+        writeln!(output, "max fan-out: {}", self.max_fan_out)?;
+
+        let caps = self.caps.iter().cloned().collect::<Vec<_>>();
+        writeln!(output, "caps: {}", caps.join(", "))
+    }
+}
+
+/// Number of vertices in the longest chain ending with the vertex.
+fn depth(graph: &DefinitionGraph, position: usize, depths: &mut [Option<usize>]) -> usize {
+    if let Some(depth) = depths[position] {
+        return depth;
+    }
+
+    let inputs = graph
+        .inputs(&graph.vertices()[position])
+        .collect::<Vec<_>>();
+    let longest = {
+        inputs
+            .into_iter()
+            .map(|(input, _)| depth(graph, input, depths))
+            .max()
+            .unwrap_or(0)
+    };
+
+    depths[position] = Some(longest + 1);
+    longest + 1
+}
+
+#[test]
+fn graph_stats() {
+    use buildkit_llb::prelude::*;
+
+    let image = Source::image("library/alpine:latest");
+    let context = Source::local("context");
+
+    let compile = |name: &str| {
+        Command::run("/bin/sh")
+            .args(["-c", &format!("build {}", name)])
+            .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+            .mount(Mount::ReadOnlySelector(context.output(), "/context", "src"))
+            .mount(Mount::Scratch(OutputIdx(0), "/out"))
+    };
+
+    let first = compile("first");
+    let second = compile("second");
+
+    let assembly = FileSystem::sequence()
+        .append(
+            FileSystem::copy()
+                .from(LayerPath::Other(first.output(0), "/first"))
+                .to(OutputIdx(0), LayerPath::Scratch("/first")),
+        )
+        .append(
+            FileSystem::copy()
+                .from(LayerPath::Other(second.output(0), "/second"))
+                .to(OutputIdx(1), LayerPath::Own(OwnOutputIdx(0), "/second")),
+        );
+
+    let definition = Terminal::with(assembly.output(1)).into_definition();
+    let stats = Stats::new(&DefinitionGraph::decode(&definition).unwrap());
+
+    assert_eq!(
+        stats,
+        Stats {
+            ops: vec![("exec", 2), ("file", 1), ("source", 2)]
+                .into_iter()
+                .collect(),
+
+            depth: 3,
+            max_fan_in: 2,
+            max_fan_out: 2,
+
+            caps: vec![
+                "exec.mount.bind",
+                "exec.mount.selector",
+                "file.base",
+                "source.image",
+                "source.local",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    );
+}
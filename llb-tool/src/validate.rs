@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use buildkit_llb::diff::describe;
+use buildkit_llb::inspect::{DefinitionGraph, Vertex};
+use buildkit_proto::pb::{self, op::Op};
+
+/// Find structural problems BuildKit would reject or that point to a bug in the graph generator.
+pub fn validate(definition: &pb::Definition) -> Vec<String> {
+    let graph = match DefinitionGraph::decode(definition) {
+        Ok(graph) => graph,
+        Err(error) => return vec![error.to_string()],
+    };
+
+    let mut problems = vec![];
+    let mut digests = HashSet::new();
+
+    for vertex in graph.vertices() {
+        if !digests.insert(vertex.digest.as_str()) {
+            problems.push(format!("operation {} is duplicated", vertex.digest));
+        }
+    }
+
+    for digest in definition.metadata.keys() {
+        if !digests.contains(digest.as_str()) {
+            problems.push(format!("metadata refers to unknown operation {}", digest));
+        }
+    }
+
+    let terminal = graph.terminal();
+
+    if terminal.op.op.is_some() || terminal.op.inputs.len() != 1 {
+        problems.push("the last operation is not a terminal with a single input".into());
+    }
+
+    let reachable = reachable(&graph);
+
+    for (position, vertex) in graph.vertices().iter().enumerate().skip(1) {
+        let name = format!("{} ({})", describe(vertex), vertex.digest);
+
+        if !reachable.contains(&position) {
+            problems.push(format!("{} is not used by the terminal", name));
+        }
+
+        for problem in validate_op(vertex) {
+            problems.push(format!("{}: {}", name, problem));
+        }
+    }
+
+    problems
+}
+
+fn validate_op(vertex: &Vertex) -> Vec<String> {
+    let inputs = vertex.op.inputs.len() as i64;
+    let mut problems = vec![];
+
+    match vertex.op.op {
+        Some(Op::Exec(ref exec)) => {
+            if exec
+                .meta
+                .as_ref()
+                .map(|meta| meta.args.is_empty())
+                .unwrap_or(true)
+            {
+                problems.push("command has no arguments".into());
+            }
+
+            if !exec.mounts.iter().any(|mount| mount.dest == "/") {
+                problems.push("there is no root mount".into());
+            }
+
+            let mut outputs = HashSet::new();
+
+            for mount in &exec.mounts {
+                if mount.input < -1 || mount.input >= inputs {
+                    problems.push(format!(
+                        "mount '{}' refers to missing input {}",
+                        mount.dest, mount.input
+                    ));
+                }
+
+                if mount.output >= 0 && !outputs.insert(mount.output) {
+                    problems.push(format!(
+                        "mount '{}' reuses output {}",
+                        mount.dest, mount.output
+                    ));
+                }
+            }
+        }
+
+        Some(Op::File(ref file)) => {
+            for (index, action) in file.actions.iter().enumerate() {
+                // Actions can use outputs of the previous actions after the op inputs.
+                let available = inputs + index as i64;
+
+                for input in &[action.input, action.secondary_input] {
+                    if *input < -1 || *input >= available {
+                        problems.push(format!(
+                            "action {} refers to missing input {}",
+                            index, input
+                        ));
+                    }
+                }
+
+                if action.action.is_none() {
+                    problems.push(format!("action {} is empty", index));
+                }
+            }
+        }
+
+        Some(Op::Source(ref source)) => {
+            if source.identifier.is_empty() {
+                problems.push("source has no identifier".into());
+            }
+        }
+
+        Some(Op::Build(_)) => {}
+        None => problems.push("only the terminal can have no operation".into()),
+    }
+
+    problems
+}
+
+fn reachable(graph: &DefinitionGraph) -> HashSet<usize> {
+    let mut visited = HashSet::new();
+    let mut pending = vec![0];
+
+    while let Some(position) = pending.pop() {
+        if visited.insert(position) {
+            pending.extend(
+                graph
+                    .inputs(&graph.vertices()[position])
+                    .map(|(input, _)| input),
+            );
+        }
+    }
+
+    visited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buildkit_llb::prelude::*;
+    use buildkit_proto::pb::Input;
+    use prost::Message;
+
+    #[test]
+    fn valid_definition() {
+        let image = Source::image("library/alpine:latest");
+        let command = Command::run("/bin/true")
+            .mount(Mount::ReadOnlyLayer(image.output(), "/"))
+            .mount(Mount::Scratch(OutputIdx(0), "/out"));
+
+        let definition = Terminal::with(command.output(0)).into_definition();
+        assert_eq!(validate(&definition), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rootless_command() {
+        let command = Command::run("/bin/true").mount(Mount::Scratch(OutputIdx(0), "/out"));
+        let definition = Terminal::with(command.output(0)).into_definition();
+
+        assert_eq!(validate(&definition).len(), 1);
+        assert!(validate(&definition)[0].ends_with("there is no root mount"));
+    }
+
+    #[test]
+    fn unused_operation() {
+        let image = Source::image("library/alpine:latest");
+        let mut definition = Terminal::with(image.output()).into_definition();
+
+        let mut bytes = vec![];
+        pb::Op {
+            op: Some(Op::Source(pb::SourceOp {
+                identifier: "local://context".into(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .encode(&mut bytes)
+        .unwrap();
+
+        // The terminal has to stay the last one.
+        definition.def.insert(0, bytes);
+
+        assert_eq!(validate(&definition).len(), 1);
+        assert!(validate(&definition)[0].ends_with("is not used by the terminal"));
+    }
+
+    #[test]
+    fn missing_input() {
+        let mut definition = pb::Definition::default();
+        let mut bytes = vec![];
+
+        pb::Op {
+            inputs: vec![Input {
+                digest: "sha256:missing".into(),
+                index: 0,
+            }],
+            ..Default::default()
+        }
+        .encode(&mut bytes)
+        .unwrap();
+
+        definition.def.push(bytes);
+
+        assert_eq!(validate(&definition).len(), 1);
+        assert!(validate(&definition)[0].contains("refers to unknown input sha256:missing"));
+    }
+}